@@ -0,0 +1,139 @@
+// Recording a run as an asciinema cast (`--asciicast path.cast`), so it can be
+// replayed with `asciinema play` or shared online.
+//
+// The format (asciicast v2) is plain text: a JSON header line with the terminal
+// size, then one JSON line per chunk of output: `[seconds, "o", "text"]`.
+// `Tee` sits between the game and the terminal: everything drawn goes to the
+// terminal as usual, and a copy goes into the cast with a timestamp.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+// An open cast file.
+pub struct Cast {
+    out: BufWriter<File>,
+    // When the recording started; event times are counted from here.
+    started: Instant,
+    // Bytes drawn since the last flush, written out as one event per frame.
+    pending: Vec<u8>,
+}
+
+impl Cast {
+    // Creates (or overwrites) the cast file and writes the header for a
+    // `width` x `height` terminal.
+    pub fn create(path: &Path, width: u16, height: u16) -> io::Result<Cast> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        // `json!` builds a JSON value from JSON-looking Rust code.
+        let header = serde_json::json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": timestamp,
+        });
+        writeln!(out, "{}", header)?;
+        Ok(Cast {
+            out,
+            started: Instant::now(),
+            pending: Vec::new(),
+        })
+    }
+
+    // Writes everything drawn since the last call as one output event.
+    fn write_event(&mut self) -> io::Result<()> {
+        // A frame can end halfway through a multi-byte character (like "█").
+        // Only the complete characters go out now; the rest waits for the next frame.
+        let complete = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            Err(err) => err.valid_up_to(),
+        };
+        if complete == 0 {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&self.pending[..complete]);
+        let event = (self.started.elapsed().as_secs_f64(), "o", text);
+        // Serializing a number and two strings can't fail, so `expect` is safe here.
+        let json = serde_json::to_string(&event).expect("an event is always valid JSON");
+        writeln!(self.out, "{}", json)?;
+        self.pending.drain(..complete);
+        self.out.flush()
+    }
+}
+
+// A writer that passes everything on to `inner` and, if recording, to a cast too.
+// Being generic over `W` means tests can use a `Vec<u8>` instead of the terminal.
+pub struct Tee<W: Write> {
+    inner: W,
+    cast: Option<Cast>,
+}
+
+impl<W: Write> Tee<W> {
+    pub fn new(inner: W, cast: Option<Cast>) -> Tee<W> {
+        Tee { inner, cast }
+    }
+}
+
+// Implementing `Write` lets crossterm's `queue!`/`execute!` draw through a `Tee`
+// exactly as they would through `io::stdout()`.
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(cast) = &mut self.cast {
+            // Copy only what the terminal actually took, so both see the same bytes.
+            cast.pending.extend_from_slice(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    // The game flushes once per frame, so each flush becomes one event.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        if let Some(cast) = &mut self.cast {
+            cast.write_event()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_each_flush_as_an_event() {
+        let path = std::env::temp_dir().join(format!("hello_rust_{}.cast", std::process::id()));
+        let cast = Cast::create(&path, 80, 24).unwrap();
+        let mut tee = Tee::new(Vec::new(), Some(cast));
+        write!(tee, "hello ").unwrap();
+        tee.flush().unwrap();
+        // "█" is three bytes; split it across two frames.
+        tee.write_all(&"█".as_bytes()[..1]).unwrap();
+        tee.flush().unwrap();
+        tee.write_all(&"█\"\n".as_bytes()[1..]).unwrap();
+        tee.flush().unwrap();
+        assert_eq!(tee.inner, "hello █\"\n".as_bytes());
+        drop(tee);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(
+            (lines[0]["width"].as_u64(), lines[0]["height"].as_u64()),
+            (Some(80), Some(24))
+        );
+        assert_eq!(lines[1][1], "o");
+        assert_eq!(lines[1][2], "hello ");
+        assert_eq!(lines[2][2], "█\"\n");
+    }
+}
@@ -0,0 +1,164 @@
+// Benchmark boards and headless benchmark runs, for profiling.
+//
+// `--bench-board <small|medium|large|huge>` starts on a fixed board: its size,
+// obstacles, snake and first food are always the same, with no randomness
+// involved, so a slow frame on one machine can be looked at again on another.
+//
+// `--bench TICKS` plays that many ticks with nobody at the keyboard and nothing
+// on screen: the autopilot (see `bot.rs`) steers, each frame is drawn into memory instead
+// of the terminal, and the update and render times are printed at the end (in
+// the same form as `--perf-log`'s summary). The random seed is fixed too, so the
+// same board and version play exactly the same game every time. When the
+// autopilot crashes, the board starts over and the run carries on.
+
+use std::time::{Duration, Instant};
+
+use rand::{SeedableRng, rngs::StdRng};
+
+use crate::{Direction, Game, Point, bot, perf, render::Draw};
+
+// The seed every benchmark game uses.
+const SEED: u64 = 483;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BenchBoard {
+    Small,
+    Medium,
+    Large,
+    Huge,
+}
+
+impl BenchBoard {
+    // Reads a preset's name, as given to `--bench-board`.
+    pub fn from_name(name: &str) -> Option<BenchBoard> {
+        match name {
+            "small" => Some(BenchBoard::Small),
+            "medium" => Some(BenchBoard::Medium),
+            "large" => Some(BenchBoard::Large),
+            "huge" => Some(BenchBoard::Huge),
+            _ => None,
+        }
+    }
+
+    // The board's width and height, the snake's length, and the gap between the
+    // obstacle pillars (a smaller gap means more of them).
+    fn layout(self) -> (u16, u16, usize, u16) {
+        match self {
+            BenchBoard::Small => (20, 10, 4, 4),
+            BenchBoard::Medium => (40, 20, 12, 4),
+            BenchBoard::Large => (100, 50, 40, 3),
+            BenchBoard::Huge => (200, 100, 120, 3),
+        }
+    }
+
+    // Builds the board: the snake along the top row heading right, the first food
+    // at the far end of that row, and a grid of one-cell pillars below.
+    pub fn build(self) -> Result<Game, String> {
+        let (width, height, length, gap) = self.layout();
+        let head = Point {
+            x: length as u16,
+            y: 1,
+        };
+        let food = Point { x: width - 2, y: 1 };
+        let mut game = Game::new(width, height, Some(head), Some(food))?;
+        game.place_snake_sized(head, Direction::Right, length)?;
+        game.obstacles = (3..height - 1)
+            .step_by(gap as usize)
+            .flat_map(|y| (2..width - 2).step_by(gap as usize).map(move |x| Point { x, y }))
+            .collect();
+        game.rng = StdRng::seed_from_u64(SEED);
+        Ok(game)
+    }
+}
+
+// What a benchmark run did, besides how long it took: the same board and version
+// should always give the same numbers.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Played {
+    pub apples: usize,
+    pub restarts: usize,
+}
+
+// Plays `ticks` ticks on boards from `new_game`, timing every update and render.
+// Returns the report to print, and what was played.
+pub fn run(
+    ticks: u64,
+    new_game: impl Fn() -> Result<Game, String>,
+) -> Result<(String, Played), String> {
+    let start = || -> Result<Game, String> {
+        let mut game = new_game()?;
+        game.rng = StdRng::seed_from_u64(SEED);
+        Ok(game)
+    };
+    let mut game = start()?;
+    let (width, height) = game.dimensions();
+    let mut header = format!(
+        "Board: {}x{}, {} obstacles, snake length {}\n",
+        width,
+        height,
+        game.obstacles.len(),
+        game.snake().body.len()
+    );
+
+    let mut played = Played {
+        apples: 0,
+        restarts: 0,
+    };
+    let mut updates: Vec<Duration> = Vec::new();
+    let mut renders: Vec<Duration> = Vec::new();
+    // Frames are drawn here instead of the terminal; cleared (but not freed) each time.
+    let mut screen = Vec::new();
+    for _ in 0..ticks {
+        if game.is_over() {
+            played.apples += game.apples;
+            played.restarts += 1;
+            game = start()?;
+        }
+        let direction = bot::next_move(&game);
+        game.turn(0, direction);
+
+        let update_start = Instant::now();
+        game.update();
+        updates.push(update_start.elapsed());
+
+        screen.clear();
+        let render_start = Instant::now();
+        game.draw(&mut screen).map_err(|err| err.to_string())?;
+        renders.push(render_start.elapsed());
+    }
+    played.apples += game.apples;
+
+    header += &format!(
+        "Ticks: {}  Apples: {}  Restarts: {}\n{}\n{}\n",
+        ticks,
+        played.apples,
+        played.restarts,
+        perf::stats_line("update", &updates),
+        perf::stats_line("render", &renders)
+    );
+    Ok((header, played))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bench_boards_are_always_the_same() {
+        for name in ["small", "medium", "large", "huge"] {
+            let board = BenchBoard::from_name(name).unwrap();
+            let (one, two) = (board.build().unwrap(), board.build().unwrap());
+            assert_eq!(one.render_ascii(), two.render_ascii());
+            assert!(!one.obstacles.is_empty());
+            assert!(one.is_food_reachable());
+        }
+        assert_eq!(BenchBoard::from_name("enormous"), None);
+
+        // The same board plays the same game.
+        let run_small = || run(300, || BenchBoard::Small.build()).unwrap();
+        let (report, played) = run_small();
+        assert!(played.apples > 0);
+        assert!(report.starts_with("Board: 20x10, "));
+        assert_eq!(run_small().1, played);
+    }
+}
@@ -0,0 +1,117 @@
+// The autopilot (`--bot`): a bot that steers player one, for a demo that plays
+// itself, or to run the game for a long time without anyone at the keyboard.
+// `--bench` uses it too.
+//
+// Each tick it picks a move in this order:
+//   1. The first step of a shortest way to the food (see `pathfinding.rs`), as
+//      long as there's still room to carry on from there.
+//   2. Otherwise, the first step of a way to its own tail. The tail moves on
+//      as the head follows it, so chasing it keeps the snake alive until the
+//      way to the food opens up again.
+//   3. Otherwise, whichever move leaves it the most room. When every move is
+//      fatal, it carries straight on.
+
+use std::{io, time::Duration};
+
+use crossterm::event::KeyEvent;
+
+use crate::{Direction, Game, Point, game_loop::Input, keys::KeyBindings, pathfinding};
+
+// The moves the bot makes (it never moves diagonally).
+const MOVES: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+// How long the game over screen stays up before the bot starts a new game.
+pub const RESTART_DELAY: Duration = Duration::from_secs(3);
+
+// The way player one should go next.
+pub fn next_move(game: &Game) -> Direction {
+    let snake = game.snake();
+    let head = snake.head();
+    let step = |p, direction| game.advance(p, direction);
+    // The reverser turns the snake round, which the plans below can't foresee,
+    // so the bot keeps off it like a wall.
+    let free = |p| !game.is_lethal(p) && game.reverser != Some(p);
+    // The move that takes the head onto the first cell of `path`.
+    let first_move = |path: Option<Vec<Point>>| {
+        let next = *path?.first()?;
+        MOVES.into_iter().find(|&d| game.next_head(d) == next)
+    };
+    // How many cells the head could still get to after moving onto `p`.
+    let room = |p: Point| pathfinding::reachable_cells(p, step, free).len();
+
+    if let Some(direction) = first_move(pathfinding::bfs_path(head, game.food, step, free))
+        && room(game.next_head(direction)) >= snake.body.len()
+    {
+        return direction;
+    }
+
+    // The tail is in the snake's body, but it'll have moved on by the time we get there.
+    let tail = *snake.body.back().expect("a snake always has a body");
+    // The crash check comes before the tail moves, though, so the head must
+    // never step right onto it: that first step has to be a free cell.
+    let to_tail = pathfinding::bfs_path(head, tail, step, |p| p == tail || free(p));
+    if let Some(path) = to_tail
+        && path.len() > 1
+        && let Some(direction) = first_move(Some(path))
+    {
+        return direction;
+    }
+
+    MOVES
+        .into_iter()
+        .filter(|&d| free(game.next_head(d)))
+        .max_by_key(|&d| room(game.next_head(d)))
+        .unwrap_or(snake.direction)
+}
+
+// The keyboard while the bot plays: only the keys that quit get through, so
+// nobody steers (or retries) by accident in the middle of a demo.
+pub struct BotInput<I: Input> {
+    inner: I,
+    keys: KeyBindings,
+}
+
+impl<I: Input> BotInput<I> {
+    pub fn new(inner: I, keys: KeyBindings) -> BotInput<I> {
+        BotInput { inner, keys }
+    }
+}
+
+impl<I: Input> Input for BotInput<I> {
+    fn poll(&mut self, timeout: Duration) -> io::Result<Option<KeyEvent>> {
+        let key = self.inner.poll(timeout)?;
+        Ok(key.filter(|&key| self.keys.quits(key)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    #[test]
+    fn the_bot_eats_and_gets_out_of_tight_spots() {
+        // Left to itself on an empty board, it keeps eating for a good while.
+        // (Seeded, so the food turns up in the same places every run.)
+        let mut game = Game::with_rng(20, 10, None, None, StdRng::seed_from_u64(517)).unwrap();
+        game.settings.no_obstacles = true;
+        for _ in 0..2000 {
+            if game.is_over() {
+                break;
+            }
+            game.turn(0, next_move(&game));
+            game.update();
+        }
+        assert!(game.apples >= 15, "only {} apples", game.apples);
+
+        // With the food straight ahead at the end of a dead end too short for
+        // the snake, it turns off and follows its tail instead.
+        let mut game = Game::new(10, 6, Some(Point { x: 5, y: 3 }), Some(Point { x: 7, y: 3 })).unwrap();
+        game.obstacles = [(6, 2), (7, 2), (8, 3), (6, 4), (7, 4)]
+            .into_iter()
+            .map(|(x, y)| Point { x, y })
+            .collect();
+        game.place_snake_sized(Point { x: 5, y: 3 }, Direction::Right, 4).unwrap();
+        assert!(matches!(next_move(&game), Direction::Up | Direction::Down));
+    }
+}
@@ -0,0 +1,114 @@
+// Cheat codes (`--cheats`): type a secret word while playing to trigger an effect.
+// Handy for trying out features (like later levels) without playing up to them.
+//
+// The words avoid the game's own keys (arrows, WASD, p, b, t, g, q), so typing
+// one doesn't also steer the snake or toggle anything.
+
+use crate::Game;
+
+// One cheat: the word to type, a short name to show, and what it does.
+pub struct Cheat {
+    pub word: &'static str,
+    pub name: &'static str,
+    // `fn(&mut Game)` is the type of a plain function (or method) that takes the
+    // game, so a method like `Game::cheat_grow` fits right in.
+    pub effect: fn(&mut Game),
+}
+
+// Every cheat. To add one, add a line here.
+pub const CHEATS: &[Cheat] = &[
+    Cheat {
+        word: "yummy",
+        name: "grow",
+        effect: Game::cheat_grow,
+    },
+    Cheat {
+        word: "level",
+        name: "next level",
+        effect: Game::level_up,
+    },
+    Cheat {
+        word: "nuke",
+        name: "bomb",
+        effect: Game::cheat_bomb,
+    },
+];
+
+// How many segments the "grow" cheat adds.
+const GROW_BY: usize = 5;
+
+// Watches the keys typed so far and spots when they spell out a cheat.
+#[derive(Default)]
+pub struct CheatMatcher {
+    recent: String,
+}
+
+impl CheatMatcher {
+    // Records one typed character. Returns the matching cheat's index in
+    // `CHEATS` if this character completed one.
+    pub fn push(&mut self, c: char) -> Option<usize> {
+        self.recent.push(c);
+        // Only the last few characters can matter, so forget anything older
+        // than the longest cheat word.
+        let longest = CHEATS
+            .iter()
+            .map(|cheat| cheat.word.len())
+            .max()
+            .unwrap_or(0);
+        while self.recent.chars().count() > longest {
+            self.recent.remove(0);
+        }
+        let found = CHEATS
+            .iter()
+            .position(|cheat| self.recent.ends_with(cheat.word))?;
+        // Start afresh, so the same letters can't trigger a second cheat.
+        self.recent.clear();
+        Some(found)
+    }
+}
+
+impl Game {
+    // Makes player one's snake longer. The new segments pile up on the tail's
+    // cell and unfold as the snake moves, just like after eating.
+    fn cheat_grow(&mut self) {
+        let snake = &mut self.snakes[0];
+        let tail = *snake.body.back().unwrap();
+        snake.body.extend(std::iter::repeat_n(tail, GROW_BY));
+    }
+
+    // Drops a bomb, if there isn't one already and there's room for it.
+    fn cheat_bomb(&mut self) {
+        if self.bomb.is_none() && self.has_room_for_pickup() {
+            self.spawn_bomb();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(matcher: &mut CheatMatcher, keys: &str) -> Vec<usize> {
+        keys.chars().filter_map(|c| matcher.push(c)).collect()
+    }
+
+    #[test]
+    fn spots_cheat_words_anywhere_in_the_typing() {
+        let mut matcher = CheatMatcher::default();
+        assert_eq!(type_keys(&mut matcher, "xxyummyxx"), [0]);
+        assert_eq!(type_keys(&mut matcher, "lev"), [0; 0]);
+        assert_eq!(type_keys(&mut matcher, "el"), [1]);
+        assert_eq!(type_keys(&mut matcher, "nukenuke"), [2, 2]);
+        assert_eq!(type_keys(&mut matcher, "yumm"), [0; 0]);
+    }
+
+    #[test]
+    fn cheats_change_the_game() {
+        let mut game = Game::new(20, 10, None, None).unwrap();
+        (CHEATS[0].effect)(&mut game);
+        assert_eq!(game.snake().body.len(), 3 + GROW_BY);
+        (CHEATS[1].effect)(&mut game);
+        assert_eq!(game.level, 2);
+        assert!(!game.obstacles.is_empty());
+    }
+}
@@ -0,0 +1,522 @@
+// Command-line argument parsing.
+// We parse the arguments by hand instead of pulling in a crate like `clap`:
+// the game only has a handful of flags, and this keeps the dependency list short.
+
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+use crate::{HEIGHT, MAX_SHIELDS, MIN_TICK_RATE, Point, RetryLayout, Settings, SpawnMode, SpeedCurve, WIDTH, bench::BenchBoard, keys::KeyBindings, map};
+
+// The help text printed for `--help` (and after an argument error).
+pub const USAGE: &str = "\
+Usage: hello_rust [OPTIONS]
+       hello_rust --scores [--json]
+       hello_rust --show-heatmap
+       hello_rust --settings
+       hello_rust --watch-best
+       hello_rust --join <ADDR>
+
+Saved settings (Settings on the menu, or --settings) are the defaults; flags go on top.
+The keys for up, down, left, right, pause, quit and restart can be changed in the
+same config file, under \"keys\" (e.g. \"keys\": { \"up\": [\"Up\", \"k\"] }).
+
+Options:
+  --map <FILE>     Load the board from a text map file (numbered food and a `moves N`
+                   line make it a puzzle: eat the food in order within N moves)
+  --start <X,Y>    Put the snake's head at column X, row Y (its body trails to the left)
+  --food <X,Y>     Put the first food at column X, row Y (the top-left wall is 0,0)
+  --face-food      Start the snake heading toward the first food
+  --width <N>      Make the board N columns wide, walls included (default 40, at most 200)
+  --height <N>     Make the board N rows tall, walls included (default 20, at most 200)
+  --speed <MS>     Make each tick on level 1 last MS milliseconds (default 150, at least 50)
+  --seed <N>       Seed the random numbers with N: the same seed gives the same food,
+                   obstacles and pickups, for sharing a layout or practicing a speedrun
+  --random-start-length <MIN,MAX>
+                   Start each game with a random snake length from MIN to MAX
+                   (as much of it as fits behind the head)
+  --spawn <MODE>   Where new food appears: uniform (default) or far from the snake
+  --retry-layout <LAYOUT>
+                   Obstacles when retrying a level after game over: fresh (default) or same
+  --speed-curve <CURVE>
+                   How the speed rises with the level: flat (default), linear, exponential
+                   or logarithmic
+  --print-speed-table
+                   Print the tick rate for each level under --speed-curve and exit
+  --accelerate     Speed up while moving in a straight line; turning resets the speed
+  --time-attack    Race a draining clock; eating food adds time
+  --time-bonus <SECONDS>
+                   Seconds added per food in time attack (default 5)
+  --waves          Survival waves: a surge of new walls arrives every 20 seconds
+  --paint          Paint the cells you leave behind; new paint scores points
+  --paint-target <PERCENT>
+                   Percentage of the floor to paint to win in paint mode (default 75)
+  --shields <N>    Start with N shields (1-9), each absorbing a fatal hit; spent ones
+                   recharge over time
+  --level-every <N>
+                   Apples needed per level (default 5)
+  --growth-delay <TICKS>
+                   Grow each new segment this many ticks after eating (default 0)
+  --endless        Keep playing when the board fills up instead of winning
+  --power-ups      Eating sometimes drops a power-up: » speed boost, ÷ shrink by 3,
+                   or $ double points (the timed ones count down in the HUD)
+  --coop           Two-player co-op: arrows and WASD steer two snakes sharing one score
+  --versus         Two players against each other: arrows and WASD, a score each; a snake
+                   that crashes is out, and when both are, the most apples wins
+  --mirror         Flip the board (and the controls) on every new level
+  --border <N>     Make the outer wall N cells thick (default 1)
+  --corridor       Keep a winding path from the snake to the food clear of walls on every level
+  --no-obstacles   Never add walls inside the board: new levels only make the game faster
+  --gates          Add one-way gates from level 2: walls you can only cross along their arrow
+  --ghost          Pass through obstacles (toggle while playing with 'g')
+  --wrap           Wrap-around walls: leave by one edge to come back in at the opposite
+                   one (toggle while playing with 'b', or turn on for good in --settings)
+  --split          Biting yourself cuts off your tail from the bite back instead of ending
+                   the game, unless that would leave fewer than 3 segments
+  --diagonal       Allow diagonal moves with the numpad corner keys (7, 9, 1, 3)
+  --quick-turns    Move as soon as you turn instead of at the end of the tick (but
+                   never more than twice as fast), for snappier play at slow speeds
+  --drunk          Novelty mode: the snake sometimes turns on its own
+  --audio-assist   Beep when carrying straight on would be fatal
+  --rainbow-food   Cycle the food color through the rainbow
+  --show-length    Show the snake's length next to the score
+  --toasts         Pop up short notes (+1, LEVEL 3, BOOM!) on the board as things happen
+  --speed-tint     Color the snake by its speed, from blue when slow to red when fast
+  --recap          Replay the run, small and sped up, on the game over screen
+  --no-score-colors
+                   Don't color the game over screen by how the score compares with your
+                   best (gold and NEW RECORD! for a new high score), just red or green
+  --crt            Darken every other row of the board, like an old CRT monitor's scanlines
+  --scaled-score   Scale the leaderboard score by how hard the rules are (shown as ×N in
+                   the HUD); ghost mode or cheats make it zero
+  --show-queue     Show the turns you've pressed that are still waiting to be made
+  --cheats         Enable secret cheat codes, for testing and fun
+  --debug          Show board metrics while paused
+  --show-regions   Shade each area of floor cut off by obstacles in its own color
+  --local-grid     Put faint dots on the empty cells near the snake's head, to help judge distances
+  --hint           Mark two suggested moves: the greedy one, straight for the food (◆), and
+                   the safe one, which keeps a way open to your tail (◇)
+  --shrink-to-fit  Shrink the board to fit a small terminal, with fewer obstacles to match
+  --resume         Continue the game saved with Ctrl+S
+  --save-file <FILE>
+                   Where Ctrl+S saves the game and --resume loads it from
+  --asciicast <FILE>
+                   Record the game as an asciinema cast file (play it with `asciinema play`)
+  --watch-best     Play back the best run (also on the menu): the last one recorded with
+                   --record that set a new high score (the keyboard can only quit)
+  --script <FILE>  Steer the snake from a script of timed turns (lines like `t100 RIGHT`);
+                   the keyboard can only quit or retry
+  --record <FILE>  Save a replay of the game (its seed, rules and turns) when it ends;
+                   a run that sets a new high score is also kept for --watch-best
+  --replay <FILE>  Play back a replay saved with --record (the keyboard can only quit)
+  --bot            Let the autopilot play, as a demo: it heads for the food, follows its
+                   tail when that's not safe, and starts a new game after each crash
+  --compare <A> <B>
+                   Play scripts A and B side by side from the same board and seed, and
+                   report the first tick their turns differ and the first their boards do
+  --host <ADDR>    Experimental: host a co-op game for a player on another machine,
+                   e.g. --host 0.0.0.0:7778 (they steer the second snake)
+  --join <ADDR>    Experimental: join a co-op game hosted with --host, e.g. --join 10.0.0.5:7778
+  --serve <ADDR>   Let others watch live over TCP, e.g. --serve 127.0.0.1:7777 then `nc 127.0.0.1 7777`
+  --free-cell-cache
+                   Track the free cells as the game goes, so food spawns without scanning
+                   the board (faster on big maps)
+  --perf-log <FILE>
+                   Write per-frame update/render timings to a CSV file
+  --bench-board <SIZE>
+                   Start on a fixed board for profiling: small, medium, large or huge
+  --bench <TICKS>  Play TICKS ticks headless with an autopilot, then print the update and
+                   render timings (with --bench-board, the same game every time)
+  --scores         Print the leaderboard and exit (add --json for JSON output)
+  --settings       Go straight to the settings screen (also on the menu) to change the
+                   saved settings (sound, theme, speed curve, controls, wrap)
+  --menu           Pick a mode (Classic, Obstacles, Wrap, Two-Player) from a menu first,
+                   or watch the best run or change the settings there
+                   (the default when started with no options in a terminal)
+  --show-heatmap   Print a map of where you've crashed over all your games and exit
+  --dump           Print the starting board as ASCII text and exit
+  --title          Show the score and level in the terminal window title
+  --no-altscreen   Draw inline in the current terminal instead of the alternate screen
+  --no-suspend     Ignore Ctrl+Z instead of suspending the game to the shell (Unix)
+  -h, --help       Print this help text
+";
+
+// All the settings that can be changed from the command line.
+// `#[derive(Debug)]` lets us print the struct with `{:?}`, handy while debugging.
+#[derive(Debug, Clone)]
+pub struct Options {
+    // When true (the default) the game runs on the terminal's "alternate screen",
+    // so your shell history is restored untouched when you quit.
+    pub alt_screen: bool,
+    // Show the score and level in the window title. Off by default because
+    // some terminals handle title changes poorly.
+    pub title: bool,
+    // Suspend to the shell on Ctrl+Z (Unix job control). On by default.
+    pub suspend: bool,
+    // A text map file to load the board from (see `map.rs` for the format).
+    pub map: Option<PathBuf>,
+    // Continue a saved game (see `save.rs`) instead of starting a new one.
+    pub resume: bool,
+    // Where to save the game (Ctrl+S) and resume it from; `None` means the default location.
+    pub save_file: Option<PathBuf>,
+    // Enable typed cheat codes (see `cheats.rs`).
+    pub cheats: bool,
+    // Write frame timings to this CSV file (see `perf.rs`).
+    pub perf_log: Option<PathBuf>,
+    // Keep a running set of free cells (see `free_cells.rs`).
+    pub free_cell_cache: bool,
+    // Record the game to this asciinema cast file (see `asciicast.rs`).
+    pub asciicast: Option<PathBuf>,
+    // Start on this fixed board instead of the classic one (see `bench.rs`).
+    pub bench_board: Option<BenchBoard>,
+    // Play this many ticks headless and print the timings, instead of playing.
+    pub bench: Option<u64>,
+    // Steer the snake from this script of timed turns instead of the keyboard (see `script.rs`).
+    pub script: Option<PathBuf>,
+    // Save a replay of the game here when it ends (see `replay.rs`).
+    pub record: Option<PathBuf>,
+    // Play back the replay saved here instead of playing.
+    pub replay: Option<PathBuf>,
+    // Let the autopilot steer player one (see `bot.rs`).
+    pub bot: bool,
+    // Play these two scripts side by side and report where they part ways (see `compare.rs`).
+    pub compare: Option<(PathBuf, PathBuf)>,
+    // Host a networked co-op game on this address (see `net.rs`).
+    pub host: Option<String>,
+    // Broadcast the board to spectators on this address (see `spectate.rs`).
+    pub serve: Option<String>,
+    // Print the starting board with `Game::render_ascii` instead of playing.
+    pub dump: bool,
+    // Print the tick rate per level instead of playing.
+    pub print_speed_table: bool,
+    // Show the main menu (see `menu.rs`) and play the mode picked there.
+    pub menu: bool,
+    // The color theme to start with, as an index into `theme::THEMES`.
+    pub theme: usize,
+    // Which keys steer, pause, quit and retry (from the config file, see `keys.rs`).
+    pub keys: KeyBindings,
+    // Where to put the snake's head, instead of the middle of the board.
+    pub start: Option<Point>,
+    // Where to put the first food, instead of a random cell.
+    pub food: Option<Point>,
+    // Start each game with a random snake length in this range (inclusive).
+    pub random_start_length: Option<(usize, usize)>,
+    // Turn the snake toward the first food before the game starts.
+    pub face_food: bool,
+    // The size of the classic board (a map or a bench board has its own size).
+    pub width: u16,
+    pub height: u16,
+    // Seed the random numbers with this, so the same seed gives the same game.
+    pub seed: Option<u64>,
+    // How many cells thick the outer wall is.
+    pub border: u16,
+    // Whether retrying a level keeps its obstacles.
+    pub retry_layout: RetryLayout,
+    // Gameplay rules passed straight through to the `Game`.
+    pub settings: Settings,
+}
+
+// `Default` gives us `Options::default()` with the values used when no flags are passed.
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            alt_screen: true,
+            title: false,
+            suspend: true,
+            map: None,
+            resume: false,
+            cheats: false,
+            save_file: None,
+            perf_log: None,
+            free_cell_cache: false,
+            asciicast: None,
+            bench_board: None,
+            bench: None,
+            script: None,
+            record: None,
+            replay: None,
+            bot: false,
+            compare: None,
+            host: None,
+            serve: None,
+            dump: false,
+            print_speed_table: false,
+            menu: false,
+            theme: 0,
+            keys: KeyBindings::default(),
+            start: None,
+            food: None,
+            face_food: false,
+            width: WIDTH,
+            height: HEIGHT,
+            seed: None,
+            random_start_length: None,
+            border: 1,
+            retry_layout: RetryLayout::Fresh,
+            settings: Settings::default(),
+        }
+    }
+}
+
+impl Options {
+    // The save file to use: `--save-file` if given, otherwise the default location.
+    pub fn save_path(&self) -> Option<PathBuf> {
+        self.save_file
+            .clone()
+            .or_else(crate::save::SaveGame::default_path)
+    }
+}
+
+// What the caller should do after parsing.
+#[derive(Debug)]
+pub enum Command {
+    // Boxed, because `Options` is much bigger than the other variants and every
+    // `Command` takes up as much room as its biggest variant.
+    Play(Box<Options>),
+    // Print the leaderboard instead of playing, as a table or (with `json`) as JSON.
+    Scores { json: bool },
+    // Print the death heatmap instead of playing.
+    Heatmap,
+    // Open the settings screen instead of playing.
+    Settings,
+    // Play back the best run's replay instead of a new game.
+    WatchBest,
+    // Join a networked co-op game at this address instead of starting one.
+    Join(String),
+    Help,
+}
+
+// Parses the arguments (without the program name) into a `Command`, applying the
+// flags on top of `options` (the defaults, with any saved settings already in).
+// Returns `Err` with a human-readable message for unknown or malformed flags.
+// Taking `impl IntoIterator` (instead of reading `std::env::args()` directly)
+// means we can feed it any list of strings.
+pub fn parse_args(args: impl IntoIterator<Item = String>, mut options: Options) -> Result<Command, String> {
+    // We need to pull values for flags like `--map <FILE>` off the same iterator,
+    // so we drive it by hand with `while let` instead of a `for` loop.
+    let mut args = args.into_iter();
+    let mut scores = false;
+    let mut json = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-altscreen" => options.alt_screen = false,
+            "--no-suspend" => options.suspend = false,
+            "--title" => options.title = true,
+            "--show-length" => options.settings.show_length = true,
+            "--toasts" => options.settings.toasts = true,
+            "--show-queue" => options.settings.show_queue = true,
+            "--speed-tint" => options.settings.speed_tint = true,
+            "--recap" => options.settings.recap = true,
+            "--local-grid" => options.settings.local_grid = true,
+            "--hint" => options.settings.hint = true,
+            "--no-score-colors" => options.settings.score_colors = false,
+            "--quick-turns" => options.settings.quick_turns = true,
+            "--crt" => options.settings.crt = true,
+            "--show-regions" => options.settings.show_regions = true,
+            "--shrink-to-fit" => options.settings.shrink_to_fit = true,
+            "--scaled-score" => options.settings.scaled_score = true,
+            "--cheats" => options.cheats = true,
+            "--debug" => options.settings.debug = true,
+            "--free-cell-cache" => options.free_cell_cache = true,
+            "--perf-log" => options.perf_log = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--asciicast" => options.asciicast = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--bench-board" => {
+                let name = value(&mut args, &arg)?;
+                options.bench_board = Some(BenchBoard::from_name(&name).ok_or_else(|| {
+                    format!("'{}' expects small, medium, large or huge, got '{}'", arg, name)
+                })?);
+            }
+            "--bench" => options.bench = Some(parse_number(&value(&mut args, &arg)?, &arg)?),
+            "--script" => options.script = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--record" => options.record = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--replay" => options.replay = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--bot" => options.bot = true,
+            "--compare" => {
+                let a = PathBuf::from(value(&mut args, &arg)?);
+                let b = PathBuf::from(value(&mut args, &arg)?);
+                options.compare = Some((a, b));
+            }
+            "--serve" => options.serve = Some(value(&mut args, &arg)?),
+            "--resume" => options.resume = true,
+            "--save-file" => options.save_file = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--dump" => options.dump = true,
+            "--print-speed-table" => options.print_speed_table = true,
+            "--menu" => options.menu = true,
+            "--speed-curve" => {
+                options.settings.speed_curve = match value(&mut args, &arg)?.as_str() {
+                    "flat" => SpeedCurve::Flat,
+                    "linear" => SpeedCurve::Linear,
+                    "exponential" => SpeedCurve::Exponential,
+                    "logarithmic" => SpeedCurve::Logarithmic,
+                    other => {
+                        return Err(format!(
+                            "'{}' expects flat, linear, exponential or logarithmic, got '{}'",
+                            arg, other
+                        ));
+                    }
+                }
+            }
+            "--scores" => scores = true,
+            "--show-heatmap" => return Ok(Command::Heatmap),
+            "--settings" => return Ok(Command::Settings),
+            "--watch-best" => return Ok(Command::WatchBest),
+            "--join" => return Ok(Command::Join(value(&mut args, &arg)?)),
+            // Hosting is co-op with the second snake steered from the other machine.
+            "--host" => {
+                options.host = Some(value(&mut args, &arg)?);
+                options.settings.coop = true;
+            }
+            "--json" => json = true,
+            "--retry-layout" => {
+                options.retry_layout = match value(&mut args, &arg)?.as_str() {
+                    "fresh" => RetryLayout::Fresh,
+                    "same" => RetryLayout::Same,
+                    other => {
+                        return Err(format!("'{}' expects fresh or same, got '{}'", arg, other));
+                    }
+                }
+            }
+            "--spawn" => {
+                options.settings.spawn = match value(&mut args, &arg)?.as_str() {
+                    "uniform" => SpawnMode::Uniform,
+                    "far" => SpawnMode::Far,
+                    other => {
+                        return Err(format!("'{}' expects uniform or far, got '{}'", arg, other));
+                    }
+                }
+            }
+            "--accelerate" => options.settings.accelerate = true,
+            "--time-attack" => options.settings.time_attack = true,
+            "--time-bonus" => {
+                let seconds: f32 = parse_number(&value(&mut args, &arg)?, &arg)?;
+                options.settings.time_bonus = Duration::try_from_secs_f32(seconds)
+                    .map_err(|_| format!("'{}' must be a non-negative number of seconds", arg))?;
+            }
+            "--waves" => options.settings.waves = true,
+            "--paint" => options.settings.paint = true,
+            "--paint-target" => {
+                let percent: u8 = parse_number(&value(&mut args, &arg)?, &arg)?;
+                if !(1..=100).contains(&percent) {
+                    return Err(format!("'{}' must be between 1 and 100", arg));
+                }
+                options.settings.paint_target = percent;
+            }
+            "--shields" => {
+                let shields: u8 = parse_number(&value(&mut args, &arg)?, &arg)?;
+                if !(1..=MAX_SHIELDS).contains(&shields) {
+                    return Err(format!("'{}' must be between 1 and {}", arg, MAX_SHIELDS));
+                }
+                options.settings.shields = shields;
+            }
+            "--level-every" => {
+                let every: usize = parse_number(&value(&mut args, &arg)?, &arg)?;
+                if every == 0 {
+                    return Err(format!("'{}' must be at least 1", arg));
+                }
+                options.settings.level_every = every;
+            }
+            "--growth-delay" => {
+                options.settings.growth_delay = parse_number(&value(&mut args, &arg)?, &arg)?
+            }
+            "--endless" => options.settings.endless = true,
+            "--coop" => options.settings.coop = true,
+            "--versus" => options.settings.versus = true,
+            "--mirror" => options.settings.mirror = true,
+            "--ghost" => options.settings.ghost = true,
+            "--wrap" => options.settings.wrap = true,
+            "--split" => options.settings.split = true,
+            "--gates" => options.settings.gates = true,
+            "--corridor" => options.settings.corridor = true,
+            "--no-obstacles" => options.settings.no_obstacles = true,
+            "--seed" => options.seed = Some(parse_number(&value(&mut args, &arg)?, &arg)?),
+            "--width" => options.width = parse_board_size(&value(&mut args, &arg)?, &arg)?,
+            "--height" => options.height = parse_board_size(&value(&mut args, &arg)?, &arg)?,
+            "--speed" => {
+                let millis: u64 = parse_number(&value(&mut args, &arg)?, &arg)?;
+                let tick_rate = Duration::from_millis(millis);
+                if tick_rate < MIN_TICK_RATE {
+                    return Err(format!("'{}' must be at least {}", arg, MIN_TICK_RATE.as_millis()));
+                }
+                options.settings.tick_rate = tick_rate;
+            }
+            "--border" => options.border = parse_number(&value(&mut args, &arg)?, &arg)?,
+            "--diagonal" => options.settings.diagonal = true,
+            "--drunk" => options.settings.drunk = true,
+            "--audio-assist" => options.settings.audio_assist = true,
+            "--rainbow-food" => options.settings.rainbow_food = true,
+            "--power-ups" => options.settings.power_ups = true,
+            "--map" => options.map = Some(PathBuf::from(value(&mut args, &arg)?)),
+            "--start" => options.start = Some(parse_point(&value(&mut args, &arg)?, &arg)?),
+            "--food" => options.food = Some(parse_point(&value(&mut args, &arg)?, &arg)?),
+            "--face-food" => options.face_food = true,
+            "--random-start-length" => {
+                options.random_start_length = Some(parse_range(&value(&mut args, &arg)?, &arg)?)
+            }
+            "-h" | "--help" => return Ok(Command::Help),
+            other => return Err(format!("unknown argument '{}'", other)),
+        }
+    }
+
+    if scores {
+        return Ok(Command::Scores { json });
+    }
+    if json {
+        return Err("'--json' only works together with '--scores'".to_string());
+    }
+    if options.settings.coop && options.settings.versus {
+        return Err("pick one of '--coop' and '--versus'".to_string());
+    }
+    if options.replay.is_some() && options.script.is_some() {
+        return Err("pick one of '--replay' and '--script'".to_string());
+    }
+    if options.bot && (options.replay.is_some() || options.script.is_some()) {
+        return Err("'--bot' can't steer a '--replay' or '--script'".to_string());
+    }
+    Ok(Command::Play(Box::new(options)))
+}
+
+// Parses a flag's value as a number (any type that implements `FromStr`, like `u16` or `f32`).
+fn parse_number<T: FromStr>(text: &str, flag: &str) -> Result<T, String> {
+    text.parse()
+        .map_err(|_| format!("'{}' expects a number, got '{}'", flag, text))
+}
+
+// Parses a board's width or height. The largest a map can be is the largest a
+// board can be: anything bigger won't fit a terminal, and a huge one would take
+// ages just to set up (before we even get to check it fits).
+fn parse_board_size(text: &str, flag: &str) -> Result<u16, String> {
+    let size: u16 = parse_number(text, flag)?;
+    if size as usize > map::MAX_SIZE {
+        return Err(format!("'{}' can be at most {}", flag, map::MAX_SIZE));
+    }
+    Ok(size)
+}
+
+// Parses a `min,max` range like `3,8`, where `min` is at least 1 and no more than `max`.
+fn parse_range(text: &str, flag: &str) -> Result<(usize, usize), String> {
+    let (min, max) = text
+        .split_once(',')
+        .ok_or_else(|| format!("'{}' expects MIN,MAX (for example 3,8), got '{}'", flag, text))?;
+    let (min, max): (usize, usize) = (parse_number(min.trim(), flag)?, parse_number(max.trim(), flag)?);
+    if min == 0 || min > max {
+        return Err(format!("'{}' needs 1 <= MIN <= MAX, got '{}'", flag, text));
+    }
+    Ok((min, max))
+}
+
+// Parses an `x,y` pair like `12,5` into a `Point`.
+fn parse_point(text: &str, flag: &str) -> Result<Point, String> {
+    // `split_once` cuts the string at the first comma, giving us the two halves.
+    let (x, y) = text
+        .split_once(',')
+        .ok_or_else(|| format!("'{}' expects X,Y (for example 12,5), got '{}'", flag, text))?;
+    Ok(Point {
+        x: parse_number(x.trim(), flag)?,
+        y: parse_number(y.trim(), flag)?,
+    })
+}
+
+// Takes the value that must follow a flag, or explains that it's missing.
+fn value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("'{}' needs a value", flag))
+}
@@ -0,0 +1,60 @@
+// Where the game gets the current time from.
+//
+// The speedrun timer, the side panel's messages and the toasts all ask "what time
+// is it?". Asking `Instant::now()` directly makes them awkward to test: the only
+// way to see a toast expire would be to sleep for a second and a half. So the game
+// asks a `Clock` instead. While playing that's `InstantClock`, the real clock;
+// tests use a `MockClock`, which stands still until the test moves it forward.
+//
+// (Timers that count down by the time between frames, like time attack and the
+// survival waves, don't need this: `Game::update_timers` is handed that time.)
+
+use std::time::Instant;
+
+// A trait is a set of methods a type promises to have. `Game` holds a
+// `Box<dyn Clock>`: some type that has `now`, decided while the program runs.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+// The real clock.
+pub struct InstantClock;
+
+impl Clock for InstantClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// A clock for tests, moved forward by hand. It's part of the library (not just its
+// own tests) so the front-end's tests can use it too.
+// `Rc<Cell<...>>` lets a test keep a copy of the clock after handing one to the
+// game: both copies share the same time, so advancing one advances the other.
+#[derive(Clone)]
+pub struct MockClock {
+    now: std::rc::Rc<std::cell::Cell<Instant>>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        MockClock {
+            now: std::rc::Rc::new(std::cell::Cell::new(Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, by: std::time::Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
@@ -0,0 +1,201 @@
+// Comparing two runs (`--compare A B`): two scripts (see `script.rs`) are played
+// side by side from the same starting board, with the same random seed, one tick
+// at a time. The report says where they first part ways: the first tick their
+// turns differ, and the first tick after which the boards differ.
+//
+// Comparing a script with itself is a determinism check. Both runs get the same
+// turns and the same food, so if their boards still drift apart, something in
+// `Game::update` depends on more than the seed and the turns (the iteration order
+// of a `HashSet`, say, which differs from one set to the next).
+
+use rand::{SeedableRng, rngs::StdRng};
+use serde_json::{Map, Value};
+
+use crate::{Direction, Game, Settings, save::SaveGame, script::Script};
+
+// The seed both runs use, so food and pickups land in the same places in each.
+const SEED: u64 = 0;
+
+// Give up after this many ticks if neither run has ended.
+const MAX_TICKS: u64 = 100_000;
+
+// Values longer than this are cut short in the report (a snake's body can be long).
+const MAX_VALUE_LEN: usize = 60;
+
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    // How many ticks were played.
+    pub ticks: u64,
+    // The first tick the scripts turned differently, and each one's turns on it.
+    pub input: Option<(u64, Vec<Direction>, Vec<Direction>)>,
+    // The first tick after which the boards differed, and each part of the board that did.
+    pub state: Option<(u64, Vec<Difference>)>,
+}
+
+// One part of the board (a field of the save, like `score`) that differs between runs.
+#[derive(Debug, PartialEq)]
+pub struct Difference {
+    pub name: String,
+    // Its value in each run, as JSON (cut short if it's long).
+    pub a: String,
+    pub b: String,
+}
+
+impl Report {
+    // The report as a few lines of text, for printing.
+    pub fn describe(&self) -> String {
+        let mut text = format!("Played {} ticks.\n", self.ticks);
+        match &self.input {
+            Some((tick, a, b)) => {
+                text += &format!(
+                    "Turns first differ on tick {}: A turns {}, B turns {}.\n",
+                    tick,
+                    names(a),
+                    names(b)
+                )
+            }
+            None => text += "Both runs make the same turns.\n",
+        }
+        match &self.state {
+            Some((tick, parts)) => {
+                text += &format!("Boards first differ after tick {}:\n", tick);
+                for part in parts {
+                    text += &format!("  {}: A {}, B {}\n", part.name, part.a, part.b);
+                }
+            }
+            None => text += "The boards never differ.\n",
+        }
+        text
+    }
+}
+
+// "UP+LEFT" for a tick's turns, or "nothing".
+fn names(turns: &[Direction]) -> String {
+    if turns.is_empty() {
+        return "nothing".to_string();
+    }
+    turns
+        .iter()
+        .map(|direction| direction.name())
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+// Plays scripts `a` and `b` in lockstep from the board in `start`, both under the
+// rules in `settings`. Stops once the boards differ, or both games are over.
+pub fn compare(
+    start: &SaveGame,
+    settings: &Settings,
+    mut a: Script,
+    mut b: Script,
+) -> Result<Report, String> {
+    let new_game = || -> Result<Game, String> {
+        let mut game = start.clone().into_game()?;
+        game.settings = settings.clone();
+        game.shields = settings.shields;
+        game.rng = StdRng::seed_from_u64(SEED);
+        Ok(game)
+    };
+    let mut games = [new_game()?, new_game()?];
+    let mut report = Report {
+        ticks: 0,
+        input: None,
+        state: None,
+    };
+
+    while report.ticks < MAX_TICKS && !games.iter().all(|game| game.game_over) {
+        let tick = report.ticks + 1;
+        let turns = [a.due(tick), b.due(tick)];
+        if report.input.is_none() && turns[0] != turns[1] {
+            report.input = Some((tick, turns[0].clone(), turns[1].clone()));
+        }
+        for (game, turns) in games.iter_mut().zip(turns) {
+            for direction in turns {
+                game.turn(0, direction);
+            }
+            game.update();
+        }
+        report.ticks = tick;
+
+        let parts = differences(&state(&games[0]), &state(&games[1]));
+        if !parts.is_empty() {
+            report.state = Some((tick, parts));
+            break;
+        }
+    }
+    Ok(report)
+}
+
+// Everything about the board worth comparing, by name: what a save holds, plus
+// whether the game has ended.
+fn state(game: &Game) -> Map<String, Value> {
+    let value =
+        serde_json::to_value(SaveGame::from_game(game)).expect("a save is always valid JSON");
+    let Value::Object(mut state) = value else {
+        unreachable!("a save is a JSON object");
+    };
+    state.insert("game_over".to_string(), game.game_over.into());
+    state.insert("game_won".to_string(), game.game_won.into());
+    state
+}
+
+// The parts of two states that differ.
+fn differences(a: &Map<String, Value>, b: &Map<String, Value>) -> Vec<Difference> {
+    a.iter()
+        .filter(|&(name, value)| b.get(name) != Some(value))
+        .map(|(name, value)| Difference {
+            name: name.clone(),
+            a: short(value),
+            b: short(b.get(name).unwrap_or(&Value::Null)),
+        })
+        .collect()
+}
+
+// A value as JSON, cut short if it's long.
+fn short(value: &Value) -> String {
+    let text = value.to_string();
+    if text.chars().count() <= MAX_VALUE_LEN {
+        return text;
+    }
+    let cut: String = text.chars().take(MAX_VALUE_LEN - 3).collect();
+    cut + "..."
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Point;
+
+    fn start() -> SaveGame {
+        let game = Game::new(20, 10, None, Some(Point { x: 15, y: 5 })).unwrap();
+        SaveGame::from_game(&game)
+    }
+
+    #[test]
+    fn a_script_matches_itself_until_the_turns_differ() {
+        let script = Script::parse("t3 UP\nt5 RIGHT\nt6 DOWN").unwrap();
+        let same = compare(
+            &start(),
+            &Settings::default(),
+            script.clone(),
+            script.clone(),
+        )
+        .unwrap();
+        assert_eq!(same.input, None);
+        assert_eq!(same.state, None);
+        assert!(same.ticks > 6);
+        assert!(same.describe().contains("never differ"));
+
+        let other = Script::parse("t3 UP\nt5 LEFT").unwrap();
+        let report = compare(&start(), &Settings::default(), script, other).unwrap();
+        assert_eq!(
+            report.input,
+            Some((5, vec![Direction::Right], vec![Direction::Left]))
+        );
+        let (tick, parts) = report.state.unwrap();
+        assert_eq!(tick, 5);
+        let names: Vec<&str> = parts.iter().map(|part| part.name.as_str()).collect();
+        assert_eq!(names, ["snakes"]);
+        assert!(parts[0].a.ends_with("..."));
+    }
+}
@@ -0,0 +1,273 @@
+// Saved preferences, and the settings screen for changing them (Settings on the
+// main menu, see `menu.rs`, or `--settings` to go straight there).
+//
+// The preferences live in `config.json` (e.g. `~/.config/hello_rust/config.json`
+// on Linux) and are the starting point for every game: command-line flags are
+// applied on top, so a flag still switches a setting on for one run. The key
+// bindings live there too, under `keys` (see `keys.rs`); the settings screen
+// doesn't change them, so they're edited in the file itself.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crossterm::{
+    QueueableCommand,
+    cursor::MoveTo,
+    event::{KeyCode, KeyModifiers},
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{Clear, ClearType},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{SpeedCurve, cli::Options, game_loop::Input, keys::KeyBindings, theme::THEMES};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+// `#[serde(default)]` fills in any field missing from the file with its default,
+// so a config written by an older version still loads.
+#[serde(default)]
+pub struct Config {
+    // Beep when carrying straight on would be fatal (the audio assist).
+    pub sound: bool,
+    // The color theme's name (see `theme.rs`).
+    pub theme: String,
+    // How the speed rises with the level (like `--speed-curve`).
+    pub speed_curve: SpeedCurve,
+    // The control scheme: with `diagonal`, the numpad corners steer diagonally too.
+    pub diagonal: bool,
+    // Start with wrap-around walls (still toggled with 'b' while playing).
+    pub wrap: bool,
+    // The keys for steering, pausing, quitting and retrying.
+    pub keys: KeyBindings,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            sound: false,
+            theme: THEMES[0].name.to_string(),
+            speed_curve: SpeedCurve::Flat,
+            diagonal: false,
+            wrap: false,
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+impl Config {
+    // Where the config lives by default. Unlike the leaderboard, it goes in the
+    // platform's config folder rather than its data folder.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("hello_rust").join("config.json"))
+    }
+
+    // Reads the config from `path`. Like the leaderboard, a missing or corrupt
+    // file just gives the defaults.
+    pub fn load(path: &Path) -> Config {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    // Writes the config to `path`, creating the folder if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self).expect("a config is always valid JSON");
+        fs::write(path, json)
+    }
+
+    // Makes these preferences the starting point for `options`.
+    pub fn apply(&self, options: &mut Options) {
+        options.settings.audio_assist = self.sound;
+        // An unknown theme name (say, from a newer version) falls back to the first theme.
+        options.theme = THEMES
+            .iter()
+            .position(|theme| theme.name == self.theme)
+            .unwrap_or(0);
+        options.settings.speed_curve = self.speed_curve;
+        options.settings.diagonal = self.diagonal;
+        options.settings.wrap = self.wrap;
+        options.keys = self.keys.clone();
+    }
+}
+
+// The rows of the settings screen, top to bottom.
+const ROWS: [&str; 5] = ["Sound", "Theme", "Speed curve", "Controls", "Wrap walls"];
+
+// Every speed curve, in the order the settings screen cycles through them.
+const SPEED_CURVES: [SpeedCurve; 4] = [
+    SpeedCurve::Flat,
+    SpeedCurve::Linear,
+    SpeedCurve::Exponential,
+    SpeedCurve::Logarithmic,
+];
+
+// The settings screen: the config being edited, and which row is selected.
+pub struct SettingsMenu {
+    config: Config,
+    selected: usize,
+}
+
+impl SettingsMenu {
+    pub fn new(config: Config) -> SettingsMenu {
+        SettingsMenu {
+            config,
+            selected: 0,
+        }
+    }
+
+    // The value shown for `row`.
+    fn value(&self, row: usize) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        match row {
+            0 => on_off(self.config.sound),
+            1 => self.config.theme.clone(),
+            2 => format!("{:?}", self.config.speed_curve).to_lowercase(),
+            3 if self.config.diagonal => "arrows + numpad diagonals".to_string(),
+            3 => "arrows".to_string(),
+            _ => on_off(self.config.wrap),
+        }
+    }
+
+    // Moves the selection up (`-1`) or down (`1`), wrapping around at the ends.
+    fn select(&mut self, step: isize) {
+        self.selected = (self.selected as isize + step).rem_euclid(ROWS.len() as isize) as usize;
+    }
+
+    // Changes the selected row to its next (`1`) or previous (`-1`) value.
+    fn cycle(&mut self, step: isize) {
+        // The index `step` places on from `index` in a list of `len`, wrapping around.
+        let next =
+            |index: usize, len: usize| (index as isize + step).rem_euclid(len as isize) as usize;
+        let config = &mut self.config;
+        match self.selected {
+            0 => config.sound = !config.sound,
+            1 => {
+                let current = THEMES
+                    .iter()
+                    .position(|theme| theme.name == config.theme)
+                    .unwrap_or(0);
+                config.theme = THEMES[next(current, THEMES.len())].name.to_string();
+            }
+            2 => {
+                let current = SPEED_CURVES
+                    .iter()
+                    .position(|&curve| curve == config.speed_curve)
+                    .unwrap_or(0);
+                config.speed_curve = SPEED_CURVES[next(current, SPEED_CURVES.len())];
+            }
+            3 => config.diagonal = !config.diagonal,
+            _ => config.wrap = !config.wrap,
+        }
+    }
+
+    // Draws the whole screen, with the selected row highlighted.
+    fn draw(&self, out: &mut impl io::Write) -> io::Result<()> {
+        out.queue(Clear(ClearType::All))?
+            .queue(MoveTo(2, 1))?
+            .queue(SetForegroundColor(Color::Green))?
+            .queue(Print("SETTINGS"))?;
+        for (row, label) in ROWS.iter().enumerate() {
+            let (marker, color) = if row == self.selected {
+                ("> ", Color::Yellow)
+            } else {
+                ("  ", Color::White)
+            };
+            out.queue(MoveTo(2, 3 + row as u16))?
+                .queue(SetForegroundColor(color))?
+                .queue(Print(format!(
+                    "{}{:<12} {}",
+                    marker,
+                    label,
+                    self.value(row)
+                )))?;
+        }
+        out.queue(MoveTo(2, 4 + ROWS.len() as u16))?
+            .queue(SetForegroundColor(Color::DarkGrey))?
+            .queue(Print(
+                "Up/Down choose, Left/Right/Enter change, Q save and quit",
+            ))?
+            .queue(ResetColor)?;
+        out.flush()
+    }
+
+    // Runs the screen until the player leaves it, then returns the edited config.
+    pub fn run(mut self, input: &mut impl Input, out: &mut impl io::Write) -> io::Result<Config> {
+        loop {
+            self.draw(out)?;
+            // Nothing changes on its own here, so just wait for the next key.
+            let Some(key) = input.poll(Duration::from_millis(500))? else {
+                continue;
+            };
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(self.config),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(self.config);
+                }
+                KeyCode::Up | KeyCode::Char('w') => self.select(-1),
+                KeyCode::Down | KeyCode::Char('s') => self.select(1),
+                KeyCode::Left | KeyCode::Char('a') => self.cycle(-1),
+                KeyCode::Right | KeyCode::Char('d') | KeyCode::Enter | KeyCode::Char(' ') => {
+                    self.cycle(1)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_loop::testing::Keys;
+
+    #[test]
+    fn settings_screen_edits_and_saves_the_config() {
+        // Sound on; theme forward one; speed curve back one (wrapping to the last);
+        // up from the top wraps round to the wrap walls row.
+        let keys = vec![
+            KeyCode::Enter,
+            KeyCode::Down,
+            KeyCode::Right,
+            KeyCode::Down,
+            KeyCode::Left,
+            KeyCode::Up,
+            KeyCode::Up,
+            KeyCode::Up,
+            KeyCode::Right,
+            KeyCode::Char('q'),
+        ];
+        let mut screen = Vec::new();
+        let config = SettingsMenu::new(Config::default())
+            .run(&mut Keys(keys), &mut screen)
+            .unwrap();
+        let expected = Config {
+            sound: true,
+            theme: THEMES[1].name.to_string(),
+            speed_curve: SpeedCurve::Logarithmic,
+            diagonal: false,
+            wrap: true,
+            keys: KeyBindings::default(),
+        };
+        assert_eq!(config, expected);
+        assert!(String::from_utf8_lossy(&screen).contains("Speed curve"));
+
+        let dir = std::env::temp_dir().join(format!("hello_rust_config_{}", std::process::id()));
+        let path = dir.join("config.json");
+        assert_eq!(Config::load(&path), Config::default());
+        config.save(&path).unwrap();
+        assert_eq!(Config::load(&path), config);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut options = Options::default();
+        config.apply(&mut options);
+        assert!(options.settings.audio_assist && options.settings.wrap);
+        assert_eq!(options.theme, 1);
+        assert_eq!(options.settings.speed_curve, SpeedCurve::Logarithmic);
+    }
+}
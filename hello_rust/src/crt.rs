@@ -0,0 +1,88 @@
+// `--crt`: an old-monitor look, with every other row of the board a shade darker,
+// like the scanlines on a CRT screen.
+//
+// Each frame starts from a cleared screen, so the dark rows are painted first
+// (see `Game::draw`), and then everything drawn on the board has to keep its
+// row's background, or it would punch a hole in the stripe. `CellAt` does that:
+// it moves the cursor and sets the background for that row in one command, so
+// the code drawing the food, snakes and so on doesn't need to know about it.
+
+use std::fmt;
+
+use crossterm::{
+    Command,
+    cursor::MoveTo,
+    style::{Color, SetBackgroundColor},
+};
+
+// The background of the darker rows: just off black, so it's subtle.
+pub const SCANLINE: Color = Color::Rgb {
+    r: 28,
+    g: 28,
+    b: 28,
+};
+
+// The background for screen row `y`: odd rows are scanlines.
+pub fn row_background(y: u16) -> Color {
+    if y % 2 == 1 { SCANLINE } else { Color::Reset }
+}
+
+// Moves the cursor to a board cell and, in CRT mode, sets the background for its row.
+pub struct CellAt {
+    x: u16,
+    y: u16,
+    background: Option<Color>,
+}
+
+impl CellAt {
+    pub fn new(x: u16, y: u16, crt: bool) -> CellAt {
+        CellAt {
+            x,
+            y,
+            background: crt.then(|| row_background(y)),
+        }
+    }
+}
+
+// Implementing crossterm's `Command` trait lets `CellAt` be queued like any
+// built-in command. It just writes the two commands it's made of, one after the other.
+impl Command for CellAt {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        MoveTo(self.x, self.y).write_ansi(f)?;
+        match self.background {
+            Some(color) => SetBackgroundColor(color).write_ansi(f),
+            None => Ok(()),
+        }
+    }
+
+    // Old Windows consoles don't understand the escape codes above, so crossterm
+    // asks for the equivalent system calls instead.
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> std::io::Result<()> {
+        MoveTo(self.x, self.y).execute_winapi()?;
+        match self.background {
+            Some(color) => SetBackgroundColor(color).execute_winapi(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::QueueableCommand;
+
+    #[test]
+    fn cells_keep_their_rows_background() {
+        let mut out = Vec::new();
+        out.queue(CellAt::new(3, 1, true)).unwrap();
+        out.queue(CellAt::new(3, 2, true)).unwrap();
+        out.queue(CellAt::new(3, 1, false)).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        // (Escape codes count from 1, so cell 3,1 is row 2, column 4.)
+        assert_eq!(
+            text,
+            "\x1b[2;4H\x1b[48;2;28;28;28m\x1b[3;4H\x1b[49m\x1b[2;4H"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+// A running set of the board's free cells (`--free-cell-cache`).
+//
+// Normally, finding a spot for new food means scanning the board for cells that
+// aren't snake or obstacle. On a big map that's a lot of cells to look at every
+// time the snake eats. Instead, the game can keep this set up to date as things
+// move (see `Game::occupy` and `Game::vacate`), and pick from it directly.
+//
+// A `HashSet` can tell us quickly whether a cell is free, but can't hand us a
+// random one without walking through it. So the cells live in a `Vec` (easy to
+// pick from at random), and a `HashMap` remembers where each one is in it (so one
+// can be found and removed without searching).
+
+use std::collections::HashMap;
+
+use rand::Rng;
+
+use crate::Point;
+
+#[derive(Debug, Default, Clone)]
+pub struct FreeCells {
+    cells: Vec<Point>,
+    // Where each cell is in `cells`.
+    index: HashMap<Point, usize>,
+}
+
+impl FreeCells {
+    // Adds a cell. Returns false if it was already there.
+    pub fn insert(&mut self, cell: Point) -> bool {
+        if self.index.contains_key(&cell) {
+            return false;
+        }
+        self.index.insert(cell, self.cells.len());
+        self.cells.push(cell);
+        true
+    }
+
+    // Removes a cell. Returns false if it wasn't there.
+    pub fn remove(&mut self, cell: Point) -> bool {
+        let Some(i) = self.index.remove(&cell) else {
+            return false;
+        };
+        // `swap_remove` fills the gap with the last cell instead of shifting all
+        // the later ones down, so the moved cell's position needs updating.
+        self.cells.swap_remove(i);
+        if let Some(&moved) = self.cells.get(i) {
+            self.index.insert(moved, i);
+        }
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    // A random free cell, or `None` if there are none.
+    pub fn pick(&self, rng: &mut impl Rng) -> Option<Point> {
+        if self.cells.is_empty() {
+            return None;
+        }
+        Some(self.cells[rng.gen_range(0..self.cells.len())])
+    }
+
+    // Every free cell, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = Point> + '_ {
+        self.cells.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    #[test]
+    fn removing_keeps_the_index_in_step() {
+        let mut free = FreeCells::default();
+        let cells: Vec<Point> = (0..5).map(|x| Point { x, y: 1 }).collect();
+        for &cell in &cells {
+            assert!(free.insert(cell));
+        }
+        assert!(!free.insert(cells[0]));
+        // Removing from the middle moves the last cell into the gap.
+        assert!(free.remove(cells[1]));
+        assert!(!free.remove(cells[1]));
+        assert!(free.remove(cells[4]));
+        assert_eq!(free.len(), 3);
+        let mut left: Vec<Point> = free.iter().collect();
+        left.sort_by_key(|p| p.x);
+        assert_eq!(left, [cells[0], cells[2], cells[3]]);
+        for cell in left {
+            assert!(free.remove(cell));
+        }
+        assert_eq!(free.pick(&mut StdRng::seed_from_u64(0)), None);
+    }
+}
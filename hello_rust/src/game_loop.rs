@@ -0,0 +1,970 @@
+// The game loop: read input, advance the game on every tick, draw a frame.
+//
+// The loop doesn't talk to the terminal itself. It reads keys through the `Input`
+// trait and draws through the `Renderer` trait, and the terminal is just one
+// implementation of each (`TerminalInput` and `TerminalRenderer`, below). Tests
+// drive the very same loop with scripted keys and a renderer that only counts
+// frames, and another front-end could plug in its own pair.
+
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    QueueableCommand,
+    cursor::{Hide, MoveTo, Show},
+    event::{
+        self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyModifiers,
+    },
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
+};
+
+use crate::{
+    Direction, Game, bot, cheats, cli,
+    keys::{Action, KeyBindings},
+    perf,
+    render::{self, Draw, PANEL_WIDTH},
+    replay, save, scores, screen, signals, spectate,
+};
+
+// How long a notice like "Game saved" stays under the board.
+const NOTICE_TIME: Duration = Duration::from_secs(2);
+
+// Where the loop gets key presses from.
+pub trait Input {
+    // Waits up to `timeout` for a key press. `Ok(None)` means none came in time
+    // (or something other than a key happened, which the loop doesn't care about).
+    fn poll(&mut self, timeout: Duration) -> io::Result<Option<KeyEvent>>;
+
+    // Turns that don't come from keys, as (player, direction), to make on game
+    // tick `tick`; asked just before that tick's move. Only a script (`script.rs`)
+    // or a player over the network (`net.rs`) has any.
+    fn turns(&mut self, _tick: u64) -> Vec<(usize, Direction)> {
+        Vec::new()
+    }
+}
+
+// A boxed `Input` is an `Input` too, so inputs can be picked (and wrapped in
+// each other) at run time, as `Box<dyn Input>`.
+impl<T: Input + ?Sized> Input for Box<T> {
+    fn poll(&mut self, timeout: Duration) -> io::Result<Option<KeyEvent>> {
+        (**self).poll(timeout)
+    }
+
+    fn turns(&mut self, tick: u64) -> Vec<(usize, Direction)> {
+        (**self).turns(tick)
+    }
+}
+
+// Where the loop draws its frames.
+pub trait Renderer {
+    // Draws one whole frame: the board (or the game over screen once the game has
+    // ended), with `message`, if any, on the line under the board.
+    fn draw(&mut self, game: &Game, message: Option<&str>) -> io::Result<()>;
+
+    // The audio assist's warning. It sounds with the next frame.
+    fn beep(&mut self) -> io::Result<()>;
+
+    // Ctrl+Z: hands the terminal back to the shell until the game is resumed
+    // (`fg`). Front-ends without job control needn't do anything.
+    fn suspend(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    // After the game was stopped some other way (say `kill -STOP`) and then
+    // continued: sets the screen up for the game again, to be drawn afresh.
+    fn resume(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Whether the whole board fits on screen right now. The game pauses while it
+    // doesn't. Front-ends without a screen size always fit.
+    fn fits(&self, _game: &Game) -> bool {
+        true
+    }
+
+    // Draws the leaderboard screen instead of the board. Front-ends that can't
+    // show it needn't do anything.
+    fn draw_scores(&mut self, _board: &scores::Leaderboard) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Like `Input`, a boxed `Renderer` is a `Renderer`.
+impl<T: Renderer + ?Sized> Renderer for Box<T> {
+    fn draw(&mut self, game: &Game, message: Option<&str>) -> io::Result<()> {
+        (**self).draw(game, message)
+    }
+
+    fn beep(&mut self) -> io::Result<()> {
+        (**self).beep()
+    }
+
+    fn suspend(&mut self) -> io::Result<()> {
+        (**self).suspend()
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        (**self).resume()
+    }
+
+    fn fits(&self, game: &Game) -> bool {
+        (**self).fits(game)
+    }
+
+    fn draw_scores(&mut self, board: &scores::Leaderboard) -> io::Result<()> {
+        (**self).draw_scores(board)
+    }
+}
+
+// What the loop needs from the program around it, besides keys and a screen: a
+// new game for each retry, and somewhere to keep finished runs. The terminal
+// front-end keeps runs in files in the data folder (see `main.rs`); tests keep
+// none.
+pub trait Session {
+    // A new game to retry with, or why there can't be one.
+    fn new_game(&mut self) -> Result<Game, String>;
+
+    // Adds a finished run to the leaderboard. Returns its entry if it made the
+    // cut, so the player can name it.
+    fn record_score(&mut self, game: &Game) -> Option<scores::Entry>;
+
+    // Gives the leaderboard's `entry` the name the player typed, and says how it went.
+    fn name_score(&mut self, entry: &scores::Entry, name: &str) -> String;
+
+    // Adds the cell the snake crashed into (if it crashed) to the death heatmap.
+    fn record_death(&mut self, game: &Game);
+
+    // Keeps `run`, which just set a new high score, as the best run to watch
+    // (`--watch-best`), in place of the last one.
+    fn keep_best_run(&mut self, run: &replay::Replay) -> io::Result<()>;
+}
+
+// Checks a `board` (width, height) fits in a terminal `size` big (columns, rows),
+// with a row to spare for the status line below it. Anything bigger wraps around
+// the edge of the terminal and garbles the screen.
+pub fn check_fits(board: (u16, u16), size: (u16, u16)) -> Result<(), String> {
+    let (columns, rows) = size;
+    if board.0 > columns || board.1 >= rows {
+        return Err(format!(
+            "a {}x{} board needs a terminal at least {}x{}, but this one is {}x{} \
+             (make it bigger, or try --width, --height or --shrink-to-fit)",
+            board.0, board.1, board.0, board.1 + 1, columns, rows
+        ));
+    }
+    Ok(())
+}
+
+// Saves the game to `path` and says how it went, for showing under the board.
+fn save_game(game: &Game, path: &std::path::Path) -> String {
+    match save::SaveGame::from_game(game).save(path) {
+        Ok(()) => "Game saved".to_string(),
+        Err(err) => format!("Save failed: {}", err),
+    }
+}
+
+// Key presses from the terminal, via crossterm.
+pub struct TerminalInput;
+
+impl Input for TerminalInput {
+    fn poll(&mut self, timeout: Duration) -> io::Result<Option<KeyEvent>> {
+        if !event::poll(timeout)? {
+            return Ok(None);
+        }
+        Ok(match event::read()? {
+            Event::Key(key) => Some(key),
+            // Anything else isn't a key press, and is dropped:
+            // - A paste (with bracketed paste on) is ignored as a whole, so pasted
+            //   text can't steer the snake, toggle modes or quit. Terminals without
+            //   bracketed paste send each character as a key press instead; those
+            //   can only queue a couple of turns at a time (see `Snake::turn`).
+            // - There's nothing to do on a resize here: the renderer sees the new
+            //   size on its next frame, and draws that one in full (see `screen.rs`).
+            _ => None,
+        })
+    }
+}
+
+// Draws on the terminal (through `out`, which is usually stdout, possibly
+// recorded by `--asciicast`), and sends each frame to any `--serve` spectators.
+// Each frame is drawn into `frame` first, and only what changed since the last
+// one goes out to the terminal (see `screen.rs`).
+pub struct TerminalRenderer<W: Write> {
+    out: W,
+    // `--title`: show the score and level in the window title too.
+    title: bool,
+    // The window title we last sent, so we only send a new one when it changes.
+    shown_title: String,
+    spectators: Option<spectate::Server>,
+    // Whether we draw on the alternate screen (and so must leave and re-enter it on Ctrl+Z).
+    alt_screen: bool,
+    // This frame, drawn in memory (cleared, but not freed, each time).
+    frame: Vec<u8>,
+    screen: screen::Screen,
+    // The game over screen's line about which keys retry and quit.
+    game_over_hint: String,
+}
+
+impl<W: Write> TerminalRenderer<W> {
+    pub fn new(
+        out: W,
+        title: bool,
+        spectators: Option<spectate::Server>,
+        alt_screen: bool,
+        keys: &KeyBindings,
+    ) -> TerminalRenderer<W> {
+        TerminalRenderer {
+            game_over_hint: render::game_over_hint(keys),
+            out,
+            title,
+            shown_title: String::new(),
+            spectators,
+            alt_screen,
+            frame: Vec::new(),
+            screen: screen::Screen::default(),
+        }
+    }
+}
+
+impl<W: Write> Renderer for TerminalRenderer<W> {
+    fn fits(&self, game: &Game) -> bool {
+        // If we can't tell the size, just draw and hope for the best.
+        terminal::size().map_or(true, |size| check_fits(game.dimensions(), size).is_ok())
+    }
+
+    fn draw(&mut self, game: &Game, message: Option<&str>) -> io::Result<()> {
+        // Spectators get the board as plain text, with the score underneath.
+        if let Some(server) = &mut self.spectators {
+            let frame = format!(
+                "{}Score: {}  Level: {}\n",
+                game.render_ascii(),
+                game.score(),
+                game.level()
+            );
+            server.broadcast(&frame);
+        }
+
+        if self.title {
+            let title = game.window_title();
+            if title != self.shown_title {
+                self.out.queue(SetTitle(&title))?;
+                self.shown_title = title;
+            }
+        }
+
+        // Each frame starts from a blank screen.
+        self.frame.clear();
+        let fits = self.fits(game);
+        let frame = &mut self.frame;
+        let size = terminal::size()?;
+        let (columns, rows) = size;
+        if !fits {
+            // A terminal shrunk below the board's size would wrap the board's rows
+            // into a mess, so say what's wrong instead until it's big enough again.
+            let (width, height) = game.dimensions();
+            frame
+                .queue(MoveTo(0, 0))?
+                .queue(SetForegroundColor(Color::Yellow))?
+                .queue(Print(format!("Terminal too small: {}x{}", columns, rows)))?
+                .queue(MoveTo(0, 1))?
+                .queue(Print(format!("The game needs {}x{}", width, height + 1)))?
+                .queue(ResetColor)?;
+        } else if game.is_over() {
+            game.draw_game_over(frame, &self.game_over_hint)?;
+        } else {
+            game.draw(frame)?;
+            // The side panel, if the terminal has room for it right of the board.
+            if columns >= game.width + 1 + PANEL_WIDTH {
+                game.draw_panel(frame)?;
+            }
+        }
+        if let Some(message) = message
+            && fits
+        {
+            frame
+                .queue(MoveTo(0, game.height))?
+                .queue(SetForegroundColor(Color::Yellow))?
+                .queue(Print(format!(" {} ", message)))?
+                .queue(ResetColor)?;
+        }
+
+        // Send the terminal what changed, and flush (actually draw it now).
+        self.screen.show(&self.frame, size, &mut self.out)?;
+        self.out.flush()
+    }
+
+    fn draw_scores(&mut self, board: &scores::Leaderboard) -> io::Result<()> {
+        self.frame.clear();
+        board.draw_screen(&mut self.frame)?;
+        self.screen.show(&self.frame, terminal::size()?, &mut self.out)?;
+        self.out.flush()
+    }
+
+    fn beep(&mut self) -> io::Result<()> {
+        // The ASCII "bell" character.
+        self.out.queue(Print("\x07"))?;
+        Ok(())
+    }
+
+    // In raw mode the terminal doesn't turn Ctrl+Z into a SIGTSTP signal for us;
+    // it arrives as an ordinary key. So we put the terminal back the way the shell
+    // expects it (like `CleanUp` does on exit), then stop ourselves with SIGTSTP.
+    // That only returns once the shell sends SIGCONT (`fg`), and then we set the
+    // terminal up for the game again.
+    #[cfg(unix)]
+    fn suspend(&mut self) -> io::Result<()> {
+        if self.alt_screen {
+            self.out.queue(LeaveAlternateScreen)?;
+        }
+        self.out.queue(DisableBracketedPaste)?.queue(Show)?;
+        self.out.flush()?;
+        terminal::disable_raw_mode()?;
+        signals::stop();
+        self.resume()
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        terminal::enable_raw_mode()?;
+        if self.alt_screen {
+            self.out.queue(EnterAlternateScreen)?;
+        }
+        self.out.queue(Hide)?.queue(EnableBracketedPaste)?;
+        // The shell may have changed the window title meanwhile, so send ours
+        // again, and the screen too.
+        self.shown_title.clear();
+        self.screen.invalidate();
+        self.out.flush()
+    }
+}
+
+// `--quick-turns` never moves the snake sooner than this fraction of a tick
+// after its last move, so however fast turns come in, it at most doubles its speed.
+const QUICK_TURN_MIN: f64 = 0.5;
+
+// Is the next move due, `since_move` after the last one? Normally that's once a
+// whole tick has passed. With `quick_turns`, a turn the player has made (`turned`)
+// brings the move forward, so it happens at once instead of at the end of the
+// tick, but not before `QUICK_TURN_MIN` of the tick has gone by.
+fn move_due(since_move: Duration, tick_rate: Duration, turned: bool, quick_turns: bool) -> bool {
+    let wait = if turned && quick_turns {
+        tick_rate.mul_f64(QUICK_TURN_MIN)
+    } else {
+        tick_rate
+    };
+    since_move >= wait
+}
+
+// Runs the game until the player quits. `options` supplies what the loop needs
+// beyond the game itself (the save file, cheats, the keys); `session` builds
+// retries and keeps finished runs; `perf_log`, if given, gets each frame's
+// timings (see `perf.rs`).
+pub fn run_game(
+    input: &mut impl Input,
+    renderer: &mut impl Renderer,
+    game: &mut Game,
+    options: &cli::Options,
+    session: &mut impl Session,
+    mut perf_log: Option<&mut perf::PerfLog>,
+) -> io::Result<()> {
+    // Timer for our game loop. It reads the game's clock (see `clock.rs`), so
+    // tests can run the loop on a clock they move forward themselves.
+    let mut last_frame = game.clock.now();
+    // Remembers whether we already warned about the current danger, so the
+    // audio assist beeps once per danger instead of on every tick.
+    let mut warned = false;
+    // Set once this run has been written to the leaderboard.
+    let mut recorded = false;
+    // When we last advanced the real-time timers (see `Game::update_timers`).
+    let mut last_timer_update = game.clock.now();
+
+    // Where Ctrl+S saves the game.
+    let save_path = options.save_path();
+    // While asking "overwrite the existing save?", this holds whether the game was
+    // paused before we asked (we pause it while the question is on screen).
+    let mut confirm_overwrite: Option<bool> = None;
+    // A short message shown under the board for a moment (e.g. "Game saved"), and when it appeared.
+    let mut notice: Option<(String, Instant)> = None;
+
+    // After a run that made the leaderboard: the box its name is being typed into.
+    let mut naming: Option<scores::NameEntry> = None;
+    // The leaderboard, while it's on screen (L on the game over screen).
+    let mut showing_scores: Option<scores::Leaderboard> = None;
+
+    // `--record`: the run so far, saved as a replay when it ends (see `replay.rs`).
+    let mut recording = options
+        .record
+        .as_ref()
+        .map(|_| replay::Replay::record(game, options.seed));
+
+    // `--cheats`: watches the typed letters for cheat words (see `cheats.rs`).
+    let mut cheat_matcher = options.cheats.then(cheats::CheatMatcher::default);
+
+    // Which snake the WASD keys steer: player two in co-op or versus, otherwise the only snake.
+    let wasd_player = game.snakes.len() - 1;
+    // Set when the player's turn is waiting for the next move (for `--quick-turns`).
+    let mut turned = false;
+    // How many turns are waiting, to see if a key adds one.
+    let queued = |game: &Game| game.snakes.iter().map(|s| s.queued_turns.len()).sum::<usize>();
+
+    // Set after the game comes back from being suspended or stopped, to carry on
+    // paused and fully redrawn, with the time away not counted.
+    let mut resumed = false;
+
+    // Infinite game loop
+    loop {
+        // Job control from outside the game (see `signals.rs`): SIGTSTP suspends
+        // it just like Ctrl+Z, and once it's continued after a stop it didn't ask
+        // for (`kill -STOP`), the terminal is set up for it again.
+        if signals::stop_asked() {
+            renderer.suspend()?;
+            resumed = true;
+        } else if signals::continued() {
+            renderer.resume()?;
+            resumed = true;
+        }
+        if std::mem::take(&mut resumed) {
+            if !game.is_over() {
+                game.set_paused(true);
+            }
+            last_frame = game.clock.now();
+            last_timer_update = game.clock.now();
+            renderer.draw(game, None)?;
+        }
+
+        // A terminal too small for the board pauses the game; it stays paused once
+        // the terminal is big enough again, until the player resumes.
+        if !game.paused && !game.is_over() && !renderer.fits(game) {
+            game.set_paused(true);
+        }
+
+        // --- Input Handling ---
+        let queued_before = queued(game);
+
+        // Check for a key press without waiting (a 0ms timeout).
+        if let Some(key) = input.poll(Duration::from_millis(0))? {
+            if let Some(was_paused) = confirm_overwrite {
+                // Waiting for an answer: only 'y' and 'n' (or Esc) do anything.
+                let answer = match key.code {
+                    KeyCode::Char('y') => Some(true),
+                    KeyCode::Char('n') | KeyCode::Esc => Some(false),
+                    _ => None,
+                };
+                if let (Some(overwrite), Some(path)) = (answer, &save_path) {
+                    let message = if overwrite {
+                        save_game(game, path)
+                    } else {
+                        "Not saved".to_string()
+                    };
+                    notice = Some((message, Instant::now()));
+                    confirm_overwrite = None;
+                    game.set_paused(was_paused);
+                }
+            } else if let Some(entry) = &mut naming {
+                // Typing a name for the leaderboard: every key goes into the box.
+                match entry.key(key) {
+                    scores::Typed::Editing => {}
+                    scores::Typed::Done(name) if !name.is_empty() => {
+                        notice = Some((session.name_score(&entry.entry, &name), Instant::now()));
+                        naming = None;
+                    }
+                    scores::Typed::Done(_) | scores::Typed::Cancelled => naming = None,
+                }
+            } else if showing_scores.is_some() {
+                // Any key closes the leaderboard.
+                showing_scores = None;
+            } else {
+                // Feed letters to the cheat matcher; a completed word triggers its effect.
+                if let Some(matcher) = &mut cheat_matcher
+                    && let KeyCode::Char(c) = key.code
+                    && let Some(index) = matcher.push(c)
+                {
+                    let cheat = &cheats::CHEATS[index];
+                    (cheat.effect)(game);
+                    game.assisted = true;
+                    notice = Some((format!("Cheat: {}", cheat.name), Instant::now()));
+                }
+
+                // The bound keys first (see `keys.rs`), then the fixed ones.
+                // With two players the bound keys (the arrows, by default) steer
+                // player one. In mirror mode the keys follow the board as drawn
+                // (see `unflip`).
+                if let Some(action) = options.keys.action(key) {
+                    match action {
+                        Action::Quit => return Ok(()),
+                        Action::Pause => game.set_paused(!game.paused),
+                        Action::Up => game.turn(0, game.unflip(Direction::Up)),
+                        Action::Down => game.turn(0, game.unflip(Direction::Down)),
+                        Action::Left => game.turn(0, game.unflip(Direction::Left)),
+                        Action::Right => game.turn(0, game.unflip(Direction::Right)),
+                        // Retrying is for the game over screen.
+                        Action::Restart => {}
+                    }
+                } else {
+                    match key.code {
+                        // Ctrl+C always quits, whatever the bindings say.
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            return Ok(());
+                        }
+
+                        // Ctrl+Z: suspend to the shell (Unix only). The game comes back
+                        // paused and fully redrawn (see `resumed`).
+                        KeyCode::Char('z')
+                            if key.modifiers.contains(KeyModifiers::CONTROL) && options.suspend =>
+                        {
+                            renderer.suspend()?;
+                            resumed = true;
+                        }
+
+                        // Save the game with Ctrl+S, asking first if that would replace an older save.
+                        KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            match &save_path {
+                                Some(path) if path.exists() => {
+                                    confirm_overwrite = Some(game.paused);
+                                    game.set_paused(true);
+                                }
+                                Some(path) => {
+                                    notice = Some((save_game(game, path), Instant::now()))
+                                }
+                                None => {
+                                    notice = Some((
+                                        "Nowhere to save; use --save-file".to_string(),
+                                        Instant::now(),
+                                    ))
+                                }
+                            }
+                        }
+
+                        // Switch wrap-around walls on or off with 'b' (for "borders")
+                        KeyCode::Char('b') => game.toggle_wrap(),
+
+                        // Phase through obstacles (or stop) with 'g' for "ghost"
+                        KeyCode::Char('g') => game.settings.ghost = !game.settings.ghost,
+
+                        // Try the next color theme. The screen's grid compares colors as
+                        // well as characters (see `screen.rs`), so every cell the new
+                        // colors change is sent again on the next tick.
+                        KeyCode::Char('t') => game.cycle_theme(),
+
+                        // WASD steers player two (or, playing alone, the only snake).
+                        KeyCode::Char('a') => game.turn(wasd_player, game.unflip(Direction::Left)),
+                        KeyCode::Char('d') => game.turn(wasd_player, game.unflip(Direction::Right)),
+                        KeyCode::Char('w') => game.turn(wasd_player, game.unflip(Direction::Up)),
+                        KeyCode::Char('s') => game.turn(wasd_player, game.unflip(Direction::Down)),
+                        // Diagonal mode: the numpad corners, which send digits with Num Lock
+                        // on and Home/PgUp/End/PgDn with it off.
+                        KeyCode::Char('7') | KeyCode::Home if game.settings.diagonal => {
+                            game.turn(0, game.unflip(Direction::UpLeft))
+                        }
+                        KeyCode::Char('9') | KeyCode::PageUp if game.settings.diagonal => {
+                            game.turn(0, game.unflip(Direction::UpRight))
+                        }
+                        KeyCode::Char('1') | KeyCode::End if game.settings.diagonal => {
+                            game.turn(0, game.unflip(Direction::DownLeft))
+                        }
+                        KeyCode::Char('3') | KeyCode::PageDown if game.settings.diagonal => {
+                            game.turn(0, game.unflip(Direction::DownRight))
+                        }
+                        _ => {} // Ignore other keys
+                    }
+                }
+            }
+        }
+
+        turned |= queued(game) > queued_before;
+
+        // --- Game Update & Rendering ---
+        // Real-time timers run every frame, independently of the tick.
+        let now = game.clock.now();
+        game.update_timers(now - last_timer_update);
+        last_timer_update = now;
+
+        // Only update if enough time has passed (the tick rate). It can change while
+        // playing (e.g. acceleration mode), so ask the game each time.
+        let tick_rate = game.effective_tick_rate();
+        if move_due(now - last_frame, tick_rate, turned, game.settings.quick_turns) {
+            // (We time the update and the render separately for `--perf-log`.)
+            let update_start = Instant::now();
+            if !game.paused {
+                for (player, direction) in input.turns(game.tick + 1) {
+                    game.turn(player, direction);
+                }
+                // `--bot`: the autopilot steers player one.
+                if options.bot {
+                    game.turn(0, bot::next_move(game));
+                }
+                match &mut recording {
+                    Some(replay) => replay.update(game),
+                    None => game.update(),
+                }
+                game.record_recap();
+            }
+            let update_time = update_start.elapsed();
+            last_frame = game.clock.now();
+            turned = false;
+
+            if game.is_over() && !recorded {
+                // A replay's run was recorded when it was first played, and the
+                // bot's runs aren't anybody's.
+                if options.replay.is_none() && !options.bot {
+                    let entry = session.record_score(game);
+                    // A recorded run that beat the high score is the new best run.
+                    if let (Some(entry), Some(replay)) = (&entry, &recording)
+                        && entry.score > game.best
+                        && let Err(err) = session.keep_best_run(replay)
+                    {
+                        let message = format!("Could not keep the best run: {}", err);
+                        notice = Some((message, Instant::now()));
+                    }
+                    naming = entry.map(scores::NameEntry::new);
+                    session.record_death(game);
+                }
+                if let (Some(replay), Some(path)) = (&recording, &options.record)
+                    && let Err(err) = replay.save(path)
+                {
+                    notice = Some((format!("Could not save the replay: {}", err), Instant::now()));
+                }
+                recorded = true;
+            }
+
+            // Audio assist: beep when danger first appears. Turning away clears
+            // `danger_ahead` on the next tick and re-arms the beep.
+            if game.settings.audio_assist && game.danger_ahead && !warned {
+                renderer.beep()?;
+            }
+            warned = game.danger_ahead;
+
+            // The overwrite question, the name being typed, or a recent notice (like
+            // why a retry failed), goes on the line under the board.
+            let prompt = naming.as_ref().map(scores::NameEntry::prompt);
+            let message = if confirm_overwrite.is_some() {
+                Some("Overwrite the saved game? (y/n)")
+            } else if let Some(prompt) = &prompt {
+                Some(prompt.as_str())
+            } else {
+                notice
+                    .as_ref()
+                    .filter(|(_, at)| at.elapsed() < NOTICE_TIME)
+                    .map(|(text, _)| text.as_str())
+            };
+            let render_start = Instant::now();
+            match &showing_scores {
+                Some(board) => renderer.draw_scores(board)?,
+                None => renderer.draw(game, message)?,
+            }
+
+            if let Some(log) = &mut perf_log {
+                log.record(update_time, render_start.elapsed())?;
+            }
+        }
+
+        // Loop Logic for Game Over state
+        if game.is_over() {
+            // The bot starts a new game once the last one has been on screen a while.
+            let mut retry = options.bot
+                && game.ended_at.is_some_and(|ended| game.clock.now().duration_since(ended) >= bot::RESTART_DELAY);
+            if naming.is_some() || showing_scores.is_some() {
+                // The keys go to the name box or the leaderboard (above).
+                std::thread::sleep(Duration::from_millis(10));
+            // Otherwise just poll input slowly to check for Quit
+            } else if let Some(key) = input.poll(Duration::from_millis(100))? {
+                let action = options.keys.action(key);
+                match key.code {
+                    _ if action == Some(Action::Quit) => return Ok(()),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(());
+                    }
+                    KeyCode::Char('z')
+                        if key.modifiers.contains(KeyModifiers::CONTROL) && options.suspend =>
+                    {
+                        renderer.suspend()?;
+                        resumed = true;
+                    }
+                    _ if action == Some(Action::Restart) => retry = true,
+                    // Show the leaderboard until the next key.
+                    KeyCode::Char('l') => {
+                        let board = scores::Leaderboard::default_path()
+                            .map(|path| scores::Leaderboard::load(&path))
+                            .unwrap_or_default();
+                        showing_scores = Some(board);
+                    }
+                    _ => {}
+                }
+            }
+
+            // Retry: swap in a new game and start the per-run bookkeeping afresh.
+            if retry {
+                match session.new_game() {
+                    Ok(fresh) => {
+                        game.reset(fresh, options.retry_layout);
+                        // `--record` keeps the latest run: start recording this one.
+                        if let Some(replay) = &mut recording {
+                            *replay = replay::Replay::record(game, options.seed);
+                        }
+                        recorded = false;
+                        warned = false;
+                        last_frame = game.clock.now();
+                        last_timer_update = game.clock.now();
+                    }
+                    Err(message) => notice = Some((message, Instant::now())),
+                }
+            }
+        } else {
+            // Sleep a tiny bit if we have time left in the frame to save CPU
+            let elapsed = game.clock.now() - last_frame;
+            if elapsed < tick_rate {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+}
+
+// Stand-ins for the keyboard, the screen and the program around the loop, for
+// any module's tests to drive the loop (or a menu) with.
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+    use crate::Point;
+    use hello_rust::clock::MockClock;
+
+    // Nothing on the keyboard, ever (and no waiting for it), so a replay plays instantly.
+    pub struct NoKeys;
+
+    impl Input for NoKeys {
+        fn poll(&mut self, _timeout: Duration) -> io::Result<Option<KeyEvent>> {
+            Ok(None)
+        }
+    }
+
+    // Presses the given keys in order, one per poll.
+    pub struct Keys(pub Vec<KeyCode>);
+
+    impl Input for Keys {
+        fn poll(&mut self, _timeout: Duration) -> io::Result<Option<KeyEvent>> {
+            Ok(Some(KeyEvent::from(self.0.remove(0))))
+        }
+    }
+
+    // Presses the given keys, one per poll, then 'q' once `polls` polls have gone by.
+    // With a `clock`, each poll also moves it forward by `step`, so the game's
+    // ticks follow the polls instead of the real time.
+    #[derive(Default)]
+    pub struct ScriptedInput {
+        pub keys: Vec<KeyCode>,
+        pub polls: usize,
+        pub clock: Option<(MockClock, Duration)>,
+    }
+
+    impl Input for ScriptedInput {
+        fn poll(&mut self, _timeout: Duration) -> io::Result<Option<KeyEvent>> {
+            if let Some((clock, step)) = &self.clock {
+                clock.advance(*step);
+            }
+            self.polls = self.polls.saturating_sub(1);
+            let code = if self.polls == 0 {
+                KeyCode::Char('q')
+            } else if self.keys.is_empty() {
+                return Ok(None);
+            } else {
+                self.keys.remove(0)
+            };
+            Ok(Some(KeyEvent::from(code)))
+        }
+    }
+
+    // Remembers where player one's head was on every frame, and draws nothing.
+    #[derive(Default)]
+    pub struct HeadRecorder {
+        pub heads: Vec<Point>,
+        // Pretend the screen is too small for the board.
+        pub too_small: bool,
+    }
+
+    impl Renderer for HeadRecorder {
+        fn draw(&mut self, game: &Game, _message: Option<&str>) -> io::Result<()> {
+            self.heads.push(game.snake().head());
+            Ok(())
+        }
+
+        fn beep(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn fits(&self, _game: &Game) -> bool {
+            !self.too_small
+        }
+    }
+
+    // Retries nothing and keeps no runs, so tests never touch the real leaderboard.
+    pub struct NoSession;
+
+    impl Session for NoSession {
+        fn new_game(&mut self) -> Result<Game, String> {
+            Err("no retries in tests".to_string())
+        }
+
+        fn record_score(&mut self, _game: &Game) -> Option<scores::Entry> {
+            None
+        }
+
+        fn name_score(&mut self, _entry: &scores::Entry, _name: &str) -> String {
+            String::new()
+        }
+
+        fn record_death(&mut self, _game: &Game) {}
+
+        fn keep_best_run(&mut self, _run: &replay::Replay) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{HeadRecorder, NoSession, ScriptedInput};
+    use super::*;
+    use crate::{Point, clock::MockClock};
+
+    #[test]
+    fn quick_turns_move_early_but_no_more_than_twice_as_fast() {
+        let tick = Duration::from_millis(100);
+        let at = Duration::from_millis;
+        // Without a turn, or without the option, moves wait for the whole tick.
+        assert!(!move_due(at(99), tick, false, true));
+        assert!(!move_due(at(99), tick, true, false));
+        assert!(move_due(at(100), tick, false, false));
+        // A turn brings the move forward, but only to half a tick after the last one.
+        assert!(!move_due(at(49), tick, true, true));
+        assert!(move_due(at(50), tick, true, true));
+    }
+
+    #[test]
+    fn runs_headless_with_scripted_input() {
+        let mut game = Game::new(20, 10, None, Some(Point { x: 1, y: 1 })).unwrap();
+        // Wrap-around walls, so the snake can't crash.
+        game.settings.wrap = true;
+        let start = game.snake().head();
+        // On a mock clock, every poll takes a whole tick: each poll before the
+        // last ('q') makes one move, however long the loop really takes.
+        let clock = MockClock::new();
+        game.clock = Box::new(clock.clone());
+        let mut input = ScriptedInput {
+            keys: vec![KeyCode::Down],
+            polls: 10,
+            clock: Some((clock, game.effective_tick_rate())),
+        };
+        let mut renderer = HeadRecorder::default();
+        run_game(
+            &mut input,
+            &mut renderer,
+            &mut game,
+            &cli::Options::default(),
+            &mut NoSession,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(renderer.heads.len(), 9);
+        // Every frame the snake had moved one more cell down.
+        assert_eq!(
+            renderer.heads[0],
+            Point {
+                x: start.x,
+                y: start.y + 1
+            }
+        );
+        assert_eq!(
+            renderer.heads[1],
+            Point {
+                x: start.x,
+                y: start.y + 2
+            }
+        );
+        assert_eq!(game.snake().direction, Direction::Down);
+    }
+
+    #[test]
+    fn a_recorded_run_that_beats_the_high_score_is_kept() {
+        // Puts every run on the leaderboard with the given score, and counts the
+        // runs kept as the best.
+        struct Board {
+            score: usize,
+            kept: usize,
+        }
+
+        impl Session for Board {
+            fn new_game(&mut self) -> Result<Game, String> {
+                Err("no retries in tests".to_string())
+            }
+
+            fn record_score(&mut self, game: &Game) -> Option<scores::Entry> {
+                Some(scores::Entry::now(self.score, game.level(), 0))
+            }
+
+            fn name_score(&mut self, _entry: &scores::Entry, _name: &str) -> String {
+                String::new()
+            }
+
+            fn record_death(&mut self, _game: &Game) {}
+
+            fn keep_best_run(&mut self, _run: &replay::Replay) -> io::Result<()> {
+                self.kept += 1;
+                Ok(())
+            }
+        }
+
+        let record = std::env::temp_dir().join(format!("hello_rust_best_{}.json", std::process::id()));
+        let options = cli::Options {
+            record: Some(record.clone()),
+            ..cli::Options::default()
+        };
+        // Steer straight into the top wall, with a high score of 10 to beat. Then
+        // skip naming the run (Esc), so 'q' quits.
+        let crash = |score| {
+            let mut game = Game::new(20, 10, Some(Point { x: 5, y: 1 }), None).unwrap();
+            game.best = 10;
+            let clock = MockClock::new();
+            game.clock = Box::new(clock.clone());
+            let mut input = ScriptedInput {
+                keys: vec![KeyCode::Up, KeyCode::Esc],
+                polls: 5,
+                clock: Some((clock, game.effective_tick_rate())),
+            };
+            let mut board = Board { score, kept: 0 };
+            run_game(&mut input, &mut HeadRecorder::default(), &mut game, &options, &mut board, None)
+                .unwrap();
+            assert!(game.is_over());
+            board.kept
+        };
+        assert_eq!(crash(11), 1);
+        assert_eq!(crash(10), 0);
+        std::fs::remove_file(&record).unwrap();
+    }
+
+    #[test]
+    fn a_screen_too_small_for_the_board_pauses_the_game() {
+        let mut game = Game::new(20, 10, None, Some(Point { x: 1, y: 1 })).unwrap();
+        let start = game.snake().head();
+        let mut input = ScriptedInput {
+            polls: 50,
+            ..ScriptedInput::default()
+        };
+        let mut renderer = HeadRecorder {
+            too_small: true,
+            ..HeadRecorder::default()
+        };
+        run_game(
+            &mut input,
+            &mut renderer,
+            &mut game,
+            &cli::Options::default(),
+            &mut NoSession,
+            None,
+        )
+        .unwrap();
+
+        assert!(game.paused);
+        assert!(renderer.heads.iter().all(|&head| head == start));
+    }
+}
@@ -0,0 +1,121 @@
+// The death heatmap: every cell the snake has crashed into, counted over all
+// the games you've played, so you can spot the places you keep dying.
+//
+// It's saved next to the leaderboard and shown with `--show-heatmap`.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crossterm::style::Stylize;
+
+use crate::{Point, render::hue_to_rgb};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Heatmap {
+    // How many times the snake died on each cell.
+    pub deaths: HashMap<Point, u32>,
+}
+
+impl Heatmap {
+    // Where the heatmap lives by default, next to the leaderboard.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("hello_rust").join("heatmap.json"))
+    }
+
+    // Reads the heatmap from `path`. Like the leaderboard, a missing or corrupt
+    // file just gives an empty heatmap.
+    pub fn load(path: &Path) -> Heatmap {
+        // JSON objects can only have text keys, so the file holds a list of
+        // `[point, count]` pairs instead of the map itself.
+        let pairs: Vec<(Point, u32)> = fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        Heatmap {
+            deaths: pairs.into_iter().collect(),
+        }
+    }
+
+    // Writes the heatmap to `path`, creating the folder if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut pairs: Vec<(&Point, &u32)> = self.deaths.iter().collect();
+        // Sorted, so the same heatmap always gives the same file.
+        pairs.sort_by_key(|(p, _)| (p.y, p.x));
+        let json = serde_json::to_string(&pairs).expect("a heatmap is always valid JSON");
+        fs::write(path, json)
+    }
+
+    // Counts one more death at `cell`.
+    pub fn record(&mut self, cell: Point) {
+        // `entry` finds the count for `cell`, or inserts 0 first if there isn't one yet.
+        *self.deaths.entry(cell).or_insert(0) += 1;
+    }
+
+    // The heatmap drawn as a colored grid at least `width` x `height` cells big:
+    // rarely fatal cells are blue, the deadliest ones red. Cells nobody died on
+    // are dots, and the outer wall is `#` like in `--dump`.
+    pub fn render(&self, width: u16, height: u16) -> String {
+        let Some(&most) = self.deaths.values().max() else {
+            return "No deaths recorded yet - go play a game!\n".to_string();
+        };
+        // Maps can be bigger than the default board, so grow the grid to fit every cell.
+        let width = self.deaths.keys().map(|p| p.x + 1).fold(width, u16::max);
+        let height = self.deaths.keys().map(|p| p.y + 1).fold(height, u16::max);
+
+        let mut out = String::new();
+        for y in 0..height {
+            for x in 0..width {
+                match self.deaths.get(&Point { x, y }) {
+                    Some(&count) => {
+                        let heat = count as f32 / most as f32;
+                        let cell = "█".with(hue_to_rgb(240.0 * (1.0 - heat)));
+                        out.push_str(&cell.to_string());
+                    }
+                    None if x == 0 || y == 0 || x == width - 1 || y == height - 1 => out.push('#'),
+                    None => out.push('.'),
+                }
+            }
+            out.push('\n');
+        }
+        let total: u32 = self.deaths.values().sum();
+        out.push_str(&format!(
+            "{} deaths on {} cells; the deadliest cell saw {}\n",
+            total,
+            self.deaths.len(),
+            most
+        ));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_deaths_and_survives_a_round_trip() {
+        let mut heatmap = Heatmap::default();
+        let wall = Point { x: 0, y: 2 };
+        heatmap.record(wall);
+        heatmap.record(wall);
+        heatmap.record(Point { x: 3, y: 1 });
+        assert_eq!(heatmap.deaths[&wall], 2);
+
+        let dir = std::env::temp_dir().join(format!("hello_rust_heatmap_{}", std::process::id()));
+        let path = dir.join("heatmap.json");
+        assert_eq!(Heatmap::load(&path), Heatmap::default());
+        heatmap.save(&path).unwrap();
+        assert_eq!(Heatmap::load(&path), heatmap);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let text = heatmap.render(5, 4);
+        assert!(text.ends_with("3 deaths on 2 cells; the deadliest cell saw 2\n"));
+        assert_eq!(text.lines().count(), 5);
+    }
+}
@@ -0,0 +1,218 @@
+// Key bindings: which keys steer, pause, quit and retry.
+//
+// They're read from the `keys` section of the config file (see `config.rs`),
+// one list of key names per action, for example:
+//
+//     "keys": { "up": ["Up", "k"], "down": ["Down", "j"], "pause": ["p"] }
+//
+// An action left out keeps its default keys, and a missing config file means
+// all the defaults. Keys held with Ctrl are never bound: Ctrl+C, Ctrl+Z and
+// Ctrl+S always do the same thing. Neither are the keys for everything else
+// (WASD for the second player, 'b', 'g', 't' and the numpad diagonals), which
+// only work when no binding uses them.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+
+// Everything a key can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Pause,
+    Quit,
+    Restart,
+}
+
+// The keys for each action, by name (see `parse_key`), as they're written in
+// the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyNames {
+    pub up: Vec<String>,
+    pub down: Vec<String>,
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    pub pause: Vec<String>,
+    pub quit: Vec<String>,
+    pub restart: Vec<String>,
+}
+
+impl Default for KeyNames {
+    fn default() -> Self {
+        // A quick way to turn `["Up"]` into a `Vec<String>`.
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        KeyNames {
+            up: names(&["Up"]),
+            down: names(&["Down"]),
+            left: names(&["Left"]),
+            right: names(&["Right"]),
+            pause: names(&["p", "Space"]),
+            quit: names(&["q", "Esc"]),
+            restart: names(&["r"]),
+        }
+    }
+}
+
+impl KeyNames {
+    // Every action with its key names, in the order they're looked up.
+    fn all(&self) -> [(Action, &[String]); 7] {
+        [
+            (Action::Quit, &self.quit),
+            (Action::Pause, &self.pause),
+            (Action::Restart, &self.restart),
+            (Action::Up, &self.up),
+            (Action::Down, &self.down),
+            (Action::Left, &self.left),
+            (Action::Right, &self.right),
+        ]
+    }
+}
+
+// The key bindings in use. They're read from (and saved as) `KeyNames`, but each
+// name is turned into its key just once, when the names are read, rather than
+// on every key press.
+// `from` and `into` tell serde to go through `KeyNames` for the file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "KeyNames", into = "KeyNames")]
+pub struct KeyBindings {
+    names: KeyNames,
+    // Every bound key with its action, in `KeyNames::all`'s order.
+    keys: Vec<(KeyCode, Action)>,
+}
+
+impl From<KeyNames> for KeyBindings {
+    fn from(names: KeyNames) -> Self {
+        let keys = names
+            .all()
+            .into_iter()
+            .flat_map(|(action, names)| names.iter().filter_map(move |name| Some((parse_key(name)?, action))))
+            .collect();
+        KeyBindings { names, keys }
+    }
+}
+
+impl From<KeyBindings> for KeyNames {
+    fn from(bindings: KeyBindings) -> Self {
+        bindings.names
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyNames::default().into()
+    }
+}
+
+impl KeyBindings {
+    // The action `key` is bound to, if any. A key bound to more than one action
+    // does the first in `KeyNames::all`'s order (so quitting wins).
+    pub fn action(&self, key: KeyEvent) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return None;
+        }
+        self.keys.iter().find(|&&(code, _)| code == key.code).map(|&(_, action)| action)
+    }
+
+    // Whether `key` quits: Ctrl+C always does, as well as the keys bound to quit.
+    pub fn quits(&self, key: KeyEvent) -> bool {
+        let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        ctrl_c || self.action(key) == Some(Action::Quit)
+    }
+
+    // How to name `action`'s key to the player, like "R" or "Esc": its first key,
+    // with letters in capitals, the way they're printed on the keys. `None` if
+    // nothing is bound to it.
+    pub fn label(&self, action: Action) -> Option<String> {
+        let (code, _) = self.keys.iter().find(|&&(_, bound)| bound == action)?;
+        Some(match code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_uppercase().to_string(),
+            KeyCode::F(number) => format!("F{}", number),
+            KeyCode::PageUp => "PgUp".to_string(),
+            KeyCode::PageDown => "PgDn".to_string(),
+            other => format!("{:?}", other),
+        })
+    }
+
+    // The key names that don't name a key, to warn about. They're ignored.
+    pub fn unknown_names(&self) -> Vec<&str> {
+        self.names
+            .all()
+            .into_iter()
+            .flat_map(|(_, names)| names)
+            .filter(|name| parse_key(name).is_none())
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+// Reads a key's name: a single character (like "w" or "R", which is Shift+R), a
+// special key ("Up", "Down", "Left", "Right", "Space", "Enter", "Esc", "Tab",
+// "Backspace", "Home", "End", "PageUp", "PageDown"), or "F1" to "F12". Special
+// keys are matched ignoring case, characters aren't.
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    let code = match name.to_lowercase().as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        other => {
+            let number: u8 = other.strip_prefix('f')?.parse().ok()?;
+            if !(1..=12).contains(&number) {
+                return None;
+            }
+            KeyCode::F(number)
+        }
+    };
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_looked_up_by_name_with_defaults_for_the_rest() {
+        assert_eq!(parse_key("k"), Some(KeyCode::Char('k')));
+        assert_eq!(parse_key("PageUp"), Some(KeyCode::PageUp));
+        assert_eq!(parse_key("space"), Some(KeyCode::Char(' ')));
+        assert_eq!(parse_key("F5"), Some(KeyCode::F(5)));
+        assert_eq!(parse_key("F13"), None);
+        assert_eq!(parse_key("Uq"), None);
+
+        // Only the actions in the file change; the others keep their defaults.
+        let keys: KeyBindings =
+            serde_json::from_str(r#"{ "up": ["k"], "quit": ["x", "Nope"] }"#).unwrap();
+        let press = |code| keys.action(KeyEvent::from(code));
+        assert_eq!(press(KeyCode::Char('k')), Some(Action::Up));
+        assert_eq!(press(KeyCode::Up), None);
+        assert_eq!(press(KeyCode::Char('x')), Some(Action::Quit));
+        assert_eq!(press(KeyCode::Char('q')), None);
+        assert_eq!(press(KeyCode::Char(' ')), Some(Action::Pause));
+        assert_eq!(press(KeyCode::Char('r')), Some(Action::Restart));
+        assert_eq!(keys.unknown_names(), vec!["Nope"]);
+        assert_eq!(keys.label(Action::Quit), Some("X".to_string()));
+        assert_eq!(keys.label(Action::Pause), Some("P".to_string()));
+        assert_eq!(KeyBindings::default().label(Action::Quit), Some("Q".to_string()));
+
+        // Ctrl+X isn't X.
+        let ctrl_x = KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL);
+        assert_eq!(keys.action(ctrl_x), None);
+    }
+}
@@ -0,0 +1,3457 @@
+// The game itself: the board, the snakes and the rules, with no terminal code.
+//
+// A package can hold a library (`src/lib.rs`) next to its program (`src/main.rs`).
+// The program uses the library like any other crate, by its name, `hello_rust`,
+// and can only see what's marked `pub` here. Keeping the rules in the library and
+// everything about the terminal (drawing, keys, command-line flags) in the program
+// means the rules can be tested, or given another front-end, without a terminal.
+
+pub mod cheats;
+pub mod clock;
+pub mod free_cells;
+pub mod map;
+pub mod pathfinding;
+pub mod recap;
+pub mod save;
+// `#[doc(hidden)]` keeps it out of the documentation: it's only for tests.
+#[doc(hidden)]
+pub mod test_support;
+
+// We need the `Rng` trait to generate random numbers for the food position.
+// `WeightedIndex` picks an index at random, with some indexes likelier than others.
+// `StdRng` is a random number generator we can "seed": the same seed always
+// produces the same sequence of numbers, which makes levels reproducible.
+use rand::{Rng, SeedableRng, distributions::WeightedIndex, prelude::Distribution, rngs::StdRng};
+// `serde` turns our structs into JSON (and back) for save files, see `save.rs`.
+use serde::{Deserialize, Serialize};
+// Standard library imports for collections and time management.
+use clock::Clock;
+use free_cells::FreeCells;
+use std::{
+    // HashSet is a collection that stores unique items and allows for ultra-fast lookups (checking if an item exists).
+    // VecDeque is a "double-ended queue" - great for adding/removing from both ends (like a snake!).
+    // HashMap stores a value for each key, like the direction of each one-way gate.
+    collections::{HashMap, HashSet, VecDeque},
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
+
+// Constants determine the size of our game board.
+// `u16` means an unsigned 16-bit integer (can't be negative).
+pub const WIDTH: u16 = 40;
+pub const HEIGHT: u16 = 20;
+
+// Time attack mode: how much time is on the clock at the start.
+// The on-screen bar shows this much time as "full".
+pub const TIME_ATTACK_START: Duration = Duration::from_secs(30);
+
+// Drunk mode: the chance, each tick, that the snake lurches into a random turn.
+pub const DRUNK_TURN_CHANCE: f64 = 0.05;
+
+// After switching wrap-around off, walls stay passable for this many ticks,
+// so a snake that happens to be heading into a wall gets a chance to turn.
+pub const WRAP_GRACE_TICKS: u32 = 3;
+
+// Survival waves: how often a surge of new walls arrives...
+pub const WAVE_INTERVAL: Duration = Duration::from_secs(20);
+// ...how long before each surge the warning is shown...
+pub const WAVE_WARNING: Duration = Duration::from_secs(3);
+// ...and how many walls the first surge adds (each wave brings one more).
+pub const WAVE_BASE_WALLS: u32 = 2;
+
+// Bombs: the chance that eating a piece of food drops a bomb somewhere on the board...
+pub const BOMB_CHANCE: f64 = 0.15;
+// ...and how far its blast reaches: every obstacle within this many cells
+// (in any direction, diagonals included) is destroyed.
+pub const BOMB_RADIUS: u16 = 2;
+
+// Hourglasses: the chance that eating drops one (it gives a spell of slow motion)...
+pub const HOURGLASS_CHANCE: f64 = 0.1;
+// ...how long the slow motion lasts...
+pub const SLOW_MOTION_TIME: Duration = Duration::from_secs(5);
+// ...and how many times longer each tick takes while it does.
+pub const SLOW_MOTION_FACTOR: u32 = 2;
+
+// Reversers: the chance that eating drops one (it swaps the snake's head and tail).
+pub const REVERSER_CHANCE: f64 = 0.08;
+
+// Power-ups (`--power-ups`): the chance that eating drops one...
+pub const POWER_UP_CHANCE: f64 = 0.12;
+// ...how long a speed boost lasts, and how many times shorter it makes each tick...
+pub const BOOST_TIME: Duration = Duration::from_secs(5);
+pub const BOOST_FACTOR: u32 = 2;
+// ...how many tail segments a shrink takes off (never leaving fewer than `MIN_SHRUNK_LENGTH`)...
+pub const SHRINK_SEGMENTS: usize = 3;
+pub const MIN_SHRUNK_LENGTH: usize = 3;
+// ...and how long food scores double.
+pub const DOUBLE_POINTS_TIME: Duration = Duration::from_secs(10);
+
+// Corridor mode: how often each step of the corridor's random walk heads for its
+// goal (the rest of the time it wanders in any direction, making the path wind).
+pub const CORRIDOR_BIAS: f64 = 0.6;
+
+// Shields (`--shields`): the most anyone can ask for, and how long a spent one
+// takes to come back.
+pub const MAX_SHIELDS: u8 = 9;
+pub const SHIELD_REGEN_TIME: Duration = Duration::from_secs(15);
+
+// `--local-grid`: how many steps from the head the guide dots reach.
+pub const LOCAL_GRID_RADIUS: u16 = 3;
+
+// Split mode: the shortest a snake can be left after biting itself. A bite any
+// nearer the head than this is fatal as usual.
+pub const MIN_SPLIT_LENGTH: usize = 3;
+
+// The side panel: how long a message stays in its log, and how many messages it
+// keeps at most.
+pub const MESSAGE_TIME: Duration = Duration::from_secs(8);
+pub const MAX_MESSAGES: usize = 6;
+
+// Toasts (`--toasts`): how long each one stays on the board. It dims for the second half.
+pub const TOAST_TIME: Duration = Duration::from_millis(1500);
+
+// How many quick key presses are remembered, to be turned one per tick.
+// Two is enough for a fast U-turn (e.g. Up then Left); more would feel laggy.
+pub const MAX_QUEUED_TURNS: usize = 2;
+
+// How long one game tick lasts at normal speed (the snake moves once per tick).
+pub const TICK_RATE: Duration = Duration::from_millis(150);
+// The shortest a tick can get, however high the level or fast the acceleration.
+pub const MIN_TICK_RATE: Duration = Duration::from_millis(50);
+
+// Acceleration mode: each tick spent going straight shaves this much off the tick...
+pub const ACCEL_STEP: Duration = Duration::from_millis(4);
+// ...up to this many ticks' worth, so the snake never gets uncontrollably fast.
+pub const MAX_ACCEL_TICKS: u32 = 15;
+// Structs define custom data types to group related data.
+// #[derive(...)] asks the compiler to automatically implement basic behaviors for us.
+// - Clone/Copy: Allows us to duplicate this Point easily.
+// - PartialEq/Eq: Allows us to compare two Points with `==`.
+// - Hash: Allows this struct to be used as a key in a HashMap or stored in a HashSet. 
+//   This is crucial for our obstacle checking, as hashing is what makes HashSet lookups fast!
+// - Debug: Lets us print a Point with `{:?}` (tests use this to show what went wrong).
+// - Serialize/Deserialize: Lets `serde` write a Point to a save file and read it back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Point {
+    pub x: u16,
+    pub y: u16,
+}
+
+impl Point {
+    // The neighboring point one step away in `direction`.
+    pub fn step(self, direction: Direction) -> Point {
+        // `match` is like a powerful switch statement.
+        match direction {
+            Direction::Up => Point {
+                x: self.x,
+                // wrapping_sub handles subtraction that might go below 0.
+                y: self.y.wrapping_sub(1), 
+            },
+            Direction::Down => Point {
+                x: self.x,
+                y: self.y + 1,
+            },
+            Direction::Left => Point {
+                x: self.x.wrapping_sub(1),
+                y: self.y,
+            },
+            Direction::Right => Point {
+                x: self.x + 1,
+                y: self.y,
+            },
+            // A diagonal step is just one vertical step and one horizontal step.
+            Direction::UpLeft => self.step(Direction::Up).step(Direction::Left),
+            Direction::UpRight => self.step(Direction::Up).step(Direction::Right),
+            Direction::DownLeft => self.step(Direction::Down).step(Direction::Left),
+            Direction::DownRight => self.step(Direction::Down).step(Direction::Right),
+        }
+    }
+
+    // How many up/down/left/right steps apart two points are (ignoring walls),
+    // also known as the "Manhattan distance".
+    pub fn distance(self, other: Point) -> u16 {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+    }
+}
+
+// Enums allow us to define a type that can be one of several variants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+    // Only used in diagonal mode (`--diagonal`).
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+// The power-ups (`--power-ups`). Only one is on the board at a time.
+// (Slowing down is the hourglass's job, which is always on.)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerUp {
+    // Makes every tick shorter for `BOOST_TIME`.
+    Boost,
+    // Takes `SHRINK_SEGMENTS` off the tail, right away.
+    Shrink,
+    // Food scores twice for `DOUBLE_POINTS_TIME`.
+    DoublePoints,
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 3] = [PowerUp::Boost, PowerUp::Shrink, PowerUp::DoublePoints];
+
+    // What the side panel's log says when it's picked up.
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUp::Boost => "Speed boost!",
+            PowerUp::Shrink => "Shrink!",
+            PowerUp::DoublePoints => "Double points!",
+        }
+    }
+}
+
+// We can add methods to our types using `impl`.
+impl Direction {
+    // Every direction, straight ones first.
+    pub const ALL: [Direction; 8] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+        Direction::UpLeft,
+        Direction::UpRight,
+        Direction::DownLeft,
+        Direction::DownRight,
+    ];
+
+    // The direction named `name` in any case, like "up" or "DOWNLEFT" (as used by
+    // `--script` and networked co-op).
+    pub fn from_name(name: &str) -> Option<Direction> {
+        Direction::ALL
+            .into_iter()
+            .find(|direction| direction.name().eq_ignore_ascii_case(name))
+    }
+
+    // The direction's name in capitals, like "UP" or "DOWNLEFT".
+    pub fn name(self) -> String {
+        // `{:?}` gives the variant's name as written, like "DownLeft".
+        format!("{:?}", self).to_uppercase()
+    }
+
+    // The direction pointing the other way (turning onto it would be a 180).
+    pub fn opposite(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::UpLeft => Direction::DownRight,
+            Direction::UpRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::UpRight,
+            Direction::DownRight => Direction::UpLeft,
+        }
+    }
+
+    // The same direction flipped left-to-right (Left becomes Right, UpLeft becomes UpRight...).
+    pub fn mirror_x(self) -> Direction {
+        match self {
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::UpLeft => Direction::UpRight,
+            Direction::UpRight => Direction::UpLeft,
+            Direction::DownLeft => Direction::DownRight,
+            Direction::DownRight => Direction::DownLeft,
+            Direction::Up | Direction::Down => self,
+        }
+    }
+
+    // The same direction flipped top-to-bottom (Up becomes Down, UpLeft becomes DownLeft...).
+    pub fn mirror_y(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::UpLeft => Direction::DownLeft,
+            Direction::DownLeft => Direction::UpLeft,
+            Direction::UpRight => Direction::DownRight,
+            Direction::DownRight => Direction::UpRight,
+            Direction::Left | Direction::Right => self,
+        }
+    }
+
+    // An arrow pointing this way, for drawing one-way gates.
+    pub fn arrow(self) -> &'static str {
+        match self {
+            Direction::Up => "↑",
+            Direction::Down => "↓",
+            Direction::Left => "←",
+            Direction::Right => "→",
+            Direction::UpLeft => "↖",
+            Direction::UpRight => "↗",
+            Direction::DownLeft => "↙",
+            Direction::DownRight => "↘",
+        }
+    }
+
+    // The two directions at right angles to this one (a left or right turn).
+    pub fn perpendicular(self) -> [Direction; 2] {
+        match self {
+            Direction::Up | Direction::Down => [Direction::Left, Direction::Right],
+            Direction::Left | Direction::Right => [Direction::Up, Direction::Down],
+            Direction::UpLeft | Direction::DownRight => [Direction::UpRight, Direction::DownLeft],
+            Direction::UpRight | Direction::DownLeft => [Direction::UpLeft, Direction::DownRight],
+        }
+    }
+}
+
+// One snake on the board.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snake {
+    // The body, head first. A VecDeque lets us add a new head and drop the tail cheaply.
+    pub body: VecDeque<Point>,
+    pub direction: Direction,
+    // The direction the snake actually moved on the previous tick, and how many
+    // ticks in a row it has kept going that way (used by acceleration mode).
+    pub last_direction: Direction,
+    pub straight_ticks: u32,
+    // Turns pressed but not made yet, oldest first: the snake makes at most one
+    // turn per tick, so quick key presses wait here for their tick.
+    // `#[serde(skip)]` leaves it out of save files (it starts empty when resuming).
+    #[serde(skip)]
+    pub queued_turns: VecDeque<Direction>,
+    // Growth delay mode: segments eaten but not grown yet, and ticks since the last
+    // one was grown. `#[serde(default)]` lets older save files without them still load.
+    #[serde(default)]
+    pub pending_growth: u32,
+    #[serde(default)]
+    pub growth_ticks: u32,
+    // After a meal, a brighter "pulse" ripples from the head to the tail, one
+    // segment per tick. This is the segment it's on (0 is the head), if any.
+    #[serde(skip)]
+    pub pulse: Option<usize>,
+    // How many apples this snake has eaten: its own score in versus mode.
+    #[serde(default)]
+    pub apples: usize,
+    // Versus mode: this snake has crashed and is out of the round. Its body stays
+    // where it stopped, as a wreck the other snake has to steer around.
+    #[serde(default)]
+    pub crashed: bool,
+}
+
+impl Snake {
+    // A snake of `length` segments with its head at `head`, heading in `direction`.
+    // The body trails out behind the head, the opposite way to where it's going.
+    pub fn new(head: Point, direction: Direction, length: usize) -> Snake {
+        let mut body = VecDeque::new();
+        let mut segment = head;
+        for _ in 0..length {
+            body.push_back(segment);
+            segment = segment.step(direction.opposite());
+        }
+        Snake {
+            body,
+            direction,
+            last_direction: direction,
+            straight_ticks: 0,
+            queued_turns: VecDeque::new(),
+            pending_growth: 0,
+            growth_ticks: 0,
+            pulse: None,
+            apples: 0,
+            crashed: false,
+        }
+    }
+
+    // Where the head is. `unwrap()` is fine because a snake is never empty.
+    pub fn head(&self) -> Point {
+        *self.body.front().unwrap()
+    }
+
+    // Queues a turn for the coming ticks, unless it would make the snake reverse
+    // straight back into its own body (a 180-degree turn).
+    // Each turn is checked against the turn queued just before it (or the current
+    // direction if none is waiting), because that's the direction the snake will
+    // really be going when this one is made. Checking against the current direction
+    // alone would let Up-then-Left, pressed within one tick while heading right,
+    // carry the snake back into itself.
+    pub fn turn(&mut self, direction: Direction) {
+        let before = self.queued_turns.back().copied().unwrap_or(self.direction);
+        let changes = direction != before && direction != before.opposite();
+        if changes && self.queued_turns.len() < MAX_QUEUED_TURNS {
+            self.queued_turns.push_back(direction);
+        }
+    }
+}
+
+// What retrying a level (R on the game over screen) does to its obstacles (`--retry-layout`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryLayout {
+    // New random obstacles, as if the level had just been reached.
+    Fresh,
+    // The same obstacles again, so the level can be learned and mastered.
+    Same,
+}
+
+// How the game speeds up from level to level (`--speed-curve`).
+// It's saved in the config file (see `config.rs`) by its lowercase name, like "linear".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedCurve {
+    // The same speed on every level (the classic game).
+    Flat,
+    // A fixed amount faster each level.
+    Linear,
+    // A fixed percentage faster each level: the biggest steps come first, and they
+    // shrink as the tick gets shorter.
+    Exponential,
+    // Quickly faster over the first few levels, then leveling off.
+    Logarithmic,
+}
+
+impl SpeedCurve {
+    // How long a tick lasts on `level` (1 and up), starting from `start` on level 1
+    // and never going below `MIN_TICK_RATE`.
+    pub fn tick_rate(self, start: Duration, level: u32) -> Duration {
+        // Levels climbed so far: 0 on level 1, so every curve starts at `start`.
+        let climbed = level.saturating_sub(1) as f64;
+        let base = start.as_secs_f64();
+        let seconds = match self {
+            SpeedCurve::Flat => base,
+            SpeedCurve::Linear => base - 0.008 * climbed,
+            SpeedCurve::Exponential => base * 0.9_f64.powf(climbed),
+            // `ln_1p(x)` is the natural logarithm of 1 + x.
+            SpeedCurve::Logarithmic => base - 0.03 * climbed.ln_1p(),
+        };
+        Duration::from_secs_f64(seconds.max(MIN_TICK_RATE.as_secs_f64()))
+    }
+}
+
+// The tick rate on each of the first few levels under `curve`, starting from `start`,
+// for `--print-speed-table`.
+pub fn speed_table(curve: SpeedCurve, start: Duration) -> String {
+    let mut out = format!("Speed curve: {:?}\nLevel  Tick    Moves/s\n", curve);
+    for level in 1..=15 {
+        let tick = curve.tick_rate(start, level);
+        out.push_str(&format!(
+            "{:>5}  {:>4}ms  {:>7.1}\n",
+            level,
+            tick.as_millis(),
+            1.0 / tick.as_secs_f64()
+        ));
+    }
+    out
+}
+
+// Where new food appears (`--spawn`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpawnMode {
+    // Any free cell, all equally likely (the classic game).
+    Uniform,
+    // Any free cell, but the farther from the snake's head the likelier, for longer chases.
+    Far,
+}
+
+// Optional gameplay rules and display tweaks, switched on with command-line flags.
+// `Settings::default()` turns every rule off, which is the classic game.
+// They're saved with a replay (see the binary's `replay.rs`); like the config,
+// a setting missing from the file gets its default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Speed up the longer the snake travels in a straight line (`--accelerate`).
+    pub accelerate: bool,
+    // How new food is placed (`--spawn`).
+    pub spawn: SpawnMode,
+    // Cycle the food's color through the rainbow (`--rainbow-food`). Purely cosmetic.
+    pub rainbow_food: bool,
+    // Power-ups (`--power-ups`): eating now and then drops a speed boost, a shrink
+    // or double points (see `PowerUp`).
+    pub power_ups: bool,
+    // Beep when the next move would be fatal (`--audio-assist`).
+    pub audio_assist: bool,
+    // Time attack (`--time-attack`): a countdown that ends the game at zero,
+    // topped up by `time_bonus` (`--time-bonus`) for every food eaten.
+    pub time_attack: bool,
+    pub time_bonus: Duration,
+    // Survival waves (`--waves`): every `WAVE_INTERVAL` a surge of extra walls appears.
+    pub waves: bool,
+    // Occasionally turn the snake left or right on its own (`--drunk`).
+    pub drunk: bool,
+    // Paint mode (`--paint`): cells the snake leaves behind get painted, each newly
+    // painted cell scores a point, and painting `paint_target` percent of the open
+    // floor (`--paint-target`) wins the game.
+    pub paint: bool,
+    pub paint_target: u8,
+    // Wrap-around walls: leaving one edge of the board brings you back in at the
+    // opposite edge instead of crashing. Toggled while playing with 'b'.
+    pub wrap: bool,
+    // Endless mode (`--endless`): filling the whole board doesn't win; the game just
+    // carries on without new food until a cell frees up.
+    pub endless: bool,
+    // Ghost mode (`--ghost`, toggled with 'g'): snakes pass through obstacles,
+    // but the outer walls and snakes themselves are still deadly.
+    pub ghost: bool,
+    // Shields (`--shields N`): up to this many shields, each absorbing one fatal
+    // hit, with spent ones coming back over time. 0 (the default) means none.
+    pub shields: u8,
+    // Split mode (`--split`): biting your own body cuts off everything from the
+    // bite back, instead of ending the game (see `try_split`).
+    pub split: bool,
+    // Guaranteed corridor (`--corridor`): each new level first carves a winding
+    // path from the snake to the food, and no wall is placed on it.
+    pub corridor: bool,
+    // No obstacles (`--no-obstacles`): new levels only speed up, and the board
+    // stays empty apart from the outer wall.
+    pub no_obstacles: bool,
+    // Shrink to fit (`--shrink-to-fit`): a board cut down to fit a small terminal
+    // (see `fit_board`) gets fewer obstacles, in proportion to its size.
+    pub shrink_to_fit: bool,
+    // One-way gates (`--gates`): from level 2 on, some of each level's walls are gates
+    // the snake can only cross in the direction their arrow points.
+    pub gates: bool,
+    // Mirror mode (`--mirror`): each new level flips the board left-right and/or
+    // upside down, controls included.
+    pub mirror: bool,
+    // Two-snake cooperative mode (`--coop`): a second snake starts mirrored across
+    // the board, both share one score, and the round ends if either dies.
+    pub coop: bool,
+    // Two-snake versus mode (`--versus`): the same two snakes, but each scores its
+    // own apples, and a snake that crashes is out while the other plays on. The
+    // round ends once both are out, and the most apples wins.
+    pub versus: bool,
+    // How many apples it takes to reach the next level (`--level-every N`, at least 1).
+    pub level_every: usize,
+    // How the base speed changes from level to level (`--speed-curve`).
+    pub speed_curve: SpeedCurve,
+    // How long a tick lasts on level 1 (`--speed MS`), before any speed-up.
+    pub tick_rate: Duration,
+    // Growth delay (`--growth-delay TICKS`): each food adds its segment this many
+    // ticks after it's eaten, so the snake extends gradually. 0 grows instantly.
+    pub growth_delay: u32,
+    // Eight-direction movement (`--diagonal`): the numpad corner keys (7, 9, 1, 3,
+    // or Home, PgUp, End, PgDn with Num Lock off) steer player one diagonally.
+    pub diagonal: bool,
+    // Show the snake's length next to the score (`--show-length`).
+    pub show_length: bool,
+    // Pop up short notes over the board when things happen (`--toasts`).
+    pub toasts: bool,
+    // Show the turns waiting in the input queue as arrows in the HUD (`--show-queue`).
+    pub show_queue: bool,
+    // Color the snake by how fast it's going (`--speed-tint`, see `speed_tint`).
+    pub speed_tint: bool,
+    // Replay the run, small and sped up, on the game over screen (`--recap`, see `recap.rs`).
+    pub recap: bool,
+    // Color the game over screen by how the score compares with the best so far,
+    // and say when it's a new record (on unless `--no-score-colors`, see `result_color`).
+    pub score_colors: bool,
+    // Faint dots on the empty cells near the head, as a guide for judging distances
+    // (`--local-grid`, see `local_grid`).
+    pub local_grid: bool,
+    // Mark a greedy and a safe next move, as a learning aid (`--hint`, see `hints`).
+    pub hint: bool,
+    // Input-latency compensation (`--quick-turns`): turning moves the snake right
+    // away instead of at the end of the tick (see `game_loop::move_due`).
+    pub quick_turns: bool,
+    // Tint each separate area of open floor its own color (`--show-regions`, see `regions`).
+    pub show_regions: bool,
+    // Darken every other row of the board like a CRT's scanlines (`--crt`, see `crt.rs`).
+    pub crt: bool,
+    // Scale the leaderboard score by how hard the run was (`--scaled-score`, see
+    // `score_multiplier`). The score on screen stays the raw count.
+    pub scaled_score: bool,
+    // Debug/assist tools, such as the board metrics shown while paused (`--debug`).
+    pub debug: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            accelerate: false,
+            spawn: SpawnMode::Uniform,
+            rainbow_food: false,
+            power_ups: false,
+            audio_assist: false,
+            time_attack: false,
+            time_bonus: Duration::from_secs(5),
+            waves: false,
+            drunk: false,
+            paint: false,
+            paint_target: 75,
+            wrap: false,
+            endless: false,
+            ghost: false,
+            shields: 0,
+            split: false,
+            corridor: false,
+            no_obstacles: false,
+            shrink_to_fit: false,
+            gates: false,
+            mirror: false,
+            coop: false,
+            versus: false,
+            level_every: 5,
+            speed_curve: SpeedCurve::Flat,
+            tick_rate: TICK_RATE,
+            growth_delay: 0,
+            diagonal: false,
+            show_length: false,
+            toasts: false,
+            show_queue: false,
+            speed_tint: false,
+            recap: false,
+            score_colors: true,
+            local_grid: false,
+            hint: false,
+            quick_turns: false,
+            scaled_score: false,
+            crt: false,
+            show_regions: false,
+            debug: false,
+        }
+    }
+}
+
+// A short popup like "+1" or "LEVEL 3", shown next to where something just happened.
+pub struct Toast {
+    pub text: String,
+    pub pos: Point,
+    pub since: Instant,
+}
+
+// Puzzle mode (a map with numbered food, see `map.rs`): the food appears in a
+// fixed order, and it all has to be eaten within a budget of moves.
+pub struct Puzzle {
+    pub food: Vec<Point>, // Every food cell, in the order it must be eaten.
+    pub eaten: usize,     // How many have been eaten; `food[eaten]` is the one on the board.
+    pub moves_left: u32,  // Each tick spends one; running out before the last food loses.
+}
+
+// A rectangle where food likes to appear (a map's `zone` lines, see `map.rs`).
+// Each new food picks a zone at random, a zone with twice the weight being twice
+// as likely, then a free cell inside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoodZone {
+    pub from: Point, // The top-left corner.
+    pub to: Point,   // The bottom-right corner (both corners are part of the zone).
+    pub weight: u32, // Always at least 1.
+}
+
+impl FoodZone {
+    // Every cell in the zone, row by row.
+    pub fn cells(&self) -> impl Iterator<Item = Point> + '_ {
+        (self.from.y..=self.to.y).flat_map(move |y| (self.from.x..=self.to.x).map(move |x| Point { x, y }))
+    }
+}
+
+// The core Game state struct.
+pub struct Game {
+    // Usually just one snake; co-op mode adds a second. `snakes[0]` is player one.
+    pub snakes: Vec<Snake>,
+    pub food: Point,
+    pub bomb: Option<Point>, // A rare pickup that clears nearby obstacles (`None` when there isn't one).
+    pub hourglass: Option<Point>, // A rare pickup that slows the game down for a while.
+    pub reverser: Option<Point>,  // A rare pickup that turns the snake around, tail first.
+    pub power_up: Option<(Point, PowerUp)>, // `--power-ups`: the power-up on the board, if any.
+    // We use a HashSet to store obstacle positions. 
+    // Why? Because checking `obstacles.contains(&point)` is O(1) (instant), 
+    // whereas searching through a Vec would be O(N) (slower as obstacles increase).
+    pub obstacles: HashSet<Point>,
+    // One-way gates: each is also in `obstacles`, so nothing spawns on it, but the
+    // snake may move onto it in the direction stored here (see `is_lethal_move`).
+    pub gates: HashMap<Point, Direction>,
+    pub settings: Settings,
+    // All randomness (food, obstacles, drunk turns) comes from this one generator.
+    pub rng: StdRng,
+    pub clock: Box<dyn clock::Clock>, // Where the current time comes from (see `clock.rs`).
+    pub score: usize, // `usize` is the standard size for indexing collections (usually 64-bit on modern PCs).
+    pub level: u32,   // Current game level
+    pub apples: usize, // Food eaten so far (the level goes up every few apples).
+    // Paint mode: every cell a snake's tail has left behind.
+    pub painted: HashSet<Point>,
+    pub danger_ahead: bool, // True when the next move (with no turn) would be fatal.
+    pub wrap_grace: u32, // Ticks left before the walls turn solid again after leaving wrap mode.
+    pub tick: u64,    // How many times `update` has moved the snake (drives animations).
+    pub game_over: bool,
+    pub game_won: bool, // Set together with `game_over` when the player met a win condition.
+    pub paused: bool, // While paused the snake doesn't move and timers don't drain.
+    pub paused_at: Option<Instant>, // When the current pause began (`None` while playing).
+    pub best: usize, // The saved high score from before this run (see `scores.rs`), 0 if none.
+    pub theme: usize, // Index into `theme::THEMES`.
+    pub flip: (bool, bool), // Mirror mode: whether the board is drawn flipped (left-right, up-down).
+    pub width: u16,
+    pub height: u16,
+    pub border: u16, // How many cells thick the outer wall is (see `--border`).
+    // `Instant` is a point in time from a monotonic clock (it never jumps backwards).
+    // We remember when the run started and when it ended to show a speedrun timer.
+    pub started_at: Instant,
+    pub ended_at: Option<Instant>, // `None` while the game is still running.
+    pub crashed_at: Option<Point>, // The cell a snake crashed into, if that's how the game ended.
+    pub time_left: Duration,       // Time attack countdown (unused in other modes).
+    pub slow_motion: Duration,     // How much slow motion is left from the last hourglass (zero if none).
+    pub boost: Duration,           // How much of a speed boost power-up is left (zero if none).
+    pub double_points: Duration,   // How much of a double points power-up is left (zero if none).
+    pub messages: Vec<(String, Instant)>, // The side panel's log of recent events, oldest first.
+    pub toasts: Vec<Toast>,        // Popups over the board for recent events (`--toasts`).
+    pub wave: u32,                 // Survival waves: how many surges have arrived so far.
+    pub wave_timer: Duration,      // Survival waves: time until the next surge.
+    pub shields: u8,               // Shields left (see `--shields`).
+    pub shield_timer: Duration,    // Time until the next spent shield comes back.
+    // Set once ghost mode or a cheat has been used this run, which voids a scaled
+    // score and keeps the run off the leaderboard.
+    pub assisted: bool,
+    // Puzzle mode's food order and move budget (`None` in the normal game).
+    pub puzzle: Option<Puzzle>,
+    // Where food is likeliest to appear, from the map. Empty means anywhere.
+    pub food_zones: Vec<FoodZone>,
+    // `--recap`: pictures of the board taken during the run, for the game over screen.
+    pub recap: recap::Recap,
+    // `--free-cell-cache`: the empty cells, kept up to date as the game goes, so new
+    // food doesn't need a scan of the board. `None` when the cache is off.
+    pub free_cells: Option<FreeCells>,
+}
+impl Game {
+
+    // Constructor method to create a new Game instance.
+    // `start` puts the snake's head on a chosen cell; `None` starts it in the middle.
+    // `first_food` pins the first piece of food to a chosen cell (handy for tests and
+    // puzzles); `None` places it randomly. Later food always spawns randomly.
+    // Returns `Err` with a message if a chosen cell isn't a legal spot.
+    // The random numbers are seeded from the operating system, so every game differs.
+    pub fn new(width: u16, height: u16, start: Option<Point>, first_food: Option<Point>) -> Result<Self, String> {
+        Self::with_rng(width, height, start, first_food, StdRng::from_entropy())
+    }
+
+    // Like `new`, but everything random (the food, the levels' obstacles, the
+    // pickups) comes from `rng`. Two games built from the same seed, e.g.
+    // `StdRng::seed_from_u64(42)`, and played the same way play out the same.
+    pub fn with_rng(
+        width: u16,
+        height: u16,
+        start: Option<Point>,
+        first_food: Option<Point>,
+        rng: StdRng,
+    ) -> Result<Self, String> {
+        // Anything smaller has no room inside the walls, and the random placement
+        // below would have no cells to pick from.
+        if (width as usize) < map::MIN_WIDTH || (height as usize) < map::MIN_HEIGHT {
+            return Err(format!(
+                "a {}x{} board is too small (it needs at least {}x{}, walls included)",
+                width, height, map::MIN_WIDTH, map::MIN_HEIGHT
+            ));
+        }
+        // Start the snake in the middle of the screen, 3 segments long, heading right.
+        // (A board only 5 wide has room for just 2 between the middle and the wall.)
+        let center = Point { x: width / 2, y: height / 2 };
+        let snake = Snake::new(center, Direction::Right, 3.min(center.x as usize));
+        let clock = clock::InstantClock;
+
+        // `mut` means this variable is mutable (can be changed).
+        let mut game = Game {
+            snakes: vec![snake],
+            food: Point { x: 0, y: 0 }, // Placeholder, we'll randomize it immediately below.
+            bomb: None,
+            hourglass: None,
+            reverser: None,
+            power_up: None,
+            obstacles: HashSet::new(),  // Start with no obstacles
+            gates: HashMap::new(),
+            settings: Settings::default(),
+            rng,
+            started_at: clock.now(),
+            clock: Box::new(clock),
+            score: 0,
+            level: 1,
+            apples: 0,
+            painted: HashSet::new(),
+            danger_ahead: false,
+            wrap_grace: 0,
+            tick: 0,
+            game_over: false,
+            game_won: false,
+            paused: false,
+            paused_at: None,
+            best: 0,
+            theme: 0,
+            flip: (false, false),
+            width,
+            height,
+            border: 1,
+            ended_at: None,
+            crashed_at: None,
+            time_left: TIME_ATTACK_START,
+            slow_motion: Duration::ZERO,
+            boost: Duration::ZERO,
+            double_points: Duration::ZERO,
+            messages: Vec::new(),
+            toasts: Vec::new(),
+            wave: 0,
+            wave_timer: WAVE_INTERVAL,
+            shields: 0,
+            shield_timer: SHIELD_REGEN_TIME,
+            assisted: false,
+            puzzle: None,
+            food_zones: Vec::new(),
+            recap: recap::Recap::default(),
+            free_cells: None,
+        };
+
+        if let Some(point) = start {
+            game.place_snake(point)?;
+        }
+        match first_food {
+            Some(point) => game.place_food(point)?,
+            None => game.spawn_food(),
+        }
+        Ok(game)
+    }
+
+    // Player one's snake (the only one outside co-op mode).
+    pub fn snake(&self) -> &Snake {
+        &self.snakes[0]
+    }
+
+    // Is any snake's body on this cell?
+    pub fn snake_at(&self, p: Point) -> bool {
+        self.snake_segments().any(|segment| segment == p)
+    }
+
+    // --- Read-only view of the game, for renderers ---
+    // Everything a front-end (this terminal one, or a GUI or web one) needs to draw
+    // the game, without reaching into its fields. None of these allocate.
+    //
+    // Coordinates: `x` is the column and `y` the row, both counting from 0 at the
+    // top-left corner. The board is `dimensions()` cells big, *including* the outer
+    // wall, so the playable cells are `1..=width - 2` by `1..=height - 2`.
+
+    // Every cell of every snake: each snake head first, then its body to the tail.
+    // `impl Iterator` means "some iterator of Points" without naming its exact type.
+    pub fn snake_segments(&self) -> impl Iterator<Item = Point> + '_ {
+        self.snakes.iter().flat_map(|snake| snake.body.iter().copied())
+    }
+
+    // Where the food is.
+    pub fn food(&self) -> Point {
+        self.food
+    }
+
+    // Every obstacle cell, in no particular order (the outer wall isn't included).
+    pub fn obstacles(&self) -> impl Iterator<Item = Point> + '_ {
+        self.obstacles.iter().copied()
+    }
+
+    pub fn score(&self) -> usize {
+        self.score
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    // Has the game ended (won or lost)?
+    pub fn is_over(&self) -> bool {
+        self.game_over
+    }
+
+    // The board's `(width, height)` in cells, including the outer wall.
+    pub fn dimensions(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    // The playable area inside the outer walls, as inclusive column and row ranges.
+    // The walls take up the first and last `border` columns (and rows); every "is this
+    // on the board?" question goes through here or `in_bounds`, so none of them can be off by one.
+    pub fn interior_bounds(&self) -> (RangeInclusive<u16>, RangeInclusive<u16>) {
+        let b = self.border;
+        (b..=self.width - 1 - b, b..=self.height - 1 - b)
+    }
+
+    // Makes the outer wall `border` cells thick (`--border N`), shrinking the playable
+    // area to match. The snake has to fit in what's left; food, bombs and obstacles
+    // now inside the wall are moved or cleared away.
+    pub fn set_border(&mut self, border: u16) -> Result<(), String> {
+        // What's left inside a wall this thick, the same minimum a map must leave.
+        let inside = |size: u16| size.saturating_sub(border.saturating_mul(2)) as usize;
+        let min_width = map::MIN_WIDTH - 2;
+        let min_height = map::MIN_HEIGHT - 2;
+        if border == 0 || inside(self.width) < min_width || inside(self.height) < min_height {
+            return Err(format!(
+                "a border of {} doesn't fit a {}x{} board (it needs at least 1 and must leave {}x{} cells inside)",
+                border, self.width, self.height, min_width, min_height
+            ));
+        }
+        let old = self.border;
+        self.border = border;
+        if self.snake_segments().any(|p| !self.in_bounds(p)) {
+            self.border = old;
+            return Err(format!("the snake doesn't fit inside a border of {}", border));
+        }
+        // `retain` keeps only the items the closure says yes to. We copy the bounds
+        // first because the closure can't borrow `self` while `obstacles` is being changed.
+        let (xs, ys) = self.interior_bounds();
+        self.obstacles.retain(|p| xs.contains(&p.x) && ys.contains(&p.y));
+        self.forget_removed_gates();
+        if self.bomb.is_some_and(|p| !self.in_bounds(p)) {
+            self.bomb = None;
+        }
+        if self.hourglass.is_some_and(|p| !self.in_bounds(p)) {
+            self.hourglass = None;
+        }
+        if self.reverser.is_some_and(|p| !self.in_bounds(p)) {
+            self.reverser = None;
+        }
+        if self.power_up.is_some_and(|(p, _)| !self.in_bounds(p)) {
+            self.power_up = None;
+        }
+        self.refresh_free_cells();
+        if !self.in_bounds(self.food) {
+            self.spawn_food();
+        }
+        Ok(())
+    }
+
+    // Is `p` inside the outer walls?
+    pub fn in_bounds(&self, p: Point) -> bool {
+        let (xs, ys) = self.interior_bounds();
+        xs.contains(&p.x) && ys.contains(&p.y)
+    }
+
+    // Every cell inside the outer walls, row by row.
+    pub fn interior_cells(&self) -> impl Iterator<Item = Point> + use<> {
+        let (xs, ys) = self.interior_bounds();
+        // `flat_map` turns "every row, and for each one every column" into one list of cells.
+        ys.flat_map(move |y| xs.clone().map(move |x| Point { x, y }))
+    }
+
+    // A random cell inside the outer walls (it may be occupied).
+    pub fn random_interior_cell(&mut self) -> Point {
+        let (xs, ys) = self.interior_bounds();
+        Point { x: self.rng.gen_range(xs), y: self.rng.gen_range(ys) }
+    }
+
+    // Co-op mode: adds player two, mirrored through the center of the board
+    // (rotated 180 degrees), so both players start in the same situation.
+    pub fn add_mirror_snake(&mut self) -> Result<(), String> {
+        let mirror = |p: Point| Point { x: self.width - 1 - p.x, y: self.height - 1 - p.y };
+        let first = self.snake();
+        let second = Snake::new(mirror(first.head()), first.direction.opposite(), first.body.len());
+
+        let fits = second.body.iter().all(|&p| {
+            self.in_bounds(p)
+                && !self.snake_at(p)
+                && !self.obstacles.contains(&p)
+        });
+        if !fits {
+            return Err("there's no room for a second snake opposite the first one".to_string());
+        }
+
+        self.snakes.push(second);
+        self.refresh_free_cells();
+        // The new snake may have landed on the food; if so, move the food.
+        if self.snake_at(self.food) {
+            self.spawn_food();
+        }
+        Ok(())
+    }
+
+    // Moves player one's snake so its head is on `head`, keeping its length and
+    // direction; the body trails out behind it. Refuses spots where the body would
+    // hit a wall or an obstacle. If the snake lands on the food, the food moves.
+    pub fn place_snake(&mut self, head: Point) -> Result<(), String> {
+        self.place_snake_facing(head, self.snake().direction)
+    }
+
+    // Like `place_snake`, but also turns the snake to head in `direction`.
+    pub fn place_snake_facing(&mut self, head: Point, direction: Direction) -> Result<(), String> {
+        self.place_snake_sized(head, direction, self.snake().body.len())
+    }
+
+    // Like `place_snake_facing`, but with a new length too.
+    pub fn place_snake_sized(&mut self, head: Point, direction: Direction, length: usize) -> Result<(), String> {
+        let snake = Snake::new(head, direction, length);
+        let fits = snake.body.iter().all(|&p| {
+            self.in_bounds(p)
+                && !self.obstacles.contains(&p)
+                && !self.snakes[1..].iter().any(|other| other.body.contains(&p))
+        });
+        if !fits {
+            return Err(format!(
+                "a snake starting at {},{} needs {} free cells behind its head, inside the walls",
+                head.x,
+                head.y,
+                snake.body.len() - 1
+            ));
+        }
+        self.snakes[0] = snake;
+        self.refresh_free_cells();
+        if self.snake_at(self.food) {
+            self.spawn_food();
+        }
+        Ok(())
+    }
+
+    // `--random-start-length`: regrows player one's snake, where it stands, to a
+    // random length from `min` to `max`. The top of the range is first cut down to
+    // what fits between the head and the wall behind it (never below one segment).
+    pub fn randomize_start_length(&mut self, min: usize, max: usize) {
+        let head = self.snake().head();
+        let direction = self.snake().direction;
+        // A straight snake is never longer than the board is wide (or tall), so
+        // there's no use trying any longer than that, however big `max` is.
+        let (xs, ys) = self.interior_bounds();
+        let widest = xs.len().max(ys.len());
+        let mut longest = max.clamp(1, widest);
+        while longest > 1 && self.place_snake_sized(head, direction, longest).is_err() {
+            longest -= 1;
+        }
+        let length = self.rng.gen_range(min.min(longest)..=longest);
+        // Shorter than a length that fits, so this fits too.
+        let _ = self.place_snake_sized(head, direction, length);
+    }
+
+    // Quick start (`--face-food`): turns player one's snake, where it stands, to head
+    // for the food along whichever axis the food is further away on. If the body
+    // doesn't fit behind the head that way, it tries the other axis, and failing
+    // that keeps its old direction.
+    pub fn face_food(&mut self) {
+        let head = self.snake().head();
+        let dx = self.food.x as i32 - head.x as i32;
+        let dy = self.food.y as i32 - head.y as i32;
+        let horizontal = if dx < 0 { Direction::Left } else { Direction::Right };
+        let vertical = if dy < 0 { Direction::Up } else { Direction::Down };
+        // `abs` drops the sign: we only care how far, not which way.
+        let mut choices = if dx.abs() >= dy.abs() { vec![horizontal, vertical] } else { vec![vertical, horizontal] };
+        // The other axis is only worth trying if the food is off that way at all.
+        if dx == 0 || dy == 0 {
+            choices.truncate(1);
+        }
+        for direction in choices {
+            if self.place_snake_facing(head, direction).is_ok() {
+                return;
+            }
+        }
+    }
+
+    // Puts the food on a specific cell, refusing walls, the snake and obstacles.
+    pub fn place_food(&mut self, point: Point) -> Result<(), String> {
+        if !self.in_bounds(point) {
+            let (xs, ys) = self.interior_bounds();
+            return Err(format!(
+                "food at {},{} is outside the playable area ({:?}, {:?})",
+                point.x, point.y, xs, ys
+            ));
+        }
+        if self.snake_at(point) || self.obstacles.contains(&point) {
+            return Err(format!("food at {},{} would sit on the snake or an obstacle", point.x, point.y));
+        }
+        self.food = point;
+        Ok(())
+    }
+
+    // Function to place food in a random location not occupied by the snake OR obstacles.
+    // `&mut self` means this method needs to modify the Game state.
+    // If there's no free cell left the board is full: that's a win, unless we're
+    // playing `--endless`, in which case the food stays where it was just eaten
+    // (under the snake) and can be eaten again once that cell is free.
+    pub fn spawn_food(&mut self) {
+        if self.free_cell_count() <= self.pickup_count() {
+            if !self.settings.endless {
+                self.win_game();
+            }
+            return;
+        }
+        if self.settings.spawn == SpawnMode::Far {
+            self.spawn_food_far();
+            return;
+        }
+        // A map's food zones come first; if they're all full, any free cell will do.
+        if !self.food_zones.is_empty() && self.spawn_food_in_zones() {
+            return;
+        }
+        loop {
+            // Generate random x and y coordinates within the walls (or, with the
+            // free cell cache, pick straight from the cells known to be free).
+            let point = match &self.free_cells {
+                Some(free) => free.pick(&mut self.rng).expect("there is a free cell"),
+                None => self.random_interior_cell(),
+            };
+            
+            // If the generated point is NOT inside the snake body AND NOT inside an obstacle, we found a valid spot!
+            if !self.snake_at(point) && !self.obstacles.contains(&point) && !self.pickup_at(point) {
+                self.food = point;
+                break; // Exit the loop.
+            }
+        }
+    }
+
+    // `--spawn far`: picks a free cell with a chance proportional to its distance
+    // from player one's head, so a cell twice as far away is twice as likely.
+    // Only called when there's at least one free cell.
+    pub fn spawn_food_far(&mut self) {
+        let head = self.snake().head();
+        let cells: Vec<Point> = match &self.free_cells {
+            Some(free) => free.iter().filter(|&p| !self.pickup_at(p)).collect(),
+            None => self
+                .interior_cells()
+                .filter(|&p| !self.snake_at(p) && !self.obstacles.contains(&p) && !self.pickup_at(p))
+                .collect(),
+        };
+        // Distances are at least 1, since the head's own cell is never free.
+        let weights = cells.iter().map(|p| p.distance(head));
+        let index = WeightedIndex::new(weights).expect("there is a free cell, and its weight is positive");
+        self.food = cells[index.sample(&mut self.rng)];
+    }
+
+    // Puts the food in one of the map's food zones: a zone is picked by weight,
+    // then a free cell inside it at random. Zones with no free cell are left out;
+    // returns false (placing nothing) if that's all of them.
+    pub fn spawn_food_in_zones(&mut self) -> bool {
+        let zones: Vec<(Vec<Point>, u32)> = self
+            .food_zones
+            .iter()
+            .map(|zone| {
+                let cells: Vec<Point> = zone
+                    .cells()
+                    .filter(|&p| self.in_bounds(p) && !self.snake_at(p) && !self.obstacles.contains(&p) && !self.pickup_at(p))
+                    .collect();
+                (cells, zone.weight)
+            })
+            .filter(|(cells, _)| !cells.is_empty())
+            .collect();
+        if zones.is_empty() {
+            return false;
+        }
+        // Added up as `u64`, so that however many zones a map has, the total can't overflow.
+        let Ok(index) = WeightedIndex::new(zones.iter().map(|&(_, weight)| weight as u64)) else {
+            return false;
+        };
+        let cells = &zones[index.sample(&mut self.rng)].0;
+        self.food = cells[self.rng.gen_range(0..cells.len())];
+        true
+    }
+
+    // Is there a pickup (a bomb, hourglass, reverser or power-up) on this cell?
+    pub fn pickup_at(&self, p: Point) -> bool {
+        self.pickups().contains(&Some(p))
+    }
+
+    // How many pickups are on the board.
+    pub fn pickup_count(&self) -> usize {
+        self.pickups().iter().flatten().count()
+    }
+
+    // Where each kind of pickup is, if it's on the board.
+    fn pickups(&self) -> [Option<Point>; 4] {
+        [self.bomb, self.hourglass, self.reverser, self.power_up.map(|(p, _)| p)]
+    }
+
+    // Is there a free cell for one more pickup, besides the food's and the other pickups'?
+    // (Without one, `random_free_cell` would search forever.)
+    pub fn has_room_for_pickup(&self) -> bool {
+        self.free_cell_count() > 1 + self.pickup_count()
+    }
+
+    // A random cell that isn't snake, obstacle, food or another pickup.
+    // Only call this when `has_room_for_pickup` says there is one.
+    pub fn random_free_cell(&mut self) -> Point {
+        loop {
+            let point = match &self.free_cells {
+                Some(free) => free.pick(&mut self.rng).expect("there is a free cell"),
+                None => self.random_interior_cell(),
+            };
+            if !self.snake_at(point) && !self.obstacles.contains(&point) && point != self.food && !self.pickup_at(point) {
+                return point;
+            }
+        }
+    }
+
+    // Drops a bomb on a random free cell.
+    pub fn spawn_bomb(&mut self) {
+        self.bomb = Some(self.random_free_cell());
+    }
+
+    // Drops an hourglass on a random free cell.
+    pub fn spawn_hourglass(&mut self) {
+        self.hourglass = Some(self.random_free_cell());
+    }
+
+    // Drops a reverser on a random free cell.
+    pub fn spawn_reverser(&mut self) {
+        self.reverser = Some(self.random_free_cell());
+    }
+
+    // Drops a random power-up on a random free cell.
+    pub fn spawn_power_up(&mut self) {
+        let kind = PowerUp::ALL[self.rng.gen_range(0..PowerUp::ALL.len())];
+        self.power_up = Some((self.random_free_cell(), kind));
+    }
+
+    // Picks up a power-up: `player`'s snake shrinks at once; the others start
+    // (or restart) their countdown.
+    pub fn use_power_up(&mut self, player: usize, kind: PowerUp) {
+        match kind {
+            PowerUp::Boost => self.boost = BOOST_TIME,
+            PowerUp::DoublePoints => self.double_points = DOUBLE_POINTS_TIME,
+            PowerUp::Shrink => {
+                let body = &mut self.snakes[player].body;
+                let keep = body.len().saturating_sub(SHRINK_SEGMENTS).max(MIN_SHRUNK_LENGTH);
+                if keep < body.len() {
+                    let removed: Vec<Point> = body.split_off(keep).into();
+                    for cell in removed {
+                        self.vacate(cell);
+                    }
+                }
+            }
+        }
+        self.log(kind.name());
+        self.toast(kind.name().trim_end_matches('!').to_uppercase(), self.snakes[player].head());
+    }
+
+    // Split mode: if `bite` is on the player's own body, cuts the snake off just
+    // before the bitten segment so the head can move on into that cell. The cut-off
+    // part is gone for good. Returns false, leaving the snake alone, when the bite is
+    // too near the head (the snake would end up shorter than `MIN_SPLIT_LENGTH`) or
+    // the cell would be deadly anyway (say, the other snake's body is there too).
+    pub fn try_split(&mut self, player: usize, bite: Point) -> bool {
+        let snake = &self.snakes[player];
+        let Some(index) = snake.body.iter().position(|&p| p == bite) else {
+            return false;
+        };
+        // The snake keeps the `index` segments in front of the bite. The move then
+        // adds the new head and drops the tail, so it ends up `index` long.
+        if index < MIN_SPLIT_LENGTH {
+            return false;
+        }
+        let direction = snake.direction;
+        // `split_off` cuts the deque in two: `body` keeps `0..index` and the rest is returned.
+        let bitten = self.snakes[player].body.split_off(index);
+        if self.is_lethal_move(bite, direction) {
+            self.snakes[player].body.extend(bitten);
+            return false;
+        }
+        self.log(format!("Split off {} segments", bitten.len()));
+        self.toast(format!("-{}", bitten.len()), bite);
+        for cell in bitten {
+            self.vacate(cell);
+        }
+        true
+    }
+
+    // Turns a snake around: its tail becomes its head, and it sets off away from
+    // the segment now behind it. If that would crash straight away (the tail was
+    // against a wall, say), it turns left or right instead when one of those is safe.
+    pub fn reverse_snake(&mut self, player: usize) {
+        // `make_contiguous` lines the deque up in memory as one slice, which can be reversed in place.
+        self.snakes[player].body.make_contiguous().reverse();
+
+        let snake = &self.snakes[player];
+        let head = snake.head();
+        // Segments grown but not unfolded yet share a cell, so look past those.
+        let behind = snake.body.iter().copied().find(|&p| p != head);
+        // Try every direction until one steps from `behind` onto the head. Going
+        // through `advance` means a snake split across a wrapping wall works too.
+        // A one-cell snake has nothing behind it, so it simply turns around.
+        let facing = behind
+            .and_then(|behind| Direction::ALL.into_iter().find(|&d| self.advance(behind, d) == head))
+            .unwrap_or(snake.direction.opposite());
+        let safe = |d: Direction| !self.is_lethal_move(self.advance(head, d), d);
+        let direction = if safe(facing) {
+            facing
+        } else {
+            facing.perpendicular().into_iter().find(|&d| safe(d)).unwrap_or(facing)
+        };
+
+        let snake = &mut self.snakes[player];
+        snake.direction = direction;
+        snake.last_direction = direction;
+        snake.straight_ticks = 0;
+        // Turns pressed for the old head make no sense for the new one.
+        snake.queued_turns.clear();
+    }
+
+    // Blows up the bomb at `center`, removing every obstacle within `BOMB_RADIUS`.
+    // Only obstacles are affected: the outer wall isn't in `obstacles`, and the
+    // snake is never hurt by its own bomb.
+    pub fn detonate(&mut self, center: Point) {
+        self.bomb = None;
+        // `retain` keeps only the items for which the closure returns true.
+        self.obstacles
+            .retain(|p| p.x.abs_diff(center.x) > BOMB_RADIUS || p.y.abs_diff(center.y) > BOMB_RADIUS);
+        self.forget_removed_gates();
+        self.refresh_free_cells();
+    }
+
+    // Clears the obstacles on and right around player one's snake, using the same
+    // "not too close to the head" rule as new levels, so a retried layout (see
+    // `Game::reset`) never kills the snake on its first move.
+    pub fn clear_start_area(&mut self) {
+        let head = self.snake().head();
+        let blocked: Vec<Point> = self
+            .obstacles
+            .iter()
+            .copied()
+            .filter(|&p| self.snake_at(p) || p.x.abs_diff(head.x) + p.y.abs_diff(head.y) <= 3)
+            .collect();
+        for p in blocked {
+            self.obstacles.remove(&p);
+        }
+        self.forget_removed_gates();
+        self.refresh_free_cells();
+        if self.obstacles.contains(&self.food) {
+            self.spawn_food();
+        }
+    }
+
+    // Drops the gates whose obstacle cell has been removed (by a bomb, say).
+    pub fn forget_removed_gates(&mut self) {
+        let obstacles = &self.obstacles;
+        self.gates.retain(|p, _| obstacles.contains(p));
+    }
+
+    // Generates a new set of random obstacles for the current level.
+    // With `--corridor`, a path from the snake to the food is carved out first and
+    // walls keep off it. The walls are then also checked like a surge's (see
+    // `add_wall`), as a safety net should the snake's own body block that path.
+    pub fn generate_level(&mut self) {
+        self.obstacles.clear(); // Remove old obstacles
+        if self.settings.no_obstacles {
+            self.gates.clear();
+            self.refresh_free_cells();
+            return;
+        }
+        // The obstacle counts below are meant for the full-size board. With
+        // `--shrink-to-fit`, a smaller board gets its share of them by area, and
+        // its walls are always placed safely so a cramped level stays winnable.
+        let (xs, ys) = self.interior_bounds();
+        let area = xs.len() * ys.len();
+        let full_area = (WIDTH as usize - 2) * (HEIGHT as usize - 2);
+        let shrunk = self.settings.shrink_to_fit && area < full_area;
+        let safely = self.settings.corridor || shrunk;
+        let corridor = if safely {
+            self.carve_corridor(self.snake().head(), self.food)
+        } else {
+            HashSet::new()
+        };
+
+        // As the level increases, we add more obstacles to make it harder!
+        let mut num_obstacles = self.level * 3 + 5;
+        if shrunk {
+            num_obstacles = num_obstacles * area as u32 / full_area as u32;
+        }
+        
+        for _ in 0..num_obstacles {
+            let mut segment = self.random_wall_segment();
+            segment.retain(|p| !corridor.contains(p));
+            self.add_wall(segment, safely);
+        }
+
+        // `--gates`: a few more walls, one per level after the first, that work as
+        // one-way gates. Each lets the snake cross it head-on, from one side only.
+        self.gates.clear();
+        if self.settings.gates {
+            for _ in 1..self.level {
+                let mut segment = self.random_wall_segment();
+                // A wall running left-right is crossed going up or down, and the other way round.
+                let horizontal = segment.windows(2).all(|pair| pair[0].y == pair[1].y);
+                let along = if horizontal { Direction::Right } else { Direction::Down };
+                let [one_way, other_way] = along.perpendicular();
+                let direction = if self.rng.gen_bool(0.5) { one_way } else { other_way };
+                segment.retain(|p| !corridor.contains(p));
+                // Cells that are already walls stay walls.
+                for p in self.add_wall(segment, safely) {
+                    self.gates.insert(p, direction);
+                }
+            }
+        }
+        self.refresh_free_cells();
+    }
+
+    // `--corridor`: a path of cells from `from` to `to`, found by a random walk that
+    // steps toward `to` most of the time (`CORRIDOR_BIAS`) and in any direction
+    // otherwise. Because it heads for `to` more often than not, it always gets there.
+    pub fn carve_corridor(&mut self, from: Point, to: Point) -> HashSet<Point> {
+        let mut corridor = HashSet::from([from]);
+        let mut at = from;
+        while at != to {
+            // The straight directions that bring us closer (one or two of them).
+            let toward: Vec<Direction> = Direction::ALL[..4]
+                .iter()
+                .copied()
+                .filter(|&d| {
+                    let next = at.step(d);
+                    next.x.abs_diff(to.x) + next.y.abs_diff(to.y) < at.x.abs_diff(to.x) + at.y.abs_diff(to.y)
+                })
+                .collect();
+            let direction = if self.rng.gen_bool(CORRIDOR_BIAS) {
+                toward[self.rng.gen_range(0..toward.len())]
+            } else {
+                Direction::ALL[self.rng.gen_range(0..4)]
+            };
+            let next = at.step(direction);
+            if self.in_bounds(next) {
+                at = next;
+                corridor.insert(at);
+            }
+        }
+        corridor
+    }
+
+    // Turns `cells` into obstacles, returning the ones that weren't already walls.
+    // With `safely`, the new walls are taken back out again (and nothing is returned)
+    // if they'd cut the snake off from the food or leave it trapped.
+    pub fn add_wall(&mut self, cells: Vec<Point>, safely: bool) -> Vec<Point> {
+        // `insert` returns false for cells that were already obstacles; we only
+        // remember the new ones so a rejected wall can be taken back out exactly.
+        let added: Vec<Point> = cells.into_iter().filter(|&p| self.obstacles.insert(p)).collect();
+        if safely && (!self.is_food_reachable() || self.is_trapped()) {
+            for p in added {
+                self.obstacles.remove(&p);
+            }
+            return Vec::new();
+        }
+        added
+    }
+
+    // Pops up `text` next to board cell `pos`, if toasts are on.
+    pub fn toast(&mut self, text: impl Into<String>, pos: Point) {
+        if self.settings.toasts {
+            let since = self.clock.now();
+            self.toasts.push(Toast { text: text.into(), pos, since });
+        }
+    }
+
+    // Adds a line to the side panel's message log, dropping the oldest if it's full.
+    pub fn log(&mut self, message: impl Into<String>) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+        let now = self.clock.now();
+        self.messages.push((message.into(), now));
+    }
+
+    // Moves on to the next level: new obstacles, and in mirror mode a new flip.
+    pub fn level_up(&mut self) {
+        self.level += 1;
+        self.log(format!("Level {}!", self.level));
+        self.generate_level();
+        if self.settings.mirror {
+            // Pick one of the other three flips at random, so the board always changes.
+            let current = self.flip;
+            while self.flip == current {
+                self.flip = (self.rng.gen_bool(0.5), self.rng.gen_bool(0.5));
+            }
+        }
+    }
+
+    // Picks a random short horizontal or vertical wall and returns the cells of it
+    // that are safe to turn into obstacles.
+    // Walls start at least one cell in from the outer wall all round. On a board too small
+    // to leave such a cell (a map only 3 rows tall, say) there's nowhere to start
+    // one, so we return no cells; `gen_range` panics if it's given an empty range.
+    pub fn random_wall_segment(&mut self) -> Vec<Point> {
+        let (xs, ys) = self.interior_bounds();
+        let (xs, ys) = (xs.start() + 1..=xs.end() - 1, ys.start() + 1..=ys.end() - 1);
+        if xs.is_empty() || ys.is_empty() {
+            return Vec::new();
+        }
+
+        // Randomly choose vertical or horizontal wall segment
+        let is_horizontal = self.rng.gen_bool(0.5);
+        let length = self.rng.gen_range(3..8); // Random length for the wall
+        
+        let start_x = self.rng.gen_range(xs);
+        let start_y = self.rng.gen_range(ys);
+        
+        let mut cells = Vec::new();
+        for i in 0..length {
+            let p = if is_horizontal {
+                Point { x: start_x + i, y: start_y }
+            } else {
+                Point { x: start_x, y: start_y + i }
+            };
+            
+            // IMPORTANT Checks:
+            // 1. Keep obstacles within bounds.
+            // 2. Don't spawn on top of a snake.
+            // 3. Don't spawn on top of the food.
+            // 4. Don't spawn right in front of a snake's face (unfair!).
+            if self.in_bounds(p)
+               && !self.snake_at(p)
+               && p != self.food 
+               && !self.pickup_at(p)
+               && self.snakes.iter().all(|s| { let head = s.head(); (head.x as i32 - p.x as i32).abs() + (head.y as i32 - p.y as i32).abs() > 3 })
+            {
+                cells.push(p);
+            }
+        }
+        cells
+    }
+
+    // Survival waves: adds a surge of extra walls on top of the ones already there.
+    // Unlike `generate_level` (without `--corridor`), each wall is only kept if the food
+    // can still be reached and the snake isn't trapped, so a surge can never make the game unwinnable.
+    pub fn surge(&mut self) {
+        self.wave += 1;
+        self.log(format!("Wave {} hits!", self.wave));
+        for _ in 0..WAVE_BASE_WALLS + self.wave {
+            let segment = self.random_wall_segment();
+            self.add_wall(segment, true);
+        }
+        self.refresh_free_cells();
+    }
+
+    // Starts the level this run ended on over again (R on the game over screen) on
+    // `fresh`, a new board set up the same way this one was: a fresh snake and
+    // score, the same level, and the obstacles `layout` asks for. The run just
+    // played counts towards the best score.
+    pub fn reset(&mut self, fresh: Game, layout: RetryLayout) {
+        let mut game = fresh;
+        game.level = self.level;
+        game.theme = self.theme;
+        game.flip = self.flip;
+        game.best = self.best.max(self.leaderboard_score());
+        match layout {
+            RetryLayout::Same => {
+                game.obstacles = std::mem::take(&mut self.obstacles);
+                game.gates = std::mem::take(&mut self.gates);
+                game.clear_start_area();
+            }
+            // Level 1 has no generated obstacles (just the map's, if any), so keep those.
+            RetryLayout::Fresh if game.level > 1 => game.generate_level(),
+            RetryLayout::Fresh => {}
+        }
+        // The new run keeps telling the time by the same clock.
+        game.clock = std::mem::replace(&mut self.clock, Box::new(clock::InstantClock));
+        game.started_at = game.clock.now();
+        *self = game;
+    }
+
+    // Ends the run and freezes the timer.
+    // We capture the end `Instant` right here (inside `update`) rather than when the
+    // player dismisses the game-over screen, so speedrun times are exact.
+    pub fn end_game(&mut self) {
+        self.game_over = true;
+        self.ended_at = Some(self.clock.now());
+    }
+
+    // Puzzle mode: puts out the next food in the map's order, or wins once the
+    // last one is eaten.
+    pub fn next_puzzle_food(&mut self) {
+        let Some(puzzle) = &mut self.puzzle else {
+            return;
+        };
+        puzzle.eaten += 1;
+        match puzzle.food.get(puzzle.eaten) {
+            Some(&food) => self.food = food,
+            None => self.win_game(),
+        }
+    }
+
+    // Ends the run as a win.
+    pub fn win_game(&mut self) {
+        self.game_won = true;
+        self.end_game();
+    }
+
+    // Paint mode: what percentage of the open floor (not obstacles) has been painted.
+    pub fn painted_percent(&self) -> usize {
+        let open_floor = self.interior_capacity() - self.obstacles.len();
+        let painted = self.painted.iter().filter(|p| !self.obstacles.contains(p)).count();
+        painted * 100 / open_floor.max(1)
+    }
+
+    // `--scaled-score`: how much the score is worth given the rules in play. Each
+    // rule that makes the game harder raises it, each that makes it easier lowers
+    // it, and they multiply together. Ghost mode or a cheat at any point this run
+    // makes it zero. Wrap and the audio assist count as they are when the run ends.
+    pub fn score_multiplier(&self) -> f32 {
+        if self.assisted || self.settings.ghost {
+            return 0.0;
+        }
+        let s = &self.settings;
+        let mut multiplier = match s.speed_curve {
+            SpeedCurve::Flat => 1.0,
+            SpeedCurve::Logarithmic => 1.1,
+            SpeedCurve::Linear => 1.25,
+            SpeedCurve::Exponential => 1.5,
+        };
+        // (rule in play, what it multiplies the score by)
+        let rules = [
+            (s.accelerate, 1.2),
+            (s.drunk, 1.3),
+            (s.waves, 1.2),
+            (s.gates, 1.1),
+            (s.mirror, 1.2),
+            (s.wrap, 0.8),
+            (s.audio_assist, 0.9),
+            (s.corridor, 0.9),
+            (s.split, 0.5),
+            (s.shields > 0, 0.7),
+        ];
+        for (on, factor) in rules {
+            if on {
+                multiplier *= factor;
+            }
+        }
+        multiplier
+    }
+
+    // The score that goes on the leaderboard: the raw score, or with
+    // `--scaled-score`, the raw score times `score_multiplier`, rounded.
+    pub fn leaderboard_score(&self) -> usize {
+        if !self.settings.scaled_score {
+            return self.score;
+        }
+        (self.score as f32 * self.score_multiplier()).round() as usize
+    }
+
+    // Pauses or resumes the game. Time spent paused doesn't count towards the run's
+    // time: on resuming, the start of the run moves forward by however long the
+    // pause lasted.
+    pub fn set_paused(&mut self, paused: bool) {
+        let now = self.clock.now();
+        match (self.paused_at, paused) {
+            (None, true) => self.paused_at = Some(now),
+            (Some(at), false) => {
+                self.started_at += now.duration_since(at);
+                self.paused_at = None;
+            }
+            _ => {}
+        }
+        self.paused = paused;
+    }
+
+    // How long this run has lasted (or lasted, if it's already over). While paused
+    // the timer stands still at the moment the pause began.
+    pub fn elapsed(&self) -> Duration {
+        let end = self.ended_at.or(self.paused_at).unwrap_or_else(|| self.clock.now());
+        end.duration_since(self.started_at)
+    }
+
+    // How long the current tick should last.
+    // The base speed depends on the level, following `--speed-curve`.
+    // In acceleration mode every straight tick makes the snake a little faster,
+    // capped at `MAX_ACCEL_TICKS` steps; turning drops back to base speed.
+    // Slow motion (from an hourglass) comes first: while it lasts, every tick is
+    // `SLOW_MOTION_FACTOR` times the base length, however fast the snake was going.
+    // The straight-line streak keeps counting meanwhile, so acceleration picks up
+    // where it was once the slow motion wears off.
+    // A speed boost power-up comes after that, making the tick `BOOST_FACTOR` times
+    // shorter (but never shorter than `MIN_TICK_RATE`).
+    pub fn effective_tick_rate(&self) -> Duration {
+        let base = self.settings.speed_curve.tick_rate(self.settings.tick_rate, self.level);
+        if !self.slow_motion.is_zero() {
+            return base * SLOW_MOTION_FACTOR;
+        }
+        let rate = if self.settings.accelerate {
+            let boost = ACCEL_STEP * self.snake().straight_ticks.min(MAX_ACCEL_TICKS);
+            base.saturating_sub(boost).max(MIN_TICK_RATE)
+        } else {
+            base
+        };
+        if self.boost.is_zero() {
+            rate
+        } else {
+            (rate / BOOST_FACTOR).max(MIN_TICK_RATE)
+        }
+    }
+
+    // Turns one player's snake (0 = player one), if that player exists.
+    pub fn turn(&mut self, player: usize, direction: Direction) {
+        if let Some(snake) = self.snakes.get_mut(player) {
+            snake.turn(direction);
+        }
+    }
+
+    // Where player one's head would end up after one step in `direction`.
+    pub fn next_head(&self, direction: Direction) -> Point {
+        self.advance(self.snake().head(), direction)
+    }
+
+    // One step from `p` in `direction`, going through the walls when they wrap.
+    pub fn advance(&self, p: Point, direction: Direction) -> Point {
+        let next = p.step(direction);
+        if !self.walls_wrap() {
+            return next;
+        }
+        // Stepping onto a wall puts us on the far side of the playable area.
+        // (Going left from x=1 gives x=0, the left wall; `step` never goes below 0.)
+        let wrap = |v: u16, range: RangeInclusive<u16>| {
+            if v < *range.start() {
+                *range.end()
+            } else if v > *range.end() {
+                *range.start()
+            } else {
+                v
+            }
+        };
+        let (xs, ys) = self.interior_bounds();
+        Point { x: wrap(next.x, xs), y: wrap(next.y, ys) }
+    }
+
+    // Do the outer walls currently wrap around (including the grace period after switching off)?
+    pub fn walls_wrap(&self) -> bool {
+        self.settings.wrap || self.wrap_grace > 0
+    }
+
+    // Switches wrap-around walls on or off mid-game.
+    pub fn toggle_wrap(&mut self) {
+        self.settings.wrap = !self.settings.wrap;
+        // Turning the walls solid under a snake that's about to go through one
+        // would be an unfair instant death, so give a few ticks' notice.
+        self.wrap_grace = if self.settings.wrap { 0 } else { WRAP_GRACE_TICKS };
+    }
+
+    // Would moving the head onto `p` by going `direction` kill the snake?
+    // The same as `is_lethal`, except that a one-way gate lets the snake through
+    // when it's going the way the gate's arrow points.
+    pub fn is_lethal_move(&self, p: Point, direction: Direction) -> bool {
+        if self.gates.get(&p) == Some(&direction) {
+            return !self.in_bounds(p) || self.snake_at(p);
+        }
+        self.is_lethal(p)
+    }
+
+    // Would moving the head onto `p` kill the snake?
+    // (One-way gates count as solid here; see `is_lethal_move`.)
+    pub fn is_lethal(&self, p: Point) -> bool {
+        // 1. Wall collision checks (Outer borders).
+        let hits_wall = !self.in_bounds(p);
+
+        // 2. Snake collision check (biting your own tail, or the other snake in co-op).
+        // 3. Obstacle collision check (hitting a generated wall), unless we're a ghost.
+        let hits_obstacle = !self.settings.ghost && self.obstacles.contains(&p);
+        hits_wall || self.snake_at(p) || hits_obstacle
+    }
+
+    // Shields: turns `player`'s snake left or right, whichever way is safe, in place
+    // of the fatal move it was about to make. Returns false if neither is.
+    pub fn dodge(&mut self, player: usize) -> bool {
+        let snake = &self.snakes[player];
+        let head = snake.head();
+        let safe = snake
+            .direction
+            .perpendicular()
+            .into_iter()
+            .find(|&direction| !self.is_lethal_move(self.advance(head, direction), direction));
+        let Some(direction) = safe else {
+            return false;
+        };
+        let snake = &mut self.snakes[player];
+        snake.direction = direction;
+        snake.last_direction = direction;
+        snake.straight_ticks = 0;
+        // Any turns still queued were meant for the old course.
+        snake.queued_turns.clear();
+        true
+    }
+
+    // Advances the real-time parts of the game by `dt`, the time since the last frame.
+    // Unlike `update`, this runs every frame, not once per tick.
+    pub fn update_timers(&mut self, dt: Duration) {
+        if self.game_over || self.paused {
+            return;
+        }
+
+        // Time attack: the clock drains constantly, and running out ends the game
+        // no matter where the snake is.
+        if self.settings.time_attack {
+            self.time_left = self.time_left.saturating_sub(dt);
+            if self.time_left.is_zero() {
+                self.end_game();
+            }
+        }
+
+        // Slow motion wears off in real time, like the time attack clock, and so do
+        // the timed power-ups.
+        self.slow_motion = self.slow_motion.saturating_sub(dt);
+        self.boost = self.boost.saturating_sub(dt);
+        self.double_points = self.double_points.saturating_sub(dt);
+
+        // Shields: spent ones come back one at a time, up to the maximum.
+        if self.shields < self.settings.shields {
+            self.shield_timer = self.shield_timer.saturating_sub(dt);
+            if self.shield_timer.is_zero() {
+                self.shields += 1;
+                self.shield_timer = SHIELD_REGEN_TIME;
+                self.log("Shield recharged");
+            }
+        }
+
+        // Old messages fade out of the side panel's log, and old toasts off the board.
+        let now = self.clock.now();
+        self.messages.retain(|(_, at)| now.duration_since(*at) < MESSAGE_TIME);
+        self.toasts.retain(|toast| now.duration_since(toast.since) < TOAST_TIME);
+
+        // Survival waves: a surge of walls arrives on a fixed schedule, however well you're doing.
+        if self.settings.waves {
+            self.wave_timer = self.wave_timer.saturating_sub(dt);
+            if self.wave_timer.is_zero() {
+                self.surge();
+                self.wave_timer = WAVE_INTERVAL;
+            }
+        }
+    }
+
+    // --- Board analysis helpers (used by the debug metrics panel) ---
+
+    // How many cells are inside the outer walls.
+    pub fn interior_capacity(&self) -> usize {
+        let (xs, ys) = self.interior_bounds();
+        xs.len() * ys.len()
+    }
+
+    // How many interior cells are empty (not snake and not obstacle).
+    // (We count cell by cell, so a snake poking into a wall or a ghost on top of
+    // an obstacle can't make the numbers go wrong.)
+    pub fn free_cell_count(&self) -> usize {
+        if let Some(free) = &self.free_cells {
+            return free.len();
+        }
+        self.interior_cells()
+            .filter(|&p| !self.snake_at(p) && !self.obstacles.contains(&p))
+            .count()
+    }
+
+    // --- The free cell cache (`--free-cell-cache`, see `free_cells.rs`) ---
+
+    // Switches the cache on, filling it from the board as it is now.
+    pub fn enable_free_cell_cache(&mut self) {
+        self.free_cells = Some(FreeCells::default());
+        self.refresh_free_cells();
+    }
+
+    // Refills the cache from scratch, after changes too sweeping to follow cell by
+    // cell (a new level, a bomb going off, a snake put somewhere new).
+    // Does nothing while the cache is off.
+    pub fn refresh_free_cells(&mut self) {
+        if self.free_cells.is_none() {
+            return;
+        }
+        let mut free = FreeCells::default();
+        for p in self.interior_cells() {
+            if !self.snake_at(p) && !self.obstacles.contains(&p) {
+                free.insert(p);
+            }
+        }
+        self.free_cells = Some(free);
+    }
+
+    // A snake's head has moved onto `cell`, so it's no longer free.
+    pub fn occupy(&mut self, cell: Point) {
+        if let Some(free) = &mut self.free_cells {
+            free.remove(cell);
+        }
+    }
+
+    // A snake segment has left `cell`. It's free again, unless another segment is
+    // still there, it's an obstacle (a ghost can slide over those), or it's outside the walls.
+    pub fn vacate(&mut self, cell: Point) {
+        let free_again = self.in_bounds(cell) && !self.snake_at(cell) && !self.obstacles.contains(&cell);
+        if let Some(free) = &mut self.free_cells
+            && free_again
+        {
+            free.insert(cell);
+        }
+    }
+
+    // Every empty cell the head could reach by moving through empty cells
+    // (a "flood fill", see `pathfinding.rs`).
+    pub fn reachable_from_head(&self) -> HashSet<Point> {
+        pathfinding::reachable_cells(
+            self.snake().head(),
+            |p, direction| self.advance(p, direction),
+            |p| !self.is_lethal(p),
+        )
+    }
+
+    // `--local-grid`: the empty cells within `LOCAL_GRID_RADIUS` steps of player
+    // one's head, which get a guide dot each.
+    pub fn local_grid(&self) -> Vec<Point> {
+        let head = self.snake().head();
+        self.interior_cells()
+            .filter(|&p| p.distance(head) <= LOCAL_GRID_RADIUS)
+            .filter(|&p| !self.snake_at(p) && !self.obstacles.contains(&p) && p != self.food && !self.pickup_at(p))
+            .collect()
+    }
+
+    // `--show-regions`: splits the floor into "regions", areas cut off from each
+    // other by obstacles, numbering each cell by its region (0, 1, 2...). A level
+    // where everything is region 0 is fully connected. Snakes don't count as walls
+    // here, since they move out of the way.
+    pub fn regions(&self) -> HashMap<Point, usize> {
+        let is_free = |p: Point| self.in_bounds(p) && !self.obstacles.contains(&p);
+        let mut regions = HashMap::new();
+        let mut count = 0;
+        for cell in self.interior_cells() {
+            if !is_free(cell) || regions.contains_key(&cell) {
+                continue;
+            }
+            // Everything this cell's flood fill reaches is in the same region (and
+            // so is the cell itself, even when it's a lone cell with no way out).
+            let reached = pathfinding::reachable_cells(cell, |p, direction| self.advance(p, direction), is_free);
+            for p in reached.into_iter().chain([cell]) {
+                regions.insert(p, count);
+            }
+            count += 1;
+        }
+        regions
+    }
+
+    // `--hint`: two suggested next moves for player one, as the cells they'd take
+    // the head to. The greedy move is the first step of a shortest way to the food.
+    // The safe move leaves the head a way to reach the tail afterwards, so the snake
+    // can always follow its own tail out of trouble: it's the greedy move if that's
+    // safe too, or else the safe move that ends nearest the food. Either one is
+    // `None` if there's no such move.
+    pub fn hints(&self) -> (Option<Point>, Option<Point>) {
+        let snake = self.snake();
+        let greedy = pathfinding::bfs_path(
+            snake.head(),
+            self.food,
+            |p, direction| self.advance(p, direction),
+            |p| !self.is_lethal(p),
+        )
+        .and_then(|path| path.first().copied());
+        let safe_moves: Vec<Point> = [Direction::Up, Direction::Down, Direction::Left, Direction::Right]
+            .into_iter()
+            .filter(|&direction| direction != snake.direction.opposite())
+            .map(|direction| self.next_head(direction))
+            .filter(|&p| !self.is_lethal(p) && self.keeps_tail_reachable(p))
+            .collect();
+        let safe = match greedy {
+            Some(p) if safe_moves.contains(&p) => Some(p),
+            _ => safe_moves.into_iter().min_by_key(|p| p.distance(self.food)),
+        };
+        (greedy, safe)
+    }
+
+    // After player one's head moves onto `next`, could it still find a way to the tail?
+    pub fn keeps_tail_reachable(&self, next: Point) -> bool {
+        // Where the body would be: the new head on, and the old tail off unless eating.
+        let mut body = self.snake().body.clone();
+        body.push_front(next);
+        if next != self.food {
+            body.pop_back();
+        }
+        let tail = *body.back().expect("the body has at least the new head");
+        if tail == next {
+            return true;
+        }
+        let is_free = |p: Point| {
+            p == tail
+                || (self.in_bounds(p)
+                    && !self.obstacles.contains(&p)
+                    && !body.contains(&p)
+                    && !self.snakes[1..].iter().any(|other| other.body.contains(&p)))
+        };
+        pathfinding::bfs_path(next, tail, |p, direction| self.advance(p, direction), is_free).is_some()
+    }
+
+    // How many moves the head needs to reach the food, if it can at all.
+    pub fn food_distance(&self) -> Option<usize> {
+        pathfinding::bfs_path(
+            self.snake().head(),
+            self.food,
+            |p, direction| self.advance(p, direction),
+            |p| !self.is_lethal(p),
+        )
+        .map(|path| path.len())
+    }
+
+    // Can the head get to the food at all?
+    pub fn is_food_reachable(&self) -> bool {
+        self.reachable_from_head().contains(&self.food)
+    }
+
+    // The snake is "trapped" when the space it can reach is smaller than its own
+    // body: it will run out of room before its tail gets out of the way.
+    pub fn is_trapped(&self) -> bool {
+        self.reachable_from_head().len() < self.snake().body.len()
+    }
+
+    // Update the game state (move snake, check collisions).
+    pub fn update(&mut self) {
+        if self.game_over {
+            return;
+        }
+
+        self.tick += 1;
+        // Moving through walls even once voids a scaled score (see `score_multiplier`).
+        self.assisted |= self.settings.ghost;
+
+        // Snakes that are out of the round (versus mode) don't move any more.
+        for snake in self.snakes.iter_mut().filter(|snake| !snake.crashed) {
+            // Make the next turn the player asked for, if any (one per tick).
+            // Each turn was checked against the one queued before it, but drunk mode
+            // may have swerved since, so check it again against the way the snake
+            // actually went last tick: one that has become a 180 (or no turn at all)
+            // is dropped, and the next one gets this tick instead.
+            while let Some(direction) = snake.queued_turns.pop_front() {
+                if direction != snake.last_direction && direction != snake.last_direction.opposite() {
+                    snake.direction = direction;
+                    break;
+                }
+            }
+
+            // Drunk mode: now and then, swerve into a random left or right turn.
+            // Only perpendicular turns are picked, so this can never cause a 180.
+            if self.settings.drunk && self.rng.gen_bool(DRUNK_TURN_CHANCE) {
+                let turns = snake.direction.perpendicular();
+                snake.direction = turns[self.rng.gen_range(0..turns.len())];
+            }
+
+            // Count how long we've been going straight (a turn resets the count).
+            if snake.direction == snake.last_direction {
+                snake.straight_ticks += 1;
+            } else {
+                snake.straight_ticks = 0;
+            }
+            snake.last_direction = snake.direction;
+        }
+
+        // Calculate every snake's new head position based on its direction.
+        // All snakes move at the same time, so we work out every move before making any.
+        let mut new_heads: Vec<Point> = self
+            .snakes
+            .iter()
+            .map(|snake| if snake.crashed { snake.head() } else { self.advance(snake.head(), snake.direction) })
+            .collect();
+        self.wrap_grace = self.wrap_grace.saturating_sub(1);
+
+        // Moving into a wall, an obstacle or any snake ends the game.
+        // So does two heads arriving on the same cell at once (a head-on crash).
+        // In versus mode it only puts the snake that crashed out of the round.
+        let mut crashes = Vec::new();
+        for (i, &new_head) in new_heads.iter().enumerate() {
+            if self.snakes[i].crashed {
+                continue;
+            }
+            let head_on = new_heads.iter().enumerate().any(|(j, &other)| j != i && other == new_head);
+            // Split mode: biting yourself costs the bitten-off part instead of the game.
+            if self.settings.split && !head_on && self.try_split(i, new_head) {
+                continue;
+            }
+            if self.is_lethal_move(new_head, self.snakes[i].direction) || head_on {
+                // A shield absorbs the hit: nobody moves this tick, and the snake
+                // that would have crashed turns somewhere safe instead.
+                if self.shields > 0 && self.dodge(i) {
+                    self.shields -= 1;
+                    self.log("Shield absorbed a hit");
+                    self.toast("SHIELD", self.snakes[i].head());
+                    return;
+                }
+                self.crashed_at = Some(new_head);
+                if self.settings.versus {
+                    crashes.push(i);
+                    continue;
+                }
+                self.end_game();
+                return;
+            }
+        }
+        for i in crashes {
+            let snake = &mut self.snakes[i];
+            snake.crashed = true;
+            new_heads[i] = snake.head();
+            let head = snake.head();
+            self.log(format!("Player {} is out!", i + 1));
+            self.toast("OUT", head);
+        }
+        if self.snakes.iter().all(|snake| snake.crashed) {
+            self.end_game();
+            return;
+        }
+
+        // Move the snakes:
+        let mut ate = false;
+        let mut bombed = None;
+        let mut slowed = None;
+        let mut powered = None;
+        let mut vacated = Vec::new();
+        for (i, (snake, &new_head)) in self.snakes.iter_mut().zip(&new_heads).enumerate() {
+            if snake.crashed {
+                continue;
+            }
+            // Add the new head position to the front of the deque.
+            snake.body.push_front(new_head);
+
+            // With a growth delay, segments owed from earlier meals are released one
+            // every `growth_delay` ticks.
+            let delay = self.settings.growth_delay;
+            let mut grows = false;
+            if snake.pending_growth > 0 {
+                snake.growth_ticks += 1;
+                if snake.growth_ticks >= delay {
+                    snake.pending_growth -= 1;
+                    snake.growth_ticks = 0;
+                    grows = true;
+                }
+            }
+
+            if new_head == self.food {
+                ate = true;
+                snake.apples += 1;
+                if delay == 0 {
+                    grows = true;
+                } else {
+                    // Grow later instead: owe the snake a segment.
+                    snake.pending_growth += 1;
+                }
+            }
+
+            if !grows {
+                // Not growing: Remove the last block (tail) to maintain the same length.
+                // This creates the illusion of movement.
+                // (When growing we do NOT remove the tail. This makes the snake grow by 1 block!)
+                vacated.extend(snake.body.pop_back());
+            }
+
+            // A meal starts the pulse at the head; otherwise it moves one segment
+            // toward the tail, and is gone once it passes it.
+            snake.pulse = if new_head == self.food {
+                Some(0)
+            } else {
+                snake.pulse.map(|i| i + 1).filter(|&i| i < snake.body.len())
+            };
+
+            // Picking up a bomb doesn't grow the snake; it goes off once everyone has moved.
+            if Some(new_head) == self.bomb {
+                bombed = Some(new_head);
+            }
+
+            // An hourglass starts (or restarts) the slow motion. A second one doesn't
+            // add up: it just winds the clock back to full.
+            if Some(new_head) == self.hourglass {
+                self.hourglass = None;
+                self.slow_motion = SLOW_MOTION_TIME;
+                slowed = Some(new_head);
+            }
+
+            // A power-up takes effect once everyone has moved (a shrink needs the
+            // snake's new tail).
+            if let Some((at, kind)) = self.power_up
+                && at == new_head
+            {
+                self.power_up = None;
+                powered = Some((i, kind));
+            }
+        }
+        if let Some(at) = slowed {
+            self.log("Slow motion!");
+            self.toast("SLOW-MO", at);
+        }
+        // Keep the free cell cache in step with the moves.
+        for &head in &new_heads {
+            self.occupy(head);
+        }
+        for &cell in &vacated {
+            self.vacate(cell);
+        }
+        if let Some((player, kind)) = powered {
+            self.use_power_up(player, kind);
+        }
+
+        // A reverser turns around the snake that picked it up.
+        if let Some(player) = new_heads.iter().position(|&head| Some(head) == self.reverser) {
+            self.reverser = None;
+            self.reverse_snake(player);
+            self.log("Reversed!");
+            self.toast("REVERSED", self.snakes[player].head());
+        }
+
+        // The next frame redraws the whole board, so blasted obstacles simply vanish from the screen.
+        if let Some(center) = bombed {
+            self.detonate(center);
+            self.log("Boom!");
+            self.toast("BOOM!", center);
+        }
+
+        // Paint mode: the cells tails just left get painted; fresh paint scores.
+        if self.settings.paint {
+            for cell in vacated {
+                // `insert` returns true only if the cell wasn't painted already.
+                if self.painted.insert(cell) {
+                    self.score += 1;
+                }
+            }
+            if self.painted_percent() >= self.settings.paint_target as usize {
+                self.win_game();
+                return;
+            }
+        }
+
+        // Check if we ate food.
+        if ate {
+            // Ate food: Score goes up (twice over with double points), spawn new food.
+            let points = if self.double_points.is_zero() { 1 } else { 2 };
+            self.score += points;
+            self.apples += 1;
+            self.toast(format!("+{}", points), self.food);
+            // Puzzle mode: the map's next food, and no pickups or new levels.
+            if self.puzzle.is_some() {
+                self.next_puzzle_food();
+            } else {
+                self.spawn_food();
+
+                // Now and then, eating also drops a pickup (only one of each kind at a time).
+                if self.bomb.is_none() && self.has_room_for_pickup() && self.rng.gen_bool(BOMB_CHANCE) {
+                    self.spawn_bomb();
+                }
+                if self.hourglass.is_none() && self.has_room_for_pickup() && self.rng.gen_bool(HOURGLASS_CHANCE) {
+                    self.spawn_hourglass();
+                }
+                if self.reverser.is_none() && self.has_room_for_pickup() && self.rng.gen_bool(REVERSER_CHANCE) {
+                    self.spawn_reverser();
+                }
+                if self.settings.power_ups
+                    && self.power_up.is_none()
+                    && self.has_room_for_pickup()
+                    && self.rng.gen_bool(POWER_UP_CHANCE)
+                {
+                    self.spawn_power_up();
+                }
+
+                // In time attack, food also refills the clock.
+                if self.settings.time_attack {
+                    self.time_left += self.settings.time_bonus;
+                }
+
+                // --- Level Up Logic ---
+                // Every few apples (5 unless `--level-every` says otherwise), we increase
+                // the level and generate new obstacles!
+                // (We count apples rather than points, since some modes score in other ways.)
+                if self.apples.is_multiple_of(self.settings.level_every) {
+                    self.level_up();
+                    self.toast(format!("LEVEL {}", self.level), self.snake().head());
+                }
+            }
+        }
+
+        // Puzzle mode: every move spends one from the budget, and running out
+        // before the last food is eaten loses.
+        if let Some(puzzle) = &mut self.puzzle {
+            puzzle.moves_left = puzzle.moves_left.saturating_sub(1);
+            if puzzle.moves_left == 0 && !self.game_over {
+                self.end_game();
+                return;
+            }
+        }
+
+        // Look one step ahead: if carrying straight on would be fatal, flag it
+        // so the front-end can play a warning (see `--audio-assist`).
+        let direction = self.snake().direction;
+        self.danger_ahead = !self.snake().crashed && self.is_lethal_move(self.next_head(direction), direction);
+    }
+
+    // Versus mode: who won the round (0 is player one), by apples eaten.
+    // `None` for a draw.
+    pub fn versus_winner(&self) -> Option<usize> {
+        let most = self.snakes.iter().map(|snake| snake.apples).max()?;
+        let mut leaders = self.snakes.iter().enumerate().filter(|(_, snake)| snake.apples == most);
+        match (leaders.next(), leaders.next()) {
+            (Some((i, _)), None) => Some(i),
+            _ => None,
+        }
+    }
+
+    // Turns a direction as seen on screen into the matching direction on the board:
+    // on a left-right flipped board, pressing Left has to move the snake Right.
+    pub fn unflip(&self, direction: Direction) -> Direction {
+        let (flip_x, flip_y) = self.flip;
+        let direction = if flip_x { direction.mirror_x() } else { direction };
+        if flip_y { direction.mirror_y() } else { direction }
+    }
+
+    // The HUD's input queue indicator (`--show-queue`): the turns each snake will
+    // make on its coming ticks, as arrows, like "Next: ↑←" (or "Next: ↑/←" in co-op).
+    // Arrows point the way the snake will go on screen, so in mirror mode they match
+    // the keys that were pressed. `None` while nothing is queued.
+    pub fn queue_indicator(&self) -> Option<String> {
+        if self.snakes.iter().all(|snake| snake.queued_turns.is_empty()) {
+            return None;
+        }
+        let queues: Vec<String> = self
+            .snakes
+            .iter()
+            .map(|snake| snake.queued_turns.iter().map(|&d| self.unflip(d).arrow()).collect())
+            .collect();
+        Some(format!("Next: {} ", queues.join("/")))
+    }
+
+    // The text for the terminal's window/tab title (see `--title`).
+    pub fn window_title(&self) -> String {
+        if self.game_over {
+            format!("Snake - final score {} (level {})", self.score, self.level)
+        } else {
+            format!("Snake - score {} - level {}", self.score, self.level)
+        }
+    }
+
+    // `--recap`: takes a picture of the board for the recap, if this tick needs one.
+    // Called after each `update`.
+    pub fn record_recap(&mut self) {
+        if self.settings.recap && self.recap.due(self.tick, self.is_over()) {
+            let frame = recap::thumbnail(&self.render_ascii());
+            self.recap.push(self.tick, frame);
+        }
+    }
+
+    // Renders the whole board as plain ASCII text, one line per row, without crossterm.
+    // (Always unflipped: this shows the board as the game sees it, not as drawn in mirror mode.)
+    // Useful for logging, dumping a board, tests, and terminals that can't do fancy output.
+    //   # wall   X obstacle   * food   B bomb   H hourglass   R reverser   O snake head   o snake body   + painted   . empty
+    //   ^ v < >  one-way gate, crossable going the way it points
+    pub fn render_ascii(&self) -> String {
+        let mut out = String::with_capacity((self.width as usize + 1) * self.height as usize);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let p = Point { x, y };
+                let tile = if !self.in_bounds(p) {
+                    '#'
+                } else if self.snakes.iter().any(|s| s.head() == p) {
+                    'O'
+                } else if self.snake_at(p) {
+                    'o'
+                } else if p == self.food() {
+                    '*'
+                } else if self.bomb == Some(p) {
+                    'B'
+                } else if self.hourglass == Some(p) {
+                    'H'
+                } else if self.reverser == Some(p) {
+                    'R'
+                } else if let Some((at, kind)) = self.power_up
+                    && at == p
+                {
+                    match kind {
+                        PowerUp::Boost => 'F',
+                        PowerUp::Shrink => 'S',
+                        PowerUp::DoublePoints => '$',
+                    }
+                } else if let Some(direction) = self.gates.get(&p) {
+                    match direction {
+                        Direction::Up => '^',
+                        Direction::Down => 'v',
+                        Direction::Left => '<',
+                        Direction::Right => '>',
+                        // Gates are only ever made straight, but just in case.
+                        _ => 'X',
+                    }
+                } else if self.obstacles.contains(&p) {
+                    'X'
+                } else if self.painted.contains(&p) {
+                    '+'
+                } else {
+                    '.'
+                };
+                out.push(tile);
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+// Formats a duration as `MM:SS.mmm` (minutes, seconds, milliseconds) for the speedrun timer.
+// `{:02}` pads a number with zeros to 2 digits, `{:03}` to 3 digits.
+pub fn format_time(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    let minutes = millis / 60_000;
+    let seconds = (millis / 1000) % 60;
+    format!("{:02}:{:02}.{:03}", minutes, seconds, millis % 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_support::{mock_clock, small_game, snake};
+
+    #[test]
+    fn renders_a_fresh_board() {
+        let game = small_game(Point { x: 8, y: 1 });
+        assert_eq!(
+            game.render_ascii(),
+            "\
+##########
+#.......*#
+#........#
+#..ooO...#
+#........#
+##########
+"
+        );
+    }
+
+    #[test]
+    fn renders_after_moving() {
+        let mut game = small_game(Point { x: 8, y: 1 });
+        game.update();
+        game.turn(0, Direction::Up);
+        game.update();
+        assert_eq!(
+            game.render_ascii(),
+            "\
+##########
+#.......*#
+#.....O..#
+#....oo..#
+#........#
+##########
+"
+        );
+    }
+
+    #[test]
+    fn renders_a_snake_coiled_in_a_corner() {
+        let mut game = small_game(Point { x: 8, y: 4 });
+        game.snakes[0].body = snake(&[(2, 2), (3, 2), (3, 1), (2, 1), (1, 1), (1, 2), (1, 3), (2, 3)]);
+        assert_eq!(
+            game.render_ascii(),
+            "\
+##########
+#ooo.....#
+#oOo.....#
+#oo......#
+#.......*#
+##########
+"
+        );
+    }
+
+    #[test]
+    fn renders_food_next_to_the_head() {
+        let game = small_game(Point { x: 6, y: 3 });
+        assert_eq!(
+            game.render_ascii(),
+            "\
+##########
+#........#
+#........#
+#..ooO*..#
+#........#
+##########
+"
+        );
+    }
+
+    #[test]
+    fn coop_snakes_share_food_and_die_together() {
+        let mut game = Game::new(12, 8, None, Some(Point { x: 3, y: 3 })).unwrap();
+        game.add_mirror_snake().unwrap();
+        assert_eq!(
+            game.render_ascii(),
+            "\
+############
+#..........#
+#..........#
+#..*.Ooo...#
+#...ooO....#
+#..........#
+#..........#
+############
+"
+        );
+
+        // Player one steering up runs into player two's body, ending the round for both.
+        let mut crash = Game::new(12, 8, None, Some(Point { x: 3, y: 3 })).unwrap();
+        crash.add_mirror_snake().unwrap();
+        crash.turn(0, Direction::Up);
+        crash.update();
+        assert!(crash.game_over);
+
+        // Player two eats the food: the shared score goes up and only that snake grows.
+        game.update();
+        assert_eq!(game.score, 0);
+        game.update();
+        assert_eq!(game.score, 1);
+        assert_eq!(game.snakes[1].body.len(), 4);
+        assert_eq!(game.snakes[0].body.len(), 3);
+        assert!(!game.game_over);
+    }
+
+    #[test]
+    fn versus_snakes_score_apart_and_drop_out_one_at_a_time() {
+        // The same start as co-op (see above).
+        let mut game = Game::new(12, 8, None, Some(Point { x: 3, y: 3 })).unwrap();
+        game.settings.versus = true;
+        game.add_mirror_snake().unwrap();
+
+        // Player two eats the food: it scores, player one doesn't.
+        game.update();
+        game.update();
+        assert_eq!((game.snakes[0].apples, game.snakes[1].apples), (0, 1));
+        // The next food spawned at random; put it out of both snakes' way.
+        game.food = Point { x: 10, y: 1 };
+
+        // Player one runs on into the right wall and is out, but player two, heading
+        // down, plays on. Player one's wreck stays where it stopped.
+        game.turn(1, Direction::Down);
+        for _ in 0..3 {
+            game.update();
+        }
+        assert!(game.snakes[0].crashed && !game.snakes[1].crashed);
+        assert!(!game.game_over);
+        assert_eq!(game.snakes[0].head(), Point { x: 10, y: 4 });
+        assert_eq!(game.snakes[1].head(), Point { x: 3, y: 6 });
+
+        // Then player two hits the bottom wall, and the round is over: one apple to none.
+        game.update();
+        assert!(game.game_over);
+        assert_eq!(game.snakes[0].head(), Point { x: 10, y: 4 });
+        assert_eq!(game.versus_winner(), Some(1));
+        game.snakes[0].apples = 1;
+        assert_eq!(game.versus_winner(), None);
+    }
+
+    #[test]
+    fn switching_wrap_off_gives_a_grace_period() {
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.snakes[0] = Snake::new(Point { x: 8, y: 3 }, Direction::Right, 3);
+        game.toggle_wrap();
+        game.toggle_wrap(); // Back to solid walls, just as the head reaches the right edge.
+        game.update();
+        assert!(!game.game_over);
+        assert_eq!(game.snake().head(), Point { x: 1, y: 3 });
+
+        // Once the grace period runs out, the walls are deadly again.
+        game.update();
+        game.update();
+        game.snakes[0] = Snake::new(Point { x: 8, y: 2 }, Direction::Right, 3);
+        game.update();
+        assert!(game.game_over);
+    }
+
+    // Builds a 20x10 game at `level` whose obstacles come from a fixed seed.
+    fn seeded_level(seed: u64, level: u32) -> Game {
+        let mut game = Game::new(20, 10, None, Some(Point { x: 3, y: 2 })).unwrap();
+        game.rng = StdRng::seed_from_u64(seed);
+        game.level = level;
+        game.generate_level();
+        game
+    }
+
+    // The obstacle cells in a stable order (a HashSet has no order of its own).
+    fn sorted_obstacles(game: &Game) -> Vec<(u16, u16)> {
+        let mut cells: Vec<_> = game.obstacles.iter().map(|p| (p.x, p.y)).collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn corridor_keeps_dense_levels_navigable() {
+        for seed in 0..20 {
+            let mut game = Game::new(20, 10, None, Some(Point { x: 17, y: 7 })).unwrap();
+            game.settings.corridor = true;
+            game.settings.gates = true;
+            game.rng = StdRng::seed_from_u64(seed);
+            let corridor = game.carve_corridor(game.snake().head(), game.food);
+            assert!(corridor.contains(&game.snake().head()) && corridor.contains(&game.food));
+            assert!(corridor.iter().all(|&p| game.in_bounds(p)));
+
+            // Far more walls than a normal level, yet the food can always be reached.
+            game.rng = StdRng::seed_from_u64(seed);
+            game.level = 30;
+            game.generate_level();
+            assert!(corridor.iter().all(|p| !game.obstacles.contains(p)));
+            assert!(game.is_food_reachable());
+            assert!(game.gates.keys().all(|p| game.obstacles.contains(p)));
+        }
+    }
+
+    #[test]
+    fn level_generation_is_reproducible_for_a_seed() {
+        for level in 1..=6 {
+            let game = seeded_level(424, level);
+            assert_eq!(sorted_obstacles(&game), sorted_obstacles(&seeded_level(424, level)));
+
+            assert!(game.obstacles.iter().all(|&p| game.in_bounds(p)));
+            assert!(game.obstacles.iter().all(|p| !game.snake_at(*p)));
+            assert!(!game.obstacles.contains(&game.food));
+            assert!(game.is_food_reachable());
+        }
+    }
+
+    #[test]
+    fn level_generation_matches_known_layout() {
+        // If this fails, the generation algorithm (or the RNG) changed and every
+        // shared seed now produces a different level. Update deliberately, if ever.
+        let game = seeded_level(424, 2);
+        let expected = [
+            (2, 3), (2, 4), (2, 5), (2, 6), (2, 7), (2, 8), (6, 6), (7, 6), (12, 3), (13, 2), (13, 3),
+            (13, 4), (13, 6), (14, 2), (14, 4), (15, 2), (15, 6), (15, 7), (15, 8), (16, 2), (17, 2),
+            (18, 2),
+        ];
+        assert_eq!(sorted_obstacles(&game), expected);
+    }
+
+    #[test]
+    fn power_ups_shrink_boost_and_double_the_points() {
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.settings.power_ups = true;
+        game.snakes[0] = Snake::new(Point { x: 5, y: 3 }, Direction::Right, 5);
+
+        // A shrink takes three segments off the tail at once.
+        game.power_up = Some((Point { x: 6, y: 3 }, PowerUp::Shrink));
+        game.update();
+        assert_eq!(game.power_up, None);
+        assert_eq!(game.snake().body, snake(&[(6, 3), (5, 3), (4, 3)]));
+        // ...but never leaves the snake shorter than three.
+        game.power_up = Some((Point { x: 7, y: 3 }, PowerUp::Shrink));
+        game.update();
+        assert_eq!(game.snake().body.len(), MIN_SHRUNK_LENGTH);
+
+        // A boost makes the ticks shorter until it wears off.
+        game.power_up = Some((Point { x: 8, y: 3 }, PowerUp::Boost));
+        game.update();
+        assert_eq!(game.effective_tick_rate(), TICK_RATE / BOOST_FACTOR);
+        game.update_timers(BOOST_TIME);
+        assert_eq!(game.effective_tick_rate(), TICK_RATE);
+
+        // While double points last, food scores two (and says so).
+        game.settings.toasts = true;
+        game.power_up = Some((Point { x: 8, y: 2 }, PowerUp::DoublePoints));
+        game.turn(0, Direction::Up);
+        game.update();
+        assert_eq!(game.double_points, DOUBLE_POINTS_TIME);
+        game.place_food(Point { x: 8, y: 1 }).unwrap();
+        game.update();
+        assert_eq!(game.score, 2);
+        assert_eq!(game.toasts.last().map(|toast| toast.text.as_str()), Some("+2"));
+        game.update_timers(DOUBLE_POINTS_TIME);
+        assert!(game.double_points.is_zero());
+    }
+
+    #[test]
+    fn hourglass_slow_motion_overrides_acceleration_until_it_runs_out() {
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.settings.accelerate = true;
+        game.hourglass = Some(Point { x: 7, y: 3 });
+        game.update();
+        let fast = game.effective_tick_rate();
+        assert!(fast < TICK_RATE);
+
+        // Picking it up: slow motion wins over the acceleration streak.
+        game.update();
+        assert_eq!(game.hourglass, None);
+        assert_eq!(game.slow_motion, SLOW_MOTION_TIME);
+        assert_eq!(game.effective_tick_rate(), TICK_RATE * SLOW_MOTION_FACTOR);
+
+        // A second hourglass winds the clock back to full rather than adding to it.
+        game.update_timers(Duration::from_secs(3));
+        game.hourglass = Some(Point { x: 8, y: 3 });
+        game.update();
+        assert_eq!(game.slow_motion, SLOW_MOTION_TIME);
+
+        // Once it wears off, the streak (which kept counting) applies again.
+        game.update_timers(SLOW_MOTION_TIME);
+        assert!(game.slow_motion.is_zero());
+        assert!(game.effective_tick_rate() < fast);
+
+        // Without acceleration, slow motion still doubles the base speed.
+        game.settings.accelerate = false;
+        assert_eq!(game.effective_tick_rate(), TICK_RATE);
+        game.slow_motion = SLOW_MOTION_TIME;
+        assert_eq!(game.effective_tick_rate(), TICK_RATE * SLOW_MOTION_FACTOR);
+    }
+
+    #[test]
+    fn one_way_gates_only_let_the_snake_through_along_their_arrow() {
+        let mut game = Game::from_map(
+            "\
+##########
+#........#
+#...S.>*.#
+#......<.#
+#........#
+##########
+",
+        )
+        .unwrap();
+        assert_eq!(game.render_ascii().lines().nth(3), Some("#......<.#"));
+        game.update();
+        game.update(); // Onto the gate, going the way it points.
+        assert!(!game.game_over);
+        assert_eq!(game.snake().head(), Point { x: 6, y: 2 });
+
+        // Against the arrow, it's a wall.
+        game.turn(0, Direction::Down);
+        game.update();
+        game.turn(0, Direction::Right);
+        assert!(!game.game_over);
+        game.update();
+        assert!(game.game_over);
+        assert_eq!(game.crashed_at, Some(Point { x: 7, y: 3 }));
+
+        // Generated gates are obstacles too, and bombs clear them like any other.
+        let mut game = seeded_level(7, 1);
+        game.settings.gates = true;
+        game.level = 3;
+        game.generate_level();
+        assert!(!game.gates.is_empty());
+        assert!(game.gates.keys().all(|p| game.obstacles.contains(p)));
+        let &gate = game.gates.keys().next().unwrap();
+        game.detonate(gate);
+        assert!(!game.gates.contains_key(&gate));
+    }
+
+    #[test]
+    fn reversing_sets_off_away_from_the_old_body() {
+        // A straight snake heading right turns around to head left.
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.reverse_snake(0);
+        assert_eq!(game.snake().body, snake(&[(3, 3), (4, 3), (5, 3)]));
+        assert_eq!(game.snake().direction, Direction::Left);
+
+        // An L-shaped snake faces away from the segment next to its new head.
+        game.snakes[0].body = snake(&[(5, 2), (5, 3), (6, 3)]);
+        game.reverse_snake(0);
+        assert_eq!(game.snake().direction, Direction::Right);
+
+        // Stacked segments (grown but not unfolded yet) are looked past.
+        game.snakes[0].body = snake(&[(4, 1), (4, 2), (4, 3), (4, 3)]);
+        game.reverse_snake(0);
+        assert_eq!(game.snake().direction, Direction::Down);
+
+        // Across a wrapping wall, the direction still follows the body.
+        game.settings.wrap = true;
+        game.snakes[0].body = snake(&[(2, 2), (1, 2), (8, 2)]);
+        game.reverse_snake(0);
+        assert_eq!(game.snake().direction, Direction::Left);
+
+        // With the tail against a solid wall, it turns aside rather than crash.
+        game.settings.wrap = false;
+        game.snakes[0].body = snake(&[(3, 1), (2, 1), (1, 1)]);
+        game.reverse_snake(0);
+        assert_eq!(game.snake().direction, Direction::Down);
+        game.update();
+        assert!(!game.game_over);
+        assert_eq!(game.snake().head(), Point { x: 1, y: 2 });
+    }
+
+    #[test]
+    fn picking_up_a_reverser_turns_the_snake_around() {
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.reverser = Some(Point { x: 6, y: 3 });
+        game.update();
+        assert_eq!(game.reverser, None);
+        assert_eq!(game.snake().body, snake(&[(4, 3), (5, 3), (6, 3)]));
+        assert_eq!(game.snake().direction, Direction::Left);
+        game.update();
+        assert!(!game.game_over);
+        assert_eq!(game.snake().head(), Point { x: 3, y: 3 });
+    }
+
+    #[test]
+    fn bombs_clear_nearby_obstacles_only() {
+        let mut game = small_game(Point { x: 1, y: 1 });
+        for (x, y) in [(8, 1), (8, 4), (7, 3), (3, 1)] {
+            game.obstacles.insert(Point { x, y });
+        }
+        game.bomb = Some(Point { x: 6, y: 3 });
+        game.update();
+
+        // The head reached the bomb: obstacles up to two cells away are gone, the rest stay.
+        assert_eq!(game.snake().head(), Point { x: 6, y: 3 });
+        assert_eq!(game.bomb, None);
+        assert!(!game.game_over);
+        assert_eq!(game.snake().body.len(), 3);
+        assert_eq!(
+            game.render_ascii(),
+            "\
+##########
+#*.X.....#
+#........#
+#...ooO..#
+#........#
+##########
+"
+        );
+    }
+
+    // A 5x4 board (six floor cells) where the snake fills all but the food's cell.
+    fn nearly_full_game(endless: bool) -> Game {
+        let mut game = Game::new(5, 4, None, Some(Point { x: 3, y: 1 })).unwrap();
+        game.settings.endless = endless;
+        game.snakes[0].body = snake(&[(2, 1), (1, 1), (1, 2), (2, 2), (3, 2)]);
+        game.snakes[0].direction = Direction::Right;
+        game
+    }
+
+    #[test]
+    fn filling_the_board_wins_unless_endless() {
+        let mut game = nearly_full_game(false);
+        game.update();
+        assert!(game.game_over && game.game_won);
+
+        let mut game = nearly_full_game(true);
+        game.update();
+        assert!(!game.game_over);
+        assert_eq!(game.score, 1);
+        // No new food: it stays under the snake's head until that cell frees up.
+        assert_eq!(game.food, game.snake().head());
+        assert_eq!(game.free_cell_count(), 0);
+    }
+
+    #[test]
+    fn snake_can_start_anywhere_it_fits() {
+        let game = Game::new(10, 6, Some(Point { x: 3, y: 1 }), Some(Point { x: 8, y: 4 })).unwrap();
+        assert_eq!(game.snake().body, snake(&[(3, 1), (2, 1), (1, 1)]));
+
+        // Too close to the left wall for the body, or on the wall itself.
+        assert!(Game::new(10, 6, Some(Point { x: 2, y: 1 }), None).is_err());
+        assert!(Game::new(10, 6, Some(Point { x: 9, y: 3 }), None).is_err());
+        // Pinned food can't go under the snake.
+        assert!(Game::new(10, 6, Some(Point { x: 5, y: 2 }), Some(Point { x: 4, y: 2 })).is_err());
+    }
+
+    #[test]
+    fn wave_surges_add_walls_without_blocking_the_food() {
+        let mut game = seeded_level(431, 1);
+        game.settings.waves = true;
+        let before = game.obstacles.clone();
+
+        game.update_timers(WAVE_INTERVAL - Duration::from_secs(1));
+        assert_eq!(game.wave, 0);
+        game.update_timers(Duration::from_secs(1));
+        assert_eq!(game.wave, 1);
+        assert_eq!(game.wave_timer, WAVE_INTERVAL);
+
+        // Surges add to the existing walls instead of replacing them.
+        assert!(game.obstacles.is_superset(&before));
+        for _ in 0..10 {
+            game.surge();
+            assert!(game.is_food_reachable());
+            assert!(!game.is_trapped());
+        }
+    }
+
+    #[test]
+    fn bounds_stop_exactly_at_the_walls() {
+        let mut game = small_game(Point { x: 8, y: 1 });
+        assert_eq!(game.interior_bounds(), (1..=8, 1..=4));
+        assert_eq!(game.interior_capacity(), 32);
+        for (x, y, inside) in [(1, 1, true), (8, 4, true), (0, 2, false), (9, 2, false), (3, 5, false)] {
+            assert_eq!(game.in_bounds(Point { x, y }), inside, "{},{}", x, y);
+        }
+
+        // Wrapping lands on the first/last interior cell, never on a wall.
+        game.settings.wrap = true;
+        assert_eq!(game.advance(Point { x: 1, y: 2 }, Direction::Left), Point { x: 8, y: 2 });
+        assert_eq!(game.advance(Point { x: 8, y: 2 }, Direction::Right), Point { x: 1, y: 2 });
+        assert_eq!(game.advance(Point { x: 3, y: 1 }, Direction::Up), Point { x: 3, y: 4 });
+        assert_eq!(game.advance(Point { x: 3, y: 4 }, Direction::Down), Point { x: 3, y: 1 });
+    }
+
+    #[test]
+    fn ghosts_pass_through_obstacles_but_not_walls() {
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.obstacles.insert(Point { x: 6, y: 3 });
+        game.settings.ghost = true;
+        game.update();
+        assert!(!game.game_over);
+        assert_eq!(game.snake().head(), Point { x: 6, y: 3 });
+
+        // Back to normal: the next obstacle is solid again.
+        game.obstacles.insert(Point { x: 7, y: 3 });
+        game.settings.ghost = false;
+        game.update();
+        assert!(game.game_over);
+
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.settings.ghost = true;
+        for _ in 0..3 {
+            game.update();
+        }
+        assert_eq!(game.snake().head(), Point { x: 8, y: 3 });
+        game.update();
+        assert!(game.game_over);
+    }
+
+    #[test]
+    fn far_spawning_puts_food_farther_from_the_head() {
+        // The average distance from the head to newly spawned food, over many spawns.
+        let average_distance = |spawn: SpawnMode| {
+            let mut game = Game::new(20, 10, None, None).unwrap();
+            game.settings.spawn = spawn;
+            game.rng = StdRng::seed_from_u64(436);
+            let head = game.snake().head();
+            let total: u32 = (0..2000)
+                .map(|_| {
+                    game.spawn_food();
+                    u32::from(game.food.x.abs_diff(head.x) + game.food.y.abs_diff(head.y))
+                })
+                .sum();
+            total as f64 / 2000.0
+        };
+        let uniform = average_distance(SpawnMode::Uniform);
+        let far = average_distance(SpawnMode::Far);
+        // On this board uniform spawns average about 6.7 cells away; weighting by
+        // distance pushes that to about 7.9. Over 2000 samples that gap is no fluke.
+        assert!(far > uniform + 0.75, "uniform {:.2}, far {:.2}", uniform, far);
+    }
+
+    const ALL_DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+    #[test]
+    fn turning_never_reverses_in_any_direction() {
+        for heading in ALL_DIRECTIONS {
+            for pressed in ALL_DIRECTIONS {
+                let mut game = Game::new(12, 8, None, Some(Point { x: 1, y: 1 })).unwrap();
+                game.snakes[0] = Snake::new(Point { x: 6, y: 4 }, heading, 3);
+                game.turn(0, pressed);
+
+                // Whatever was pressed, the next move is safe: never back into the neck.
+                game.update();
+                let expected = if pressed == heading.opposite() { heading } else { pressed };
+                assert_eq!(game.snake().direction, expected, "heading {:?}, pressed {:?}", heading, pressed);
+                assert!(!game.game_over, "heading {:?}, pressed {:?}", heading, pressed);
+                assert_eq!(game.snake().head(), Point { x: 6, y: 4 }.step(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn a_one_cell_snake_still_cannot_reverse() {
+        // A single cell has no neck to bite, so reversing would be harmless, but the
+        // rule is about the direction alone: it applies the same at any length.
+        for heading in ALL_DIRECTIONS {
+            let mut game = Game::new(12, 8, None, Some(Point { x: 1, y: 1 })).unwrap();
+            game.snakes[0] = Snake::new(Point { x: 6, y: 4 }, heading, 1);
+            game.turn(0, heading.opposite());
+            game.update();
+            assert_eq!(game.snake().direction, heading);
+            assert!(!game.game_over);
+        }
+    }
+
+    #[test]
+    fn quick_turns_within_one_tick_cannot_whiplash() {
+        // Heading right, the player hits Up then Left before the next tick. Both are
+        // fine on their own terms (Left is not the opposite of Up), so both happen,
+        // one per tick: a tight U-turn instead of reversing into the neck.
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.turn(0, Direction::Up);
+        game.turn(0, Direction::Left);
+        game.update();
+        assert_eq!(game.snake().head(), Point { x: 5, y: 2 });
+        game.update();
+        assert!(!game.game_over);
+        assert_eq!(game.snake().head(), Point { x: 4, y: 2 });
+
+        // Up then Down is a 180 over two ticks: Down is checked against the queued
+        // Up (not the current Right), so it's refused.
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.turn(0, Direction::Up);
+        game.turn(0, Direction::Down);
+        game.update();
+        game.update();
+        assert!(!game.game_over);
+        assert_eq!(game.snake().body, snake(&[(5, 1), (5, 2), (5, 3)]));
+
+        // Up then Left, but a drunken swerve sent the snake Right on the tick in
+        // between: Left is a 180 now, so it's dropped and the snake goes on Right.
+        game.snakes[0].queued_turns = VecDeque::from([Direction::Left]);
+        game.snakes[0].direction = Direction::Right;
+        game.snakes[0].last_direction = Direction::Right;
+        game.update();
+        assert!(!game.game_over);
+        assert_eq!(game.snake().head(), Point { x: 6, y: 1 });
+        assert!(game.snake().queued_turns.is_empty());
+    }
+
+    #[test]
+    fn diagonal_moves_step_both_ways_and_cannot_reverse() {
+        let diagonals = [Direction::UpLeft, Direction::UpRight, Direction::DownLeft, Direction::DownRight];
+        for heading in ALL_DIRECTIONS.into_iter().chain(diagonals) {
+            for pressed in diagonals {
+                let mut game = Game::new(12, 8, None, Some(Point { x: 1, y: 1 })).unwrap();
+                game.settings.diagonal = true;
+                game.snakes[0] = Snake::new(Point { x: 6, y: 4 }, heading, 3);
+                game.turn(0, pressed);
+                game.update();
+                let expected = if pressed == heading.opposite() { heading } else { pressed };
+                assert_eq!(game.snake().head(), Point { x: 6, y: 4 }.step(expected), "heading {:?}, pressed {:?}", heading, pressed);
+                assert!(!game.game_over, "heading {:?}, pressed {:?}", heading, pressed);
+            }
+        }
+        assert_eq!(Point { x: 6, y: 4 }.step(Direction::UpRight), Point { x: 7, y: 3 });
+
+        // Mirrored boards flip each half of a diagonal on its own.
+        let mut game = Game::new(12, 8, None, None).unwrap();
+        game.flip = (true, false);
+        assert_eq!(game.unflip(Direction::UpLeft), Direction::UpRight);
+        game.flip = (false, true);
+        assert_eq!(game.unflip(Direction::UpLeft), Direction::DownLeft);
+    }
+
+    #[test]
+    fn a_flood_of_key_presses_turns_at_most_once_per_tick() {
+        // Like pasting "wasdwasd..." into a terminal without bracketed paste.
+        let mut game = Game::new(20, 12, None, Some(Point { x: 1, y: 1 })).unwrap();
+        for _ in 0..25 {
+            for direction in [Direction::Up, Direction::Left, Direction::Down, Direction::Right] {
+                game.turn(0, direction);
+            }
+        }
+        assert_eq!(game.snake().queued_turns.len(), MAX_QUEUED_TURNS);
+        // The queued turns play out one per tick, and then the snake carries straight on.
+        let mut directions = Vec::new();
+        for _ in 0..4 {
+            game.update();
+            directions.push(game.snake().direction);
+        }
+        assert_eq!(directions, [Direction::Up, Direction::Left, Direction::Left, Direction::Left]);
+        assert!(!game.game_over);
+    }
+
+    #[test]
+    fn levels_go_up_every_n_apples() {
+        for every in [1, 3, 5] {
+            let mut game = Game::new(30, 6, None, None).unwrap();
+            game.settings.level_every = every;
+            let mut levels = Vec::new();
+            for _ in 0..9 {
+                // Put the food right in front of the snake, so every tick eats.
+                game.food = game.next_head(Direction::Right);
+                game.obstacles.clear();
+                (game.bomb, game.hourglass, game.reverser) = (None, None, None);
+                game.update();
+                levels.push(game.level);
+            }
+            assert!(!game.game_over);
+            let expected: Vec<u32> = (1..=9).map(|apples| 1 + (apples / every) as u32).collect();
+            assert_eq!(levels, expected, "--level-every {}", every);
+        }
+    }
+
+    #[test]
+    fn free_cell_cache_matches_a_full_scan() {
+        // The cache must always hold exactly what a scan of the board would find.
+        fn assert_in_step(game: &Game) {
+            let cache = game.free_cells.as_ref().unwrap();
+            let scanned: HashSet<Point> = game
+                .interior_cells()
+                .filter(|&p| !game.snake_at(p) && !game.obstacles.contains(&p))
+                .collect();
+            assert_eq!(cache.iter().collect::<HashSet<_>>(), scanned);
+            assert_eq!(cache.len(), scanned.len());
+        }
+
+        let mut rng = StdRng::seed_from_u64(465);
+        for seed in 0..10 {
+            let mut game = Game::new(16, 10, None, None).unwrap();
+            game.rng = StdRng::seed_from_u64(seed);
+            game.settings.wrap = true;
+            game.settings.split = true;
+            game.settings.growth_delay = seed as u32 % 3;
+            game.generate_level();
+            game.enable_free_cell_cache();
+            assert_in_step(&game);
+            for _ in 0..300 {
+                match rng.gen_range(0..20) {
+                    0 => game.level_up(),
+                    1 => game.surge(),
+                    2 => {
+                        let center = game.random_interior_cell();
+                        game.detonate(center);
+                    }
+                    3 => game.settings.ghost = !game.settings.ghost,
+                    4..=9 => game.turn(0, Direction::ALL[rng.gen_range(0..4)]),
+                    _ => game.update(),
+                }
+                assert_in_step(&game);
+                if game.game_over {
+                    break;
+                }
+                // Food never lands on a taken cell.
+                assert!(!game.snake_at(game.food) && !game.obstacles.contains(&game.food));
+            }
+        }
+    }
+
+    #[test]
+    fn split_mode_cuts_off_the_bitten_part() {
+        // Head at 3,2, curled round so its own body is below it.
+        let curl = |direction| {
+            let mut curled = Snake::new(Point { x: 3, y: 2 }, direction, 1);
+            curled.body = snake(&[(3, 2), (4, 2), (5, 2), (5, 3), (4, 3), (3, 3), (2, 3), (1, 3)]);
+            curled
+        };
+
+        // Moving down bites index 5 (3,3): the 5 segments in front of it stay, then
+        // the head moves on and the tail follows, leaving 5.
+        let mut game = small_game(Point { x: 8, y: 1 });
+        game.settings.split = true;
+        game.snakes[0] = curl(Direction::Down);
+        game.update();
+        assert!(!game.game_over);
+        assert_eq!(game.snake().body, snake(&[(3, 3), (3, 2), (4, 2), (5, 2), (5, 3)]));
+
+        // A bite that would leave the snake too short is still fatal: going
+        // diagonally from 4,2 onto index 2 would leave just 2 segments.
+        let mut game = small_game(Point { x: 8, y: 1 });
+        game.settings.split = true;
+        game.settings.diagonal = true;
+        game.snakes[0].body = snake(&[(4, 2), (4, 3), (5, 3), (5, 2), (6, 2)]);
+        game.snakes[0].direction = Direction::DownRight;
+        game.update();
+        assert!(game.game_over);
+        assert_eq!(game.snake().body.len(), 5);
+
+        // Without split mode any bite ends the game.
+        let mut game = small_game(Point { x: 8, y: 1 });
+        game.snakes[0] = curl(Direction::Down);
+        game.update();
+        assert!(game.game_over);
+    }
+
+    #[test]
+    fn tiny_boards_never_panic_on_random_placement() {
+        assert!(Game::new(4, 3, None, None).is_err());
+        assert!(Game::new(5, 2, None, None).is_err());
+
+        // The smallest boards a map can make have no room for random walls at all.
+        for (width, height) in [(5, 3), (5, 4), (6, 5)] {
+            let mut game = Game::new(width, height, None, None).unwrap();
+            game.settings.gates = true;
+            for _ in 0..5 {
+                game.level_up();
+                game.surge();
+            }
+            assert!(game.obstacles.iter().all(|&p| game.in_bounds(p)));
+        }
+
+        // A thick border leaves a 3x1 strip: still no panic.
+        let mut game = Game::new(9, 5, Some(Point { x: 4, y: 2 }), None).unwrap();
+        game.set_border(2).unwrap();
+        game.level_up();
+        assert!(game.obstacles.is_empty());
+
+        // A board with no free cell left at all: food can't spawn, so the game is won.
+        let mut game = Game::new(5, 3, None, None).unwrap();
+        game.obstacles.extend([Point { x: 1, y: 1 }, Point { x: 3, y: 1 }]);
+        game.spawn_food();
+        assert!(game.game_won);
+    }
+
+    #[test]
+    fn the_speedrun_timer_runs_on_the_game_clock() {
+        let mut game = small_game(Point { x: 8, y: 1 });
+        let clock = mock_clock(&mut game);
+        assert_eq!(game.elapsed(), Duration::ZERO);
+        clock.advance(Duration::from_secs(90));
+        assert_eq!(format_time(game.elapsed()), format_time(Duration::from_secs(90)));
+
+        // Messages fade from the log after `MESSAGE_TIME`, and the timer stops at the end.
+        game.log("Hello");
+        clock.advance(MESSAGE_TIME);
+        game.update_timers(Duration::ZERO);
+        assert!(game.messages.is_empty());
+        game.end_game();
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(game.elapsed(), Duration::from_secs(90) + MESSAGE_TIME);
+    }
+
+    #[test]
+    fn food_zones_share_the_food_by_weight() {
+        let text = "zone 1,1 2,1 3\nzone 9,3 10,3 1\n############\n#..........#\n#...S......#\n#..........#\n############\n";
+        let mut game = Game::from_map(text).unwrap();
+        game.rng = StdRng::seed_from_u64(480);
+        let mut in_first = 0;
+        for _ in 0..4000 {
+            game.spawn_food();
+            let food = game.food;
+            assert!(food.y == 1 && food.x <= 2 || food.y == 3 && food.x >= 9);
+            if food.y == 1 {
+                in_first += 1;
+            }
+        }
+        // Three times the weight: about three quarters of the food.
+        assert!((2800..3200).contains(&in_first), "{} of 4000", in_first);
+
+        // With every zone full, food goes anywhere free.
+        game.obstacles.extend([(1, 1), (2, 1), (9, 3), (10, 3)].map(|(x, y)| Point { x, y }));
+        game.spawn_food();
+        assert!(!game.obstacles.contains(&game.food) && !game.snake_at(game.food));
+    }
+
+    #[test]
+    fn face_food_heads_for_the_food_along_the_longer_axis() {
+        // Food up and to the left, further up than left: head up, body trailing down.
+        let mut game = Game::new(12, 10, Some(Point { x: 6, y: 5 }), Some(Point { x: 5, y: 1 })).unwrap();
+        game.face_food();
+        assert_eq!(game.snake().direction, Direction::Up);
+        assert_eq!(game.snake().body, [Point { x: 6, y: 5 }, Point { x: 6, y: 6 }, Point { x: 6, y: 7 }]);
+
+        // Food to the left, but a wall right behind the head: the body can't trail
+        // right, so the snake takes the other axis instead.
+        let mut game = Game::new(12, 10, Some(Point { x: 9, y: 5 }), Some(Point { x: 2, y: 3 })).unwrap();
+        game.snakes[0] = Snake::new(Point { x: 9, y: 5 }, Direction::Down, 3);
+        game.obstacles.insert(Point { x: 10, y: 5 });
+        game.face_food();
+        assert_eq!(game.snake().direction, Direction::Up);
+        assert!(game.snake().body.iter().all(|&p| game.in_bounds(p)));
+
+        // Nowhere it fits: it keeps going the way it was.
+        game.obstacles.insert(Point { x: 9, y: 6 });
+        game.face_food();
+        assert_eq!(game.snake().direction, Direction::Up);
+        game.snakes[0] = Snake::new(Point { x: 9, y: 5 }, Direction::Right, 3);
+        game.obstacles.insert(Point { x: 8, y: 5 });
+        game.face_food();
+        assert_eq!(game.snake().direction, Direction::Right);
+    }
+
+    #[test]
+    fn speed_curves_only_ever_get_faster_within_bounds() {
+        for curve in [SpeedCurve::Flat, SpeedCurve::Linear, SpeedCurve::Exponential, SpeedCurve::Logarithmic] {
+            let ticks: Vec<Duration> = (1..=100).map(|level| curve.tick_rate(TICK_RATE, level)).collect();
+            assert_eq!(ticks[0], TICK_RATE, "{:?}", curve);
+            // `windows(2)` gives every pair of neighbors: each level is at least as fast as the last.
+            assert!(ticks.windows(2).all(|pair| pair[1] <= pair[0]), "{:?}", curve);
+            assert!(ticks.iter().all(|&tick| tick >= MIN_TICK_RATE), "{:?}", curve);
+            if curve != SpeedCurve::Flat {
+                assert!(ticks[1] < ticks[0], "{:?}", curve);
+                assert_eq!(ticks[99], MIN_TICK_RATE, "{:?}", curve);
+            }
+        }
+        // The speed table lists every level's tick.
+        let table = speed_table(SpeedCurve::Linear, TICK_RATE);
+        assert!(table.contains("    2   142ms"));
+        // `--speed` moves the whole table.
+        let table = speed_table(SpeedCurve::Linear, Duration::from_millis(250));
+        assert!(table.contains("    1   250ms") && table.contains("    2   242ms"));
+
+        // Acceleration speeds up from the level's base speed, but never past the floor.
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.settings.speed_curve = SpeedCurve::Exponential;
+        game.settings.accelerate = true;
+        game.level = 9;
+        game.snakes[0].straight_ticks = MAX_ACCEL_TICKS;
+        assert_eq!(game.effective_tick_rate(), MIN_TICK_RATE);
+    }
+
+    #[test]
+    fn growth_delay_releases_segments_on_later_ticks() {
+        let mut game = small_game(Point { x: 6, y: 3 });
+        game.settings.growth_delay = 2;
+        let length = |game: &Game| game.snake().body.len();
+
+        game.update(); // Eats: nothing grows yet, one segment is owed.
+        assert_eq!((game.score, length(&game), game.snake().pending_growth), (1, 3, 1));
+        game.food = Point { x: 7, y: 3 };
+        game.update(); // Eats again one tick later: now two are owed.
+        assert_eq!((length(&game), game.snake().pending_growth), (3, 2));
+        game.food = Point { x: 1, y: 4 }; // Out of the way.
+        game.turn(0, Direction::Up);
+        game.update(); // Two ticks after the first meal: its segment appears.
+        assert_eq!((length(&game), game.snake().pending_growth), (4, 1));
+        game.turn(0, Direction::Left);
+        game.update();
+        assert_eq!(length(&game), 4);
+        game.update(); // And two ticks after that, the second.
+        assert_eq!((length(&game), game.snake().pending_growth), (5, 0));
+        game.update();
+        assert!(!game.game_over);
+        assert_eq!(length(&game), 5);
+    }
+
+    #[test]
+    fn a_thick_border_shrinks_the_play_area() {
+        let mut game = Game::new(12, 8, None, Some(Point { x: 1, y: 1 })).unwrap();
+        game.obstacles.insert(Point { x: 10, y: 6 });
+        game.set_border(2).unwrap();
+        assert_eq!(game.interior_bounds(), (2..=9, 2..=5));
+        // The food was inside the new wall, so it moved; the obstacle is gone.
+        assert!(game.in_bounds(game.food));
+        assert!(game.obstacles.is_empty());
+        assert_eq!(game.interior_capacity(), 8 * 4);
+        assert_eq!(game.render_ascii().lines().nth(1), Some("############"));
+
+        // The inner edge of the wall is what kills.
+        game.food = Point { x: 2, y: 2 };
+        game.snakes[0] = Snake::new(Point { x: 9, y: 4 }, Direction::Right, 3);
+        game.update();
+        assert!(game.game_over);
+        assert_eq!(game.crashed_at, Some(Point { x: 10, y: 4 }));
+
+        // Too thick to leave room, or to fit the snake.
+        let mut game = Game::new(12, 8, None, None).unwrap();
+        assert!(game.set_border(0).is_err());
+        assert!(game.set_border(4).is_err());
+        game.snakes[0] = Snake::new(Point { x: 9, y: 4 }, Direction::Right, 3);
+        assert!(game.set_border(3).is_err());
+        assert_eq!(game.border, 1);
+    }
+
+    #[test]
+    fn regions_split_where_obstacles_cut_the_floor() {
+        let mut game = small_game(Point { x: 1, y: 1 });
+        let regions = game.regions();
+        assert_eq!(regions.len(), game.interior_capacity());
+        assert!(regions.values().all(|&region| region == 0));
+
+        // A wall down column 3 cuts off the left side; a boxed-in cell is a region of its own.
+        for y in 1..=4 {
+            game.obstacles.insert(Point { x: 3, y });
+        }
+        for (x, y) in [(7, 1), (8, 2)] {
+            game.obstacles.insert(Point { x, y });
+        }
+        let regions = game.regions();
+        let region = |x, y| regions[&Point { x, y }];
+        assert_eq!(region(1, 1), 0);
+        assert_eq!(region(2, 4), 0);
+        assert_eq!(region(4, 1), 1);
+        assert_eq!(region(8, 1), 2);
+        assert_eq!(regions.values().max(), Some(&2));
+        assert!(!regions.contains_key(&Point { x: 3, y: 2 }));
+    }
+
+    #[test]
+    fn random_start_lengths_stay_in_range_and_fit() {
+        let mut lengths = HashSet::new();
+        for seed in 0..200 {
+            let mut game = Game::with_rng(10, 6, None, None, StdRng::seed_from_u64(seed)).unwrap();
+            // The head starts at x 5 heading right, so at most 5 segments fit behind it.
+            game.randomize_start_length(2, 8);
+            let body = &game.snake().body;
+            assert!((2..=5).contains(&body.len()));
+            assert!(body.iter().all(|&p| game.in_bounds(p)));
+            assert!(!game.snake_at(game.food));
+            lengths.insert(body.len());
+        }
+        assert_eq!(lengths.len(), 4);
+
+        // The same seed gives the same snake (and the same food, which can
+        // be in the way).
+        let grow = |seed| {
+            let mut game = Game::with_rng(40, 20, None, None, StdRng::seed_from_u64(seed)).unwrap();
+            game.randomize_start_length(3, 12);
+            (game.snake().body.clone(), game.food)
+        };
+        for seed in 0..20 {
+            assert_eq!(grow(seed), grow(seed));
+        }
+
+        // A huge `max` is cut down to the board without trying every length on the way.
+        let mut game = Game::new(40, 20, None, None).unwrap();
+        game.randomize_start_length(1, usize::MAX);
+        assert!(game.snake().body.len() <= 38);
+    }
+
+    #[test]
+    fn shields_absorb_a_crash_and_recharge() {
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.snakes[0].body = snake(&[(8, 2), (7, 2), (6, 2)]);
+        game.settings.shields = 1;
+        game.shields = 1;
+
+        // Heading into the right wall: the shield turns the snake instead.
+        game.update();
+        assert!(!game.is_over());
+        assert_eq!(game.shields, 0);
+        assert_eq!(game.snake().head(), Point { x: 8, y: 2 });
+        assert!(matches!(game.snake().direction, Direction::Up | Direction::Down));
+
+        // It comes back in time...
+        game.update_timers(SHIELD_REGEN_TIME);
+        assert_eq!(game.shields, 1);
+        game.update_timers(SHIELD_REGEN_TIME);
+        assert_eq!(game.shields, 1);
+
+        // ...but in a dead end there's nowhere safe to turn, so the shield can't help.
+        game.snakes[0].body = snake(&[(8, 1), (8, 2), (8, 3)]);
+        game.snakes[0].direction = Direction::Up;
+        game.obstacles.insert(Point { x: 7, y: 1 });
+        game.update();
+        assert!(game.is_over());
+        assert_eq!(game.shields, 1);
+    }
+
+    #[test]
+    fn puzzles_are_won_in_order_or_lost_on_moves() {
+        // Straight right: food 1 is two moves away, food 2 one more.
+        let map = |moves: u32| format!("moves {}\n##########\n#..S.12..#\n#........#\n##########\n", moves);
+        let mut game = Game::from_map(&map(4)).unwrap();
+        game.update();
+        game.update();
+        assert_eq!(game.score(), 1);
+        assert_eq!(game.food, Point { x: 6, y: 1 });
+        assert!(game.bomb.is_none() && game.level() == 1);
+        game.update();
+        assert!(game.is_over() && game.game_won);
+        assert_eq!(game.puzzle.as_ref().map(|p| p.moves_left), Some(1));
+
+        // One move short of the second food.
+        let mut game = Game::from_map(&map(2)).unwrap();
+        game.update();
+        assert!(!game.is_over());
+        game.update();
+        assert!(game.is_over() && !game.game_won);
+        assert_eq!(game.score(), 1);
+    }
+
+    #[test]
+    fn scaled_score_rewards_hard_rules_and_voids_assists() {
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.score = 10;
+        assert_eq!(game.score_multiplier(), 1.0);
+        // Off by default: the leaderboard gets the raw score whatever the rules.
+        game.settings.drunk = true;
+        assert_eq!(game.leaderboard_score(), 10);
+
+        game.settings.scaled_score = true;
+        game.settings.speed_curve = SpeedCurve::Exponential;
+        assert_eq!(game.leaderboard_score(), 20); // 10 × 1.5 × 1.3 = 19.5
+        game.settings.wrap = true;
+        assert_eq!(game.leaderboard_score(), 16); // × 0.8
+
+        // One tick in ghost mode voids the run, even after switching it off again.
+        game.settings.ghost = true;
+        game.update();
+        game.settings.ghost = false;
+        assert_eq!(game.leaderboard_score(), 0);
+        assert_eq!(game.score(), 10);
+    }
+
+    #[test]
+    fn queue_indicator_shows_pending_turns_as_drawn() {
+        let mut game = small_game(Point { x: 1, y: 1 });
+        assert_eq!(game.queue_indicator(), None);
+        game.snakes[0].turn(Direction::Up);
+        game.snakes[0].turn(Direction::Left);
+        assert_eq!(game.queue_indicator().as_deref(), Some("Next: ↑← "));
+        // On a board flipped left-right the snake really goes right; on screen that's left.
+        game.flip = (true, false);
+        game.snakes[0].queued_turns = VecDeque::from([Direction::Up, Direction::Right]);
+        assert_eq!(game.queue_indicator().as_deref(), Some("Next: ↑← "));
+        // The first turn is made on the next tick, leaving the second.
+        game.flip = (false, false);
+        game.update();
+        assert_eq!(game.queue_indicator().as_deref(), Some("Next: → "));
+    }
+
+    #[test]
+    fn renders_obstacles() {
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.obstacles = [(7, 1), (7, 2), (7, 3), (2, 4), (3, 4)]
+            .iter()
+            .map(|&(x, y)| Point { x, y })
+            .collect();
+        assert_eq!(
+            game.render_ascii(),
+            "\
+##########
+#*.....X.#
+#......X.#
+#..ooO.X.#
+#.XX.....#
+##########
+"
+        );
+    }
+}
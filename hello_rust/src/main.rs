@@ -1,435 +1,795 @@
+// The terminal front-end: reads the command line, sets up the terminal, and runs
+// the game (which lives in the library, `lib.rs`) on it.
+//
+// `mod` tells Rust to include another source file (here `src/cli.rs`) as a module.
+mod asciicast;
+mod bench;
+mod bot;
+mod cli;
+mod compare;
+mod config;
+mod crt;
+mod game_loop;
+mod heatmap;
+mod keys;
+mod menu;
+mod net;
+mod perf;
+mod replay;
+mod render;
+mod scores;
+mod screen;
+mod script;
+mod signals;
+mod spectate;
+mod theme;
+
 // This line imports modules from the `crossterm` crate, which helps us manipulate the terminal.
 // Crates are like libraries or packages in other languages.
 use crossterm::{
     cursor::{Hide, MoveTo, Show},
-    event::{self, Event, KeyCode, KeyModifiers},
-    style::{Color, Print, ResetColor, SetForegroundColor},
+    event::{DisableBracketedPaste, EnableBracketedPaste},
+    style::Print,
     terminal::{
-        disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen,
-        LeaveAlternateScreen,
+        self, disable_raw_mode, enable_raw_mode, EnterAlternateScreen,
+        LeaveAlternateScreen, SetTitle,
     },
-    ExecutableCommand, QueueableCommand,
-};
-// We need the `Rng` trait to generate random numbers for the food position.
-use rand::Rng;
-// Standard library imports for collections, input/output, and time management.
-use std::{
-    // HashSet is a collection that stores unique items and allows for ultra-fast lookups (checking if an item exists).
-    // VecDeque is a "double-ended queue" - great for adding/removing from both ends (like a snake!).
-    collections::{HashSet, VecDeque},
-    io::{self, Write},
-    time::{Duration, Instant},
+    ExecutableCommand,
 };
+// The game itself, from our own library crate (see `lib.rs`).
+use hello_rust::*;
+use rand::{SeedableRng, rngs::StdRng};
+use std::io::{self, IsTerminal, Write};
 
-// Constants determine the size of our game board.
-// `u16` means an unsigned 16-bit integer (can't be negative).
-const WIDTH: u16 = 40;
-const HEIGHT: u16 = 20;
-
-// Structs define custom data types to group related data.
-// #[derive(...)] asks the compiler to automatically implement basic behaviors for us.
-// - Clone/Copy: Allows us to duplicate this Point easily.
-// - PartialEq/Eq: Allows us to compare two Points with `==`.
-// - Hash: Allows this struct to be used as a key in a HashMap or stored in a HashSet. 
-//   This is crucial for our obstacle checking, as hashing is what makes HashSet lookups fast!
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
-struct Point {
-    x: u16,
-    y: u16,
+// `--shrink-to-fit`: the board size to use in a terminal `size` big (columns, rows).
+// The `board` asked for, cut down to fit with a row to spare for the status line
+// below it, but never smaller than the smallest board there is (even if that won't fit).
+fn fit_board(board: (u16, u16), size: (u16, u16)) -> (u16, u16) {
+    let (columns, rows) = size;
+    let width = board.0.min(columns).max(map::MIN_WIDTH as u16);
+    let height = board.1.min(rows.saturating_sub(1)).max(map::MIN_HEIGHT as u16);
+    (width, height)
 }
 
-// Enums allow us to define a type that can be one of several variants.
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
+// Builds the starting board from the command-line options:
+// either loaded from a map file or the classic empty board, with any fixed first food.
+fn build_game(options: &cli::Options) -> Result<Game, String> {
+    // `--seed`: the same seed gives the same food, obstacles and pickups every time.
+    let rng = || match options.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let mut game = match &options.map {
+        // `--resume` picks up the saved game instead of starting a new one.
+        // (The save doesn't keep the random numbers' state, so with `--seed` the
+        // rest of the game is seeded afresh from where it was saved.)
+        _ if options.resume => {
+            let path = options.save_path().ok_or("no save file location; pass --save-file")?;
+            let mut game = save::SaveGame::load(&path)?
+                .into_game()
+                .map_err(|err| format!("could not resume {}: {}", path.display(), err))?;
+            game.rng = rng();
+            game
+        }
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .map_err(|err| format!("could not read map {}: {}", path.display(), err))?;
+            let mut game = Game::from_map_with_rng(&text, rng())
+                .map_err(|err| format!("could not load map {}: {}", path.display(), err))?;
+            if let Some(start) = options.start {
+                game.place_snake(start)?;
+            }
+            if let Some(food) = options.food {
+                game.place_food(food)?;
+            }
+            game
+        }
+        // `--bench-board`: a fixed board for profiling (see `bench.rs`).
+        None if let Some(board) = options.bench_board => board.build()?,
+        None => {
+            // `--shrink-to-fit`: a smaller board if the terminal can't show the full one.
+            let board = (options.width, options.height);
+            let (width, height) = match terminal::size() {
+                Ok(size) if options.settings.shrink_to_fit => fit_board(board, size),
+                _ => board,
+            };
+            Game::with_rng(width, height, options.start, options.food, rng())?
+        }
+    };
+    game.settings = options.settings.clone();
+    // Shields start fully charged.
+    game.shields = game.settings.shields;
+    game.theme = options.theme;
+    if options.border != 1 {
+        game.set_border(options.border)?;
+        // The food was checked against the old, thinner wall; check it again.
+        if let Some(food) = options.food {
+            game.place_food(food)?;
+        }
+    }
+    // A resumed game keeps its snake.
+    if let Some((min, max)) = options.random_start_length
+        && !options.resume
+    {
+        game.randomize_start_length(min, max);
+    }
+    // A resumed game carries on the way it was going.
+    if options.face_food && !options.resume {
+        game.face_food();
+    }
+    if (game.settings.coop || game.settings.versus) && game.snakes.len() == 1 {
+        game.add_mirror_snake()?;
+    }
+    if options.free_cell_cache {
+        game.enable_free_cell_cache();
+    }
+    // With `--seed`, the game is played from the seed afresh once the board is
+    // set up. That's where a replay's random numbers start from too (see
+    // `replay.rs`), so recording a seeded game with `--record` doesn't change it.
+    if let Some(seed) = options.seed {
+        game.rng = StdRng::seed_from_u64(seed);
+    }
+    Ok(game)
 }
 
-// We can add methods to our types using `impl`.
-impl Direction {
-    // We could add helper methods here (like obtaining the opposite direction),
-    // but for this simple tutorial, we handle direction changes directly in the input loop.
+// The terminal game's `Session` (see `game_loop.rs`): retries are built from the
+// command-line options, and finished runs go into the files in the data folder.
+struct Saved<'a> {
+    options: &'a cli::Options,
 }
 
-// The core Game state struct.
-struct Game {
-    snake: VecDeque<Point>,
-    food: Point,
-    // We use a HashSet to store obstacle positions. 
-    // Why? Because checking `obstacles.contains(&point)` is O(1) (instant), 
-    // whereas searching through a Vec would be O(N) (slower as obstacles increase).
-    obstacles: HashSet<Point>,
-    direction: Direction,
-    score: usize, // `usize` is the standard size for indexing collections (usually 64-bit on modern PCs).
-    level: u32,   // Current game level
-    game_over: bool,
-    width: u16,
-    height: u16,
-}
+impl game_loop::Session for Saved<'_> {
+    fn new_game(&mut self) -> Result<Game, String> {
+        build_game(self.options)
+    }
 
-impl Game {
-    // Constructor method to create a new Game instance.
-    fn new(width: u16, height: u16) -> Self {
-        // Start the snake in the middle of the screen.
-        let start_x = width / 2;
-        let start_y = height / 2;
-
-        // Create the initial snake body parts.
-        // `mut` means this variable is mutable (can be changed).
-        let mut snake = VecDeque::new();
-        snake.push_back(Point { x: start_x, y: start_y });
-        snake.push_back(Point {
-            x: start_x - 1,
-            y: start_y,
-        });
-        snake.push_back(Point {
-            x: start_x - 2,
-            y: start_y,
-        });
-
-        let mut game = Game {
-            snake,
-            food: Point { x: 0, y: 0 }, // Placeholder, we'll randomize it immediately below.
-            obstacles: HashSet::new(),  // Start with no obstacles
-            direction: Direction::Right,
-            score: 0,
-            level: 1,
-            game_over: false,
-            width,
-            height,
-        };
-        
-        game.spawn_food();
-        game
-    }
-
-    // Function to place food in a random location not occupied by the snake OR obstacles.
-    // `&mut self` means this method needs to modify the Game state.
-    fn spawn_food(&mut self) {
-        let mut rng = rand::thread_rng(); // Get a random number generator thread.
-        loop {
-            // Generate random x and y coordinates within the walls.
-            let x = rng.gen_range(1..self.width - 1);
-            let y = rng.gen_range(1..self.height - 1);
-            let point = Point { x, y };
-            
-            // If the generated point is NOT inside the snake body AND NOT inside an obstacle, we found a valid spot!
-            if !self.snake.contains(&point) && !self.obstacles.contains(&point) {
-                self.food = point;
-                break; // Exit the loop.
-            }
+    // We're in raw mode with the board on screen, so there's no good place to report
+    // a failed save; the run simply doesn't get recorded.
+    // Neither does a versus round: its score is two players' apples added together,
+    // which isn't one player's run to put on the board. Nor does a run that used
+    // ghost mode or a cheat (see `Game::assisted`), which wasn't won fair and square.
+    fn record_score(&mut self, game: &Game) -> Option<scores::Entry> {
+        if game.settings.versus || game.assisted || game.settings.ghost {
+            return None;
         }
+        let path = scores::Leaderboard::default_path()?;
+        let mut board = scores::Leaderboard::load(&path);
+        let time_ms = game.elapsed().as_millis() as u64;
+        let entry = scores::Entry::now(game.leaderboard_score(), game.level(), time_ms);
+        board.record(entry.clone())?;
+        board.save(&path).ok()?;
+        Some(entry)
     }
 
-    // Generates a new set of random obstacles for the current level.
-    fn generate_level(&mut self) {
-        let mut rng = rand::thread_rng();
-        self.obstacles.clear(); // Remove old obstacles
-        
-        // As the level increases, we add more obstacles to make it harder!
-        let num_obstacles = self.level * 3 + 5;
-        
-        for _ in 0..num_obstacles {
-            // Randomly choose vertical or horizontal wall segment
-            let is_horizontal = rng.gen_bool(0.5);
-            let length = rng.gen_range(3..8); // Random length for the wall
-            
-            let start_x = rng.gen_range(2..self.width - 2);
-            let start_y = rng.gen_range(2..self.height - 2);
-            
-            for i in 0..length {
-                let p = if is_horizontal {
-                    Point { x: start_x + i, y: start_y }
-                } else {
-                    Point { x: start_x, y: start_y + i }
-                };
-                
-                // IMPORTANT Checks:
-                // 1. Keep obstacles within bounds.
-                // 2. Don't spawn on top of the snake.
-                // 3. Don't spawn on top of the food.
-                // 4. Don't spawn right in front of the snake's face (unfair!).
-                if p.x > 0 && p.x < self.width - 1 
-                   && p.y > 0 && p.y < self.height - 1
-                   && !self.snake.contains(&p)
-                   && p != self.food 
-                   && self.snake.front().map_or(true, |head| (head.x as i32 - p.x as i32).abs() + (head.y as i32 - p.y as i32).abs() > 3)
-                {
-                    self.obstacles.insert(p);
-                }
-            }
+    fn name_score(&mut self, entry: &scores::Entry, name: &str) -> String {
+        let Some(path) = scores::Leaderboard::default_path() else {
+            return "Nowhere to save the name".to_string();
+        };
+        let mut board = scores::Leaderboard::load(&path);
+        if !board.set_name(entry, name) {
+            return "The run is no longer on the leaderboard".to_string();
+        }
+        match board.save(&path) {
+            Ok(()) => format!("Saved as {}", name),
+            Err(err) => format!("Could not save the name: {}", err),
         }
     }
 
-    // Update the game state (move snake, check collisions).
-    fn update(&mut self) {
-        if self.game_over {
+    fn record_death(&mut self, game: &Game) {
+        let (Some(cell), Some(path)) = (game.crashed_at, heatmap::Heatmap::default_path()) else {
             return;
+        };
+        let mut heatmap = heatmap::Heatmap::load(&path);
+        heatmap.record(cell);
+        let _ = heatmap.save(&path);
+    }
+
+    fn keep_best_run(&mut self, run: &replay::Replay) -> io::Result<()> {
+        match replay::best_path() {
+            Some(path) => run.save(&path),
+            None => Ok(()),
         }
+    }
+}
 
-        // Calculate the new head position based on current direction.
-        // `unwrap()` is used because we know the snake is never empty.
-        let head = self.snake.front().unwrap();
-        
-        // `match` is like a powerful switch statement.
-        let new_head = match self.direction {
-            Direction::Up => Point {
-                x: head.x,
-                // wrapping_sub handles subtraction that might go below 0.
-                y: head.y.wrapping_sub(1), 
-            },
-            Direction::Down => Point {
-                x: head.x,
-                y: head.y + 1,
-            },
-            Direction::Left => Point {
-                x: head.x.wrapping_sub(1),
-                y: head.y,
-            },
-            Direction::Right => Point {
-                x: head.x + 1,
-                y: head.y,
-            },
-        };
+// Struct to handle cleanup when the program exits.
+// Rust utilizes RAII (Resource Acquisition Is Initialization).
+// When `cleanup` goes out of scope, `drop()` is called automatically.
+//
+// Each field records one setup step that actually succeeded, and only those
+// steps are undone. So if setup fails halfway (say raw mode works but the
+// alternate screen doesn't) and `main` bails out with `?`, the guard still
+// restores exactly what was changed, and nothing else.
+#[derive(Default)]
+struct CleanUp {
+    // Whether raw mode is on (and so must be switched off).
+    raw_mode: bool,
+    // Whether we switched to the alternate screen (and so must switch back).
+    alt_screen: bool,
+    // Whether we hid the cursor (and so must show it again).
+    cursor_hidden: bool,
+    // Whether we changed the window title (and so must clear it again).
+    title: bool,
+    // The board's height, if we drew inline in the normal screen (and so must move
+    // the cursor below the board).
+    inline: Option<u16>,
+    // Whether we turned on bracketed paste (and so must turn it off).
+    bracketed_paste: bool,
+}
 
-        // 1. Wall collision checks (Outer borders).
-        if new_head.x == 0
-            || new_head.x >= self.width - 1
-            || new_head.y == 0
-            || new_head.y >= self.height - 1
-        {
-            self.game_over = true;
-            return;
+impl CleanUp {
+    // Undoes every recorded step, writing the terminal commands to `out`.
+    // Each flag is cleared as its step is undone, so calling this twice is harmless.
+    fn restore(&mut self, out: &mut impl Write) {
+        // `std::mem::take` reads a value and leaves the default (`false`) in its place.
+        if std::mem::take(&mut self.raw_mode) {
+            let _ = disable_raw_mode();
+        }
+        if std::mem::take(&mut self.bracketed_paste) {
+            let _ = out.execute(DisableBracketedPaste);
+        }
+        if std::mem::take(&mut self.cursor_hidden) {
+            let _ = out.execute(Show);
         }
+        if std::mem::take(&mut self.title) {
+            // Terminals can't tell us the old title, so we set an empty one; most
+            // terminals then fall back to their default (and shells usually set their own).
+            let _ = out.execute(SetTitle(""));
+        }
+        if std::mem::take(&mut self.alt_screen) {
+            let _ = out.execute(LeaveAlternateScreen);
+        }
+        if let Some(height) = self.inline.take() {
+            // Drawing inline: park the cursor below the board so the shell prompt
+            // appears underneath it and the final frame stays in the scrollback.
+            // (One extra row for the status line drawn below the board.)
+            let _ = out.execute(MoveTo(0, height + 1));
+            let _ = out.execute(Print("\r\n"));
+        }
+    }
+}
+
+impl Drop for CleanUp {
+    fn drop(&mut self) {
+        // Restore terminal to normal mode (show cursor, disable raw input, ...).
+        self.restore(&mut io::stdout());
+    }
+}
 
-        // 2. Self collision check (biting own tail).
-        if self.snake.contains(&new_head) {
-             self.game_over = true;
-            return;   
+// `--watch-best` (or Best Run on the menu): where the best run's replay is, to
+// play back like `--replay`.
+// Exits with a message if no run has set a high score with `--record` yet.
+fn best_run() -> std::path::PathBuf {
+    match replay::best_path().filter(|path| path.exists()) {
+        Some(path) => path,
+        None => {
+            eprintln!("No best run recorded yet: play with --record FILE and set a high score.");
+            std::process::exit(1);
         }
+    }
+}
 
-        // 3. Obstacle collision check (hitting a generated wall).
-        if self.obstacles.contains(&new_head) {
-            self.game_over = true;
-            return;
+// `--join`: plays player two in a game hosted elsewhere with `--host`, steering
+// with this player's own `keys`.
+fn join_game(addr: &str, keys: &keys::KeyBindings) -> io::Result<()> {
+    let stream = match std::net::TcpStream::connect(addr) {
+        Ok(stream) => stream,
+        Err(err) => {
+            eprintln!("error: could not join {}: {}", addr, err);
+            std::process::exit(1);
         }
+    };
+    let mut cleanup = CleanUp::default();
+    enable_raw_mode()?;
+    cleanup.raw_mode = true;
+    let mut stdout = io::stdout();
+    stdout.execute(EnterAlternateScreen)?;
+    cleanup.alt_screen = true;
+    stdout.execute(Hide)?;
+    cleanup.cursor_hidden = true;
+
+    let mut renderer = game_loop::TerminalRenderer::new(stdout, false, None, true, keys);
+    let result = net::join(stream, &mut game_loop::TerminalInput, &mut renderer, keys);
+    // Restore the terminal before saying why the game ended.
+    drop(cleanup);
+    if let Err(err) = result {
+        eprintln!("{}", err);
+    }
+    Ok(())
+}
 
-        // Move the snake:
-        // Add the new head position to the front of the deque.
-        self.snake.push_front(new_head);
-
-        // Check if we ate food.
-        if new_head == self.food {
-            // Ate food: Score goes up, spawn new food.
-            self.score += 1;
-            self.spawn_food();
-            
-            // --- Level Up Logic ---
-            // Every 5 points, we increase the level and generate new obstacles!
-            if self.score % 5 == 0 {
-                self.level += 1;
-                self.generate_level();
+// The saved preferences (see `config.rs`), as the options the flags start from.
+fn options_from(config: &config::Config) -> cli::Options {
+    let mut defaults = cli::Options::default();
+    config.apply(&mut defaults);
+    defaults
+}
+
+// Saves the settings chosen on the settings screen, and says where they went.
+fn save_settings(config: &config::Config, path: &std::path::Path) {
+    match config.save(path) {
+        Ok(()) => println!("Settings saved to {}", path.display()),
+        Err(err) => eprintln!("warning: could not save settings to {}: {}", path.display(), err),
+    }
+}
+
+// `--settings`: runs the settings screen, then saves what was chosen.
+fn edit_settings(config: config::Config, path: Option<std::path::PathBuf>) -> io::Result<()> {
+    let Some(path) = path else {
+        eprintln!("error: no config folder to save settings in");
+        std::process::exit(1);
+    };
+    let mut cleanup = CleanUp::default();
+    enable_raw_mode()?;
+    cleanup.raw_mode = true;
+    let mut stdout = io::stdout();
+    stdout.execute(EnterAlternateScreen)?;
+    cleanup.alt_screen = true;
+    stdout.execute(Hide)?;
+    cleanup.cursor_hidden = true;
+
+    let config = config::SettingsMenu::new(config).run(&mut game_loop::TerminalInput, &mut stdout)?;
+    drop(cleanup);
+    save_settings(&config, &path);
+    Ok(())
+}
+
+// The main menu: runs it on its own screen and returns what was picked, with
+// the settings if the player changed them there.
+fn choose_mode(config: config::Config) -> io::Result<(menu::Choice, Option<config::Config>)> {
+    let mut cleanup = CleanUp::default();
+    enable_raw_mode()?;
+    cleanup.raw_mode = true;
+    let mut stdout = io::stdout();
+    stdout.execute(EnterAlternateScreen)?;
+    cleanup.alt_screen = true;
+    stdout.execute(Hide)?;
+    cleanup.cursor_hidden = true;
+
+    let scores = scores::Leaderboard::default_path()
+        .map(|path| scores::Leaderboard::load(&path))
+        .unwrap_or_default();
+    let mut menu = menu::MainMenu::new(scores, config);
+    let choice = menu.run(&mut game_loop::TerminalInput, &mut stdout)?;
+    Ok((choice, menu.config()))
+}
+
+// The main entry point of our program.
+fn main() -> io::Result<()> {
+    // Read the command-line flags before touching the terminal,
+    // so errors and `--help` print normally.
+    // The saved preferences (see `config.rs`) are the defaults the flags start from.
+    let config_path = config::Config::default_path();
+    let config = config_path.as_deref().map(config::Config::load).unwrap_or_default();
+    let defaults = options_from(&config);
+    for name in config.keys.unknown_names() {
+        eprintln!("warning: ignoring unknown key '{}' in the config's key bindings", name);
+    }
+
+    let mut options = match cli::parse_args(std::env::args().skip(1), defaults.clone()) {
+        Ok(cli::Command::Play(options)) => *options,
+        Ok(cli::Command::Settings) => return edit_settings(config, config_path),
+        Ok(cli::Command::WatchBest) => cli::Options {
+            replay: Some(best_run()),
+            ..defaults
+        },
+        Ok(cli::Command::Join(addr)) => return join_game(&addr, &defaults.keys),
+        Ok(cli::Command::Scores { json }) => {
+            let board = scores::Leaderboard::default_path()
+                .map(|path| scores::Leaderboard::load(&path))
+                .unwrap_or_default();
+            if json {
+                println!("{}", board.to_json());
+            } else {
+                print!("{}", board.format_table());
             }
-            
-            // IMPORTANT: We do NOT remove the tail. This makes the snake grow by 1 block!
-        } else {
-            // Didn't eat: Remove the last block (tail) to maintain the same length.
-            // This creates the illusion of movement.
-            self.snake.pop_back();
+            return Ok(());
         }
+        Ok(cli::Command::Heatmap) => {
+            let heatmap = heatmap::Heatmap::default_path()
+                .map(|path| heatmap::Heatmap::load(&path))
+                .unwrap_or_default();
+            print!("{}", heatmap.render(WIDTH, HEIGHT));
+            return Ok(());
+        }
+        Ok(cli::Command::Help) => {
+            print!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    // `--print-speed-table` shows how fast each level will be, then exits.
+    if options.print_speed_table {
+        print!("{}", speed_table(options.settings.speed_curve, options.settings.tick_rate));
+        return Ok(());
     }
 
-    // Render the current state to the terminal using buffered output.
-    fn draw(&self, stdout: &mut io::Stdout) -> io::Result<()> {
-        // Draw Borders
-        // Queueing commands is faster than printing immediately.
-        stdout.queue(SetForegroundColor(Color::Grey))?;
-        
-        for x in 0..self.width {
-            // Top and bottom walls
-            stdout
-                .queue(MoveTo(x, 0))?
-                .queue(Print("█"))?
-                .queue(MoveTo(x, self.height - 1))?
-                .queue(Print("█"))?;
+    // The main menu lets the player pick a mode first, which may change the options.
+    // `--menu` asks for it; so does starting the game with no options at all, as
+    // long as someone is at a terminal to pick (not, say, piped into a script).
+    let bare = std::env::args().len() == 1;
+    if options.menu || (bare && io::stdin().is_terminal() && io::stdout().is_terminal()) {
+        let (choice, edited) = choose_mode(config)?;
+        // Settings changed in the menu are saved, and the flags go on top of them instead.
+        if let Some(config) = edited {
+            if let Some(path) = &config_path {
+                save_settings(&config, path);
+            }
+            if let Ok(cli::Command::Play(with_new)) =
+                cli::parse_args(std::env::args().skip(1), options_from(&config))
+            {
+                options = *with_new;
+            }
         }
-        for y in 0..self.height {
-            // Left and right walls
-            stdout
-                .queue(MoveTo(0, y))?
-                .queue(Print("█"))?
-                .queue(MoveTo(self.width - 1, y))?
-                .queue(Print("█"))?;
+        match choice {
+            menu::Choice::Play(mode) => mode.apply(&mut options),
+            menu::Choice::WatchBest => options.replay = Some(best_run()),
+            menu::Choice::Quit => return Ok(()),
         }
+    }
 
-        // Draw Obstacles (The generated walls)
-        stdout.queue(SetForegroundColor(Color::DarkGrey))?;
-        for obstacle in &self.obstacles {
-            stdout
-                .queue(MoveTo(obstacle.x, obstacle.y))?
-                .queue(Print("▓"))?; // Use a different character for inner walls
+    // `--replay`: read the recording now, so a bad file is reported up front.
+    let replay = match &options.replay {
+        Some(path) => match replay::Replay::load(path) {
+            Ok(replay) => Some(replay),
+            Err(message) => {
+                eprintln!("error: {}", message);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // Build the starting board: either from a map file or the classic empty board
+    // (or, for `--replay`, the recorded run's board, rules and seed).
+    // We do this before entering raw mode so a bad map or bad `--food` prints a readable error.
+    let built = match &replay {
+        Some(replay) => replay.start_game().map(|mut game| {
+            game.theme = options.theme;
+            game
+        }),
+        None => build_game(&options),
+    };
+    let mut game = match built {
+        Ok(game) => game,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            std::process::exit(1);
         }
+    };
 
-        // Draw Score and Level
-        stdout
-            .queue(MoveTo(2, 0))?
-            .queue(Print(format!(" Score: {}  Level: {} ", self.score, self.level)))?;
-
-        // Draw Food
-        stdout
-            .queue(SetForegroundColor(Color::Red))?
-            .queue(MoveTo(self.food.x, self.food.y))?
-            .queue(Print("●"))?;
-
-        // Draw Snake
-        stdout.queue(SetForegroundColor(Color::Green))?;
-        for (i, point) in self.snake.iter().enumerate() {
-            stdout.queue(MoveTo(point.x, point.y))?;
-            if i == 0 {
-                stdout.queue(Print("O"))?; // Head
-            } else {
-                stdout.queue(Print("o"))?; // Body
+    // `--dump` prints the starting board as ASCII and exits without touching the terminal.
+    if options.dump {
+        print!("{}", game.render_ascii());
+        return Ok(());
+    }
+
+    // `--bench` plays headless on fresh copies of this board, reports, and exits.
+    if let Some(ticks) = options.bench {
+        match bench::run(ticks, || build_game(&options)) {
+            Ok((report, _)) => print!("{}", report),
+            Err(message) => {
+                eprintln!("error: {}", message);
+                std::process::exit(1);
             }
         }
-        
-        // Reset color to default so we don't mess up the terminal
-        stdout.queue(ResetColor)?;
-        Ok(())
+        return Ok(());
     }
-}
 
-// Struct to handle cleanup when the program exits.
-// Rust utilizes RAII (Resource Acquisition Is Initialization).
-// When `_cleanup` goes out of scope, `drop()` is called automatically.
-struct CleanUp;
+    // `--compare` plays two scripts from this board side by side, reports, and exits.
+    if let Some((a, b)) = &options.compare {
+        let run = || -> Result<compare::Report, String> {
+            let (a, b) = (script::Script::load(a)?, script::Script::load(b)?);
+            compare::compare(&save::SaveGame::from_game(&game), &game.settings, a, b)
+        };
+        match run() {
+            Ok(report) => print!("{}", report.describe()),
+            Err(message) => {
+                eprintln!("error: {}", message);
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
 
-impl Drop for CleanUp {
-    fn drop(&mut self) {
-        // Restore terminal to normal mode (show cursor, disable raw input).
-        let _ = disable_raw_mode();
-        let _ = io::stdout().execute(Show); 
-        let _ = io::stdout().execute(LeaveAlternateScreen);
+    // Everything from here on plays on the terminal, so the board had better fit.
+    if let Ok(size) = terminal::size()
+        && let Err(message) = game_loop::check_fits(game.dimensions(), size)
+    {
+        eprintln!("error: {}", message);
+        std::process::exit(1);
     }
-}
 
-// The main entry point of our program.
-fn main() -> io::Result<()> {
-    // Create our cleanup guard.
-    let _cleanup = CleanUp;
+    // `--perf-log`: open the CSV file now, so a bad path is reported before the game starts.
+    let mut perf_log = match &options.perf_log {
+        Some(path) => match perf::PerfLog::create(path) {
+            Ok(log) => Some(log),
+            Err(err) => {
+                eprintln!("error: could not create perf log {}: {}", path.display(), err);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // `--asciicast`: same idea, open the recording before the game starts.
+    let cast = match &options.asciicast {
+        Some(path) => {
+            let (width, height) = terminal::size()?;
+            match asciicast::Cast::create(path, width, height) {
+                Ok(cast) => Some(cast),
+                Err(err) => {
+                    eprintln!("error: could not create {}: {}", path.display(), err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    // `--script`: read the playthrough now, so mistakes in it are reported up front.
+    let script = match &options.script {
+        Some(path) => match script::Script::load(path) {
+            Ok(script) => Some(script),
+            Err(message) => {
+                eprintln!("error: {}", message);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // `--serve`: start listening for spectators, also before the game starts.
+    let spectators = match &options.serve {
+        Some(addr) => match spectate::Server::bind(addr) {
+            Ok(server) => Some(server),
+            Err(err) => {
+                eprintln!("error: could not serve spectators on {}: {}", addr, err);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // `--host`: wait for the other player last, once everything else is ready.
+    let guest = match &options.host {
+        Some(addr) => match net::wait_for_guest(addr) {
+            Ok(stream) => Some(stream),
+            Err(err) => {
+                eprintln!("error: could not host on {}: {}", addr, err);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // Create our cleanup guard before touching the terminal. Each step below is
+    // recorded right after it succeeds, so an early `?` return undoes just those.
+    let mut cleanup = CleanUp::default();
     
     // Enable "raw mode" for direct key input.
     enable_raw_mode()?;
+    cleanup.raw_mode = true;
     
     let mut stdout = io::stdout();
     // Use an "alternate screen" buffer so we don't clutter the user's terminal history.
-    stdout.execute(EnterAlternateScreen)?;
+    // With `--no-altscreen` we draw straight into the normal buffer instead.
+    if options.alt_screen {
+        stdout.execute(EnterAlternateScreen)?;
+        cleanup.alt_screen = true;
+    } else {
+        cleanup.inline = Some(game.height);
+    }
     stdout.execute(Hide)?; // Hide the flashing cursor cursor
+    cleanup.cursor_hidden = true;
+    // "Bracketed paste" makes the terminal mark pasted text, so it reaches us as one
+    // paste event instead of a flood of key presses (see `game_loop::TerminalInput`).
+    stdout.execute(EnableBracketedPaste)?;
+    cleanup.bracketed_paste = true;
 
-    // Initialize the game state
-    let mut game = Game::new(WIDTH, HEIGHT);
-    
-    // Timer for our game loop
-    let mut last_frame = Instant::now();
-    let tick_rate = Duration::from_millis(150); // Game updates every 150ms
-
-    // Infinite game loop
-    loop {
-        // --- Input Handling ---
-        // `poll` checks if there is an input event waiting (instantly, 0ms wait).
-        if event::poll(Duration::from_millis(0))? {
-            // Read the event
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    // Quit on 'q', 'Esc', or Ctrl+C
-                    KeyCode::Char('q') | KeyCode::Esc => break,
-                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
-                    
-                    // Change direction based on key press (WASD or Arrows)
-                    KeyCode::Left | KeyCode::Char('a') => {
-                        if game.direction != Direction::Right {
-                            game.direction = Direction::Left;
-                        }
-                    }
-                    KeyCode::Right | KeyCode::Char('d') => {
-                        if game.direction != Direction::Left {
-                            game.direction = Direction::Right;
-                        }
-                    }
-                    KeyCode::Up | KeyCode::Char('w') => {
-                        if game.direction != Direction::Down {
-                            game.direction = Direction::Up;
-                        }
-                    }
-                    KeyCode::Down | KeyCode::Char('s') => {
-                        if game.direction != Direction::Up {
-                            game.direction = Direction::Down;
-                        }
-                    }
-                    _ => {} // Ignore other keys
+    // From here on everything drawn also goes into the `--asciicast` recording (if any).
+    // The terminal setup above and the cleanup afterwards stay out of it.
+    let stdout = asciicast::Tee::new(stdout, cast);
+
+    // The leaderboard's high score, for the HUD, the side panel and the game over screen.
+    game.best = scores::Leaderboard::default_path()
+        .map_or(0, |path| scores::Leaderboard::load(&path).best_score());
+
+    // `--title` sets the window title on the first frame, so it needs clearing at the end.
+    cleanup.title = options.title;
+
+    // Play! The loop itself lives in `game_loop.rs`; here we hand it the terminal.
+    // The keyboard, with extras wrapped around it: with `--script` the script steers
+    // (and the keyboard can only quit), and with `--host` the other player steers too.
+    let mut input: Box<dyn game_loop::Input> = Box::new(game_loop::TerminalInput);
+    if let Some(script) = script {
+        input = Box::new(script::ScriptInput::new(input, script));
+    }
+    if let Some(replay) = replay {
+        input = Box::new(replay::ReplayInput::new(input, replay, options.keys.clone()));
+    }
+    if options.bot {
+        input = Box::new(bot::BotInput::new(input, options.keys.clone()));
+    }
+    let mut renderer: Box<dyn game_loop::Renderer> = Box::new(game_loop::TerminalRenderer::new(
+        stdout, options.title, spectators, options.alt_screen, &options.keys,
+    ));
+    if let Some(stream) = guest {
+        input = Box::new(net::HostInput::new(input, &stream)?);
+        renderer = Box::new(net::HostRenderer::new(renderer, stream));
+    }
+    // Stops and restarts from outside the game (`kill -TSTP`, `fg`) are handled
+    // by the loop from now on (see `signals.rs`).
+    signals::install();
+    let mut session = Saved { options: &options };
+    game_loop::run_game(
+        &mut input,
+        &mut renderer,
+        &mut game,
+        &options,
+        &mut session,
+        perf_log.as_mut(),
+    )?;
+    // Closes the `--asciicast` recording, if there is one.
+    drop(renderer);
+
+    // Restore the terminal now (instead of at the end of `main`) so the
+    // performance summary prints normally.
+    drop(cleanup);
+    if let Some(log) = &perf_log {
+        print!("{}", log.summary());
+    }
+
+    Ok(()) // Return "Ok" to signal the main function finished successfully.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Draw;
+    use hello_rust::clock;
+    use std::time::Duration;
+
+    #[test]
+    fn cleanup_only_undoes_steps_that_succeeded() {
+        // Pretend setup got into the alternate screen but failed before hiding the cursor.
+        let mut cleanup = CleanUp { alt_screen: true, ..CleanUp::default() };
+        let mut out = Vec::new();
+        cleanup.restore(&mut out);
+
+        let mut expected = Vec::new();
+        expected.execute(LeaveAlternateScreen).unwrap();
+        assert_eq!(out, expected);
+
+        // Everything is undone now, so a second restore (like the one in `drop`) writes nothing.
+        out.clear();
+        cleanup.restore(&mut out);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn shrink_to_fit_keeps_tiny_boards_winnable() {
+        // Big terminals get the classic board; small ones lose a row for the status line.
+        let classic = (WIDTH, HEIGHT);
+        assert_eq!(fit_board(classic, (120, 40)), classic);
+        assert_eq!(fit_board(classic, (30, 12)), (30, 11));
+        assert_eq!(fit_board(classic, (3, 2)), (5, 3));
+        assert_eq!(fit_board((60, 30), (120, 40)), (60, 30));
+
+        // Without it, a board too big for the terminal is refused up front.
+        assert!(game_loop::check_fits(classic, (40, 21)).is_ok());
+        let err = game_loop::check_fits(classic, (40, 20)).unwrap_err();
+        assert!(err.contains("at least 40x21"));
+        assert!(game_loop::check_fits(classic, (39, 30)).is_err());
+
+        // The tiniest board: a 3x1 strip, with a 2-long snake and the food in front of it.
+        let mut game = Game::new(5, 3, None, None).unwrap();
+        assert_eq!(game.snake().body.len(), 2);
+        assert!(game.snake_segments().all(|p| game.in_bounds(p)));
+        assert_eq!(game.food, Point { x: 3, y: 1 });
+        game.update();
+        assert!(game.game_won);
+
+        // Fewer obstacles on smaller boards, and none that cut the snake off.
+        for (width, height) in [(5, 3), (7, 4), (12, 6), (20, 10)] {
+            for seed in 0..10 {
+                let mut game = Game::new(width, height, None, None).unwrap();
+                game.settings.shrink_to_fit = true;
+                game.rng = StdRng::seed_from_u64(seed);
+                for _ in 0..5 {
+                    game.level_up();
+                    assert!(game.is_food_reachable());
                 }
+                let interior = (width as usize - 2) * (height as usize - 2);
+                assert!(game.obstacles.len() < interior / 2);
             }
         }
+    }
 
-        // --- Game Update & Rendering ---
-        // only update if enough time has passed (tick rate)
-        if last_frame.elapsed() >= tick_rate {
-            game.update();
-            last_frame = Instant::now();
-            
-            // Clear the screen buffer before drawing the new frame.
-            stdout.queue(Clear(ClearType::All))?; 
-            
-            if !game.game_over {
-                 game.draw(&mut stdout)?;
-            } else {
-                 // Draw Game Over Screen
-                 let msg = "GAME OVER";
-                 let score_msg = format!("Final Score: {}", game.score);
-                 let restart_msg = "Press Q to Quit";
-                 
-                 let center_x = WIDTH / 2;
-                 let center_y = HEIGHT / 2;
-                 
-                 // Center the text
-                 stdout.queue(SetForegroundColor(Color::Red))?;
-                 stdout.queue(MoveTo(center_x - (msg.len() as u16 / 2), center_y - 1))?;
-                 stdout.queue(Print(msg))?;
-                 
-                 stdout.queue(SetForegroundColor(Color::White))?;
-                 stdout.queue(MoveTo(center_x - (score_msg.len() as u16 / 2), center_y + 1))?;
-                 stdout.queue(Print(score_msg))?;
-                 
-                 stdout.queue(MoveTo(center_x - (restart_msg.len() as u16 / 2), center_y + 3))?;
-                 stdout.queue(Print(restart_msg))?;
-                 stdout.queue(ResetColor)?;
+    #[test]
+    fn assisted_runs_stay_off_the_leaderboard() {
+        use game_loop::Session;
+
+        let options = cli::Options::default();
+        let mut session = Saved { options: &options };
+        let mut game = build_game(&options).unwrap();
+        game.score = 50;
+        game.end_game();
+        // A cheat was used at some point, or ghost mode is on as the run ends.
+        game.assisted = true;
+        assert!(session.record_score(&game).is_none());
+        game.assisted = false;
+        game.settings.ghost = true;
+        assert!(session.record_score(&game).is_none());
+    }
+
+    #[test]
+    fn the_same_seed_plays_the_same_game() {
+        let play = |seed, record: bool| {
+            let options = cli::Options { seed: Some(seed), ..Default::default() };
+            let mut game = build_game(&options).unwrap();
+            if record {
+                replay::Replay::record(&mut game, options.seed);
             }
-            
-            // Flush commands to the terminal (actually draw everything now).
-            stdout.flush()?;
-        }
-        
-        // Loop Logic for Game Over state
-        if game.game_over {
-             // Just poll input slowly to check for Quit
-             if event::poll(Duration::from_millis(100))? {
-                 if let Event::Key(key) = event::read()? {
-                    match key.code {
-                        KeyCode::Char('q') | KeyCode::Esc => break,
-                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
-                        _ => {}
-                    }
-                 }
-             }
-        } else {
-            // Sleep a tiny bit if we have time left in the frame to save CPU
-             let elapsed = last_frame.elapsed();
-             if elapsed < tick_rate {
-                 std::thread::sleep(Duration::from_millis(10));
-             }
-        }
+            let mut boards = vec![game.render_ascii()];
+            for _ in 0..3 {
+                game.level_up();
+                game.spawn_food();
+                boards.push(game.render_ascii());
+            }
+            boards
+        };
+        assert_eq!(play(507, false), play(507, false));
+        assert_ne!(play(507, false), play(508, false));
+        // Recording it (`--record`) doesn't change the game.
+        assert_eq!(play(507, false), play(507, true));
     }
 
-    Ok(()) // Return "Ok" to signal the main function finished successfully.
-}
\ No newline at end of file
+    #[test]
+    fn retrying_a_level_keeps_or_replaces_its_obstacles() {
+        let mut options = cli::Options::default();
+        let mut old = build_game(&options).unwrap();
+        old.level = 3;
+        old.generate_level();
+        // One obstacle right on the starting snake's path, which has to go.
+        let start = old.snake().head();
+        old.obstacles.insert(start.step(Direction::Right));
+        old.score = 12;
+        old.end_game();
+
+        let obstacles = old.obstacles.clone();
+        let clock = clock::MockClock::new();
+        old.clock = Box::new(clock.clone());
+        clock.advance(Duration::from_secs(30));
+
+        options.retry_layout = RetryLayout::Same;
+        let mut game = old;
+        game.reset(build_game(&options).unwrap(), options.retry_layout);
+        assert_eq!((game.level, game.score, game.is_over()), (3, 0, false));
+        assert!(!game.obstacles.contains(&start.step(Direction::Right)));
+        assert!(game.obstacles.is_subset(&obstacles));
+        // The level was generated around the same starting spot, so nothing else had to go.
+        assert_eq!(game.obstacles.len(), obstacles.len() - 1);
+        assert!(!game.is_lethal(game.next_head(Direction::Right)));
+        // The run just played is the one to beat now.
+        assert_eq!(game.best, 12);
+        let mut out = Vec::new();
+        game.draw(&mut out).unwrap();
+        assert!(String::from_utf8_lossy(&out).contains("Best: 12"));
+        // The timer starts over, on the same clock.
+        assert_eq!(game.elapsed(), Duration::ZERO);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(game.elapsed(), Duration::from_secs(1));
+
+        let same = game.obstacles.clone();
+        options.retry_layout = RetryLayout::Fresh;
+        game.reset(build_game(&options).unwrap(), options.retry_layout);
+        assert_eq!(game.level, 3);
+        assert!(!game.obstacles.is_empty());
+        assert_ne!(game.obstacles, same);
+    }
+}
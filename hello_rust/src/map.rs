@@ -0,0 +1,566 @@
+// Loading hand-made boards from plain text files.
+//
+// A map is a grid of characters, one line per row, describing the WHOLE board
+// including the outer wall:
+//
+//   #  wall (on the edge) or obstacle (inside)
+//   .  empty floor (a space works too)
+//   S  the snake's head; its body trails two cells to the left, heading right
+//   *  the first piece of food (optional - it's placed randomly if missing)
+//   ^ v < >  a one-way gate the snake can only cross going the way it points
+//   1-9  puzzle food, eaten in number order (instead of '*', see below)
+//
+// Example:
+//
+//   ##########
+//   #........#
+//   #...S..*.#
+//   #..###...#
+//   ##########
+//
+// A puzzle map numbers its food instead, and starts with a `moves N` line: all
+// the food has to be eaten, in order, within N moves (see `Puzzle`).
+//
+//   moves 12
+//   #########
+//   #..S..1.#
+//   #..3..2.#
+//   #########
+//
+// Any map can also start with `zone` lines, each a rectangle (its top-left and
+// bottom-right cells, counting the wall as row and column 0) and a weight. New
+// food then only appears inside the zones, picking between them by weight (a
+// whole number from 1 to `MAX_ZONE_WEIGHT`), until they're full. Here food turns
+// up in the left corner three times as often as in the right one:
+//
+//   zone 1,1 2,2 3
+//   zone 7,1 8,2 1
+//   ##########
+//   #........#
+//   #...S....#
+//   ##########
+
+use std::fmt;
+
+use rand::{SeedableRng, rngs::StdRng};
+
+use crate::{Direction, FoodZone, Game, Point, Puzzle, Snake};
+
+// Maps smaller than this can't fit the starting snake; bigger ones won't fit a terminal.
+pub const MIN_WIDTH: usize = 5;
+pub const MIN_HEIGHT: usize = 3;
+pub const MAX_SIZE: usize = 200;
+// The heaviest a food zone can be.
+pub const MAX_ZONE_WEIGHT: u32 = 1000;
+
+// Everything that can go wrong while reading a map.
+// Each variant carries enough detail to tell the user exactly what to fix.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MapError {
+    Empty,
+    JaggedRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    BadSize {
+        width: usize,
+        height: usize,
+    },
+    UnknownTile {
+        row: usize,
+        col: usize,
+        tile: char,
+    },
+    OpenBorder {
+        row: usize,
+        col: usize,
+    },
+    NoSnake,
+    MultipleSnakes,
+    SnakeDoesNotFit,
+    MultipleFood,
+    NoRoomForFood,
+    BadMoveLimit(String),
+    NoMoveLimit,
+    PuzzleOrder,
+    BadZone(String),
+    ZoneOffFloor(String),
+}
+
+// `Display` controls how the error looks when printed with `{}`.
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Empty => write!(f, "the map is empty"),
+            MapError::JaggedRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} is {} cells wide, but the first row is {} wide",
+                row + 1,
+                found,
+                expected
+            ),
+            MapError::BadSize { width, height } => write!(
+                f,
+                "a {}x{} map is not allowed (must be between {}x{} and {}x{})",
+                width, height, MIN_WIDTH, MIN_HEIGHT, MAX_SIZE, MAX_SIZE
+            ),
+            MapError::UnknownTile { row, col, tile } => write!(
+                f,
+                "unknown tile {:?} at row {}, column {}",
+                tile,
+                row + 1,
+                col + 1
+            ),
+            MapError::OpenBorder { row, col } => write!(
+                f,
+                "the outer edge must be wall ('#'), but row {}, column {} is open",
+                row + 1,
+                col + 1
+            ),
+            MapError::NoSnake => write!(f, "the map has no snake start ('S')"),
+            MapError::MultipleSnakes => write!(f, "the map has more than one snake start ('S')"),
+            MapError::SnakeDoesNotFit => write!(
+                f,
+                "the snake needs two free cells to the left of 'S' for its body"
+            ),
+            MapError::MultipleFood => write!(f, "the map has more than one food ('*')"),
+            MapError::NoRoomForFood => write!(f, "the map has no free cell left for food"),
+            MapError::BadMoveLimit(line) => write!(
+                f,
+                "{:?} is not a move limit (it should look like \"moves 20\")",
+                line
+            ),
+            MapError::NoMoveLimit => write!(
+                f,
+                "a puzzle map needs both numbered food and a \"moves N\" line at the top"
+            ),
+            MapError::PuzzleOrder => write!(
+                f,
+                "puzzle food must be numbered 1, 2, 3... with no gaps or repeats, and no '*'"
+            ),
+            MapError::BadZone(line) => write!(
+                f,
+                "{:?} is not a food zone (it should look like \"zone 1,1 4,3 2\": two corners and a weight from 1 to {})",
+                line, MAX_ZONE_WEIGHT
+            ),
+            MapError::ZoneOffFloor(line) => write!(
+                f,
+                "food zone {:?} must be on the board and cover some open floor",
+                line
+            ),
+        }
+    }
+}
+
+// Lets `MapError` be used anywhere a standard error is expected (e.g. with `?` into `Box<dyn Error>`).
+impl std::error::Error for MapError {}
+
+impl Game {
+    // Builds a game from the text of a map file.
+    // Never panics: anything unexpected in `text` comes back as a `MapError`.
+    pub fn from_map(text: &str) -> Result<Game, MapError> {
+        Game::from_map_with_rng(text, StdRng::from_entropy())
+    }
+
+    // Like `from_map`, with the random numbers (for the food, if the map doesn't
+    // place it) coming from `rng`, like `Game::with_rng`.
+    pub fn from_map_with_rng(text: &str, rng: StdRng) -> Result<Game, MapError> {
+        // Collect the rows as vectors of chars. `trim_end_matches('\r')` lets
+        // Windows-style line endings work too. Trailing blank lines are ignored.
+        let mut rows: Vec<Vec<char>> = text
+            .lines()
+            .map(|line| line.trim_end_matches('\r').chars().collect())
+            .collect();
+        while rows.last().is_some_and(|row| row.is_empty()) {
+            rows.pop();
+        }
+
+        // A puzzle's move limit and any food zones come first, each on a line of its own.
+        let mut move_limit = None;
+        let mut zone_lines = Vec::new();
+        while let Some(first) = rows.first() {
+            let line: String = first.iter().collect();
+            if let Some(moves) = line.strip_prefix("moves") {
+                let moves = moves.trim().parse::<u32>();
+                match moves {
+                    Ok(moves) if moves > 0 => move_limit = Some(moves),
+                    _ => return Err(MapError::BadMoveLimit(line)),
+                }
+            } else if line.starts_with("zone") {
+                zone_lines.push(line);
+            } else {
+                break;
+            }
+            rows.remove(0);
+        }
+
+        let height = rows.len();
+        let width = rows.first().ok_or(MapError::Empty)?.len();
+
+        for (row, cells) in rows.iter().enumerate() {
+            if cells.len() != width {
+                return Err(MapError::JaggedRow {
+                    row,
+                    expected: width,
+                    found: cells.len(),
+                });
+            }
+        }
+        if !(MIN_WIDTH..=MAX_SIZE).contains(&width) || !(MIN_HEIGHT..=MAX_SIZE).contains(&height) {
+            return Err(MapError::BadSize { width, height });
+        }
+
+        let mut obstacles = Vec::new();
+        let mut gates = Vec::new();
+        let mut head = None;
+        let mut food = None;
+        // Puzzle food, with its number.
+        let mut numbered: Vec<(u32, Point)> = Vec::new();
+
+        for (row, cells) in rows.iter().enumerate() {
+            for (col, &tile) in cells.iter().enumerate() {
+                let on_edge = row == 0 || col == 0 || row == height - 1 || col == width - 1;
+                let point = Point {
+                    x: col as u16,
+                    y: row as u16,
+                };
+
+                match tile {
+                    '#' if on_edge => {} // The outer wall is drawn by the game itself.
+                    _ if on_edge => return Err(MapError::OpenBorder { row, col }),
+                    '#' => obstacles.push(point),
+                    '^' | 'v' | '<' | '>' => {
+                        let direction = match tile {
+                            '^' => Direction::Up,
+                            'v' => Direction::Down,
+                            '<' => Direction::Left,
+                            _ => Direction::Right,
+                        };
+                        // A gate is a wall with a way through, so it's an obstacle too.
+                        obstacles.push(point);
+                        gates.push((point, direction));
+                    }
+                    '.' | ' ' => {}
+                    'S' if head.is_some() => return Err(MapError::MultipleSnakes),
+                    'S' => head = Some(point),
+                    '*' if food.is_some() => return Err(MapError::MultipleFood),
+                    '*' => food = Some(point),
+                    '1'..='9' => numbered.push((tile.to_digit(10).unwrap_or(0), point)),
+                    tile => return Err(MapError::UnknownTile { row, col, tile }),
+                }
+            }
+        }
+
+        let head = head.ok_or(MapError::NoSnake)?;
+
+        // Each zone has to be on the board and reach at least one cell of open floor.
+        let mut food_zones = Vec::new();
+        for line in zone_lines {
+            let zone = parse_zone(&line).ok_or_else(|| MapError::BadZone(line.clone()))?;
+            let on_board = (zone.to.x as usize) < width && (zone.to.y as usize) < height;
+            let on_floor = on_board
+                && zone.cells().any(|p| {
+                    (1..width - 1).contains(&(p.x as usize))
+                        && (1..height - 1).contains(&(p.y as usize))
+                        && !obstacles.contains(&p)
+                });
+            if !on_floor {
+                return Err(MapError::ZoneOffFloor(line));
+            }
+            food_zones.push(zone);
+        }
+
+        // Puzzle food must run 1, 2, 3... and comes with a move limit.
+        numbered.sort_by_key(|&(number, _)| number);
+        let in_order = numbered
+            .iter()
+            .zip(1..)
+            .all(|(&(number, _), expected)| number == expected);
+        if !in_order || (food.is_some() && !numbered.is_empty()) {
+            return Err(MapError::PuzzleOrder);
+        }
+        if numbered.is_empty() != move_limit.is_none() {
+            return Err(MapError::NoMoveLimit);
+        }
+        let puzzle_food: Vec<Point> = numbered.into_iter().map(|(_, point)| point).collect();
+
+        // The body trails to the left of the head, so those two cells must be open floor.
+        let is_open = |p: Point| {
+            p.x > 0 && !obstacles.contains(&p) && food != Some(p) && !puzzle_food.contains(&p)
+        };
+        let body: Vec<Point> = (1..=2)
+            .map(|i| Point {
+                x: head.x.saturating_sub(i),
+                y: head.y,
+            })
+            .collect();
+        if head.x < 3 || !body.iter().all(|&p| is_open(p)) {
+            return Err(MapError::SnakeDoesNotFit);
+        }
+
+        // With no fixed food there's nothing for `Game::new` to reject.
+        let mut game = Game::with_rng(width as u16, height as u16, None, None, rng)
+            .expect("random food always fits");
+        game.snakes = vec![Snake::new(head, Direction::Right, 3)];
+        game.obstacles = obstacles.into_iter().collect();
+        game.gates = gates.into_iter().collect();
+        game.food_zones = food_zones;
+
+        if let Some(moves_left) = move_limit {
+            game.food = puzzle_food[0];
+            game.puzzle = Some(Puzzle {
+                food: puzzle_food,
+                eaten: 0,
+                moves_left,
+            });
+            return Ok(game);
+        }
+
+        match food {
+            Some(food) => game.food = food,
+            None => {
+                // `spawn_food` would loop forever on a board with no free cell, so check first.
+                if game.snake().body.len() + game.obstacles.len() >= game.interior_capacity() {
+                    return Err(MapError::NoRoomForFood);
+                }
+                game.spawn_food();
+            }
+        }
+
+        Ok(game)
+    }
+}
+
+// Reads a `zone X1,Y1 X2,Y2 WEIGHT` line, or `None` if it doesn't look like one.
+// The corners can come in either order.
+fn parse_zone(line: &str) -> Option<FoodZone> {
+    let corner = |text: &str| -> Option<Point> {
+        let (x, y) = text.split_once(',')?;
+        Some(Point {
+            x: x.trim().parse().ok()?,
+            y: y.trim().parse().ok()?,
+        })
+    };
+    let mut words = line.strip_prefix("zone")?.split_whitespace();
+    let (a, b) = (corner(words.next()?)?, corner(words.next()?)?);
+    let weight = words
+        .next()?
+        .parse::<u32>()
+        .ok()
+        .filter(|weight| (1..=MAX_ZONE_WEIGHT).contains(weight))?;
+    if words.next().is_some() {
+        return None;
+    }
+    Some(FoodZone {
+        from: Point {
+            x: a.x.min(b.x),
+            y: a.y.min(b.y),
+        },
+        to: Point {
+            x: a.x.max(b.x),
+            y: a.y.max(b.y),
+        },
+        weight,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    // Checks the invariants every successfully loaded game must satisfy.
+    fn assert_valid(game: &Game) {
+        let inside = |p: &Point| game.in_bounds(*p);
+        let snake = &game.snake().body;
+        assert_eq!(snake.len(), 3);
+        assert!(snake.iter().all(inside));
+        assert!(game.obstacles.iter().all(inside));
+        assert!(inside(&game.food));
+        assert!(!snake.contains(&game.food));
+        assert!(!game.obstacles.contains(&game.food));
+        assert!(snake.iter().all(|p| !game.obstacles.contains(p)));
+    }
+
+    // Loads `text` and checks we got either a valid game or an error that explains itself.
+    fn check(text: &str) {
+        match Game::from_map(text) {
+            Ok(game) => assert_valid(&game),
+            Err(error) => assert!(!error.to_string().is_empty()),
+        }
+    }
+
+    #[test]
+    fn loads_a_simple_map() {
+        let game = Game::from_map("#########\n#...S.*.#\n#.##....#\n#########\n").unwrap();
+        assert_eq!((game.width, game.height), (9, 4));
+        assert_eq!(game.snake().head(), Point { x: 4, y: 1 });
+        assert_eq!(game.food, Point { x: 6, y: 1 });
+        assert_eq!(game.obstacles.len(), 2);
+        assert_valid(&game);
+    }
+
+    #[test]
+    fn targeted_bad_maps_are_rejected() {
+        assert_eq!(Game::from_map("").err(), Some(MapError::Empty));
+        assert_eq!(Game::from_map("\n\n").err(), Some(MapError::Empty));
+        assert!(matches!(
+            Game::from_map("######\n#..S.#\n####\n").err(),
+            Some(MapError::JaggedRow { row: 2, .. })
+        ));
+        assert_eq!(
+            Game::from_map("######\n#....#\n######").err(),
+            Some(MapError::NoSnake)
+        );
+        assert_eq!(
+            Game::from_map("########\n#..S..S#\n########").err(),
+            Some(MapError::MultipleSnakes)
+        );
+        assert!(matches!(
+            Game::from_map("#S#").err(),
+            Some(MapError::BadSize { .. })
+        ));
+        let huge = format!("{}\n", "#".repeat(MAX_SIZE + 1)).repeat(3);
+        assert!(matches!(
+            Game::from_map(&huge).err(),
+            Some(MapError::BadSize { .. })
+        ));
+        let walls = "#######\n".repeat(5);
+        assert_eq!(Game::from_map(&walls).err(), Some(MapError::NoSnake));
+        assert_eq!(
+            Game::from_map("#####\n#..S#\n#####").err(),
+            Some(MapError::NoRoomForFood)
+        );
+        assert_eq!(
+            Game::from_map("######\n#.S..#\n######").err(),
+            Some(MapError::SnakeDoesNotFit)
+        );
+        assert!(matches!(
+            Game::from_map("######\n.S...#\n######").err(),
+            Some(MapError::OpenBorder { .. })
+        ));
+        assert!(matches!(
+            Game::from_map("######\n#?S..#\n######").err(),
+            Some(MapError::UnknownTile { .. })
+        ));
+    }
+
+    #[test]
+    fn loads_a_puzzle() {
+        let text = "moves 12\n#########\n#..S..1.#\n#..3..2.#\n#########\n";
+        let game = Game::from_map(text).unwrap();
+        let puzzle = game.puzzle.as_ref().unwrap();
+        let food = [
+            Point { x: 6, y: 1 },
+            Point { x: 6, y: 2 },
+            Point { x: 3, y: 2 },
+        ];
+        assert_eq!(puzzle.food, food);
+        assert_eq!((puzzle.eaten, puzzle.moves_left), (0, 12));
+        assert_eq!(game.food, food[0]);
+
+        let bad = |text: &str| Game::from_map(text).err();
+        assert_eq!(
+            bad("#########\n#...S.1.#\n#########"),
+            Some(MapError::NoMoveLimit)
+        );
+        assert_eq!(
+            bad("moves 5\n#########\n#...S.*.#\n#########"),
+            Some(MapError::NoMoveLimit)
+        );
+        assert_eq!(
+            bad("moves 5\n#########\n#...S.2.#\n#########"),
+            Some(MapError::PuzzleOrder)
+        );
+        assert_eq!(
+            bad("moves 5\n#########\n#..S*.1.#\n#########"),
+            Some(MapError::PuzzleOrder)
+        );
+        assert!(matches!(
+            bad("moves lots\n#########\n#...S.1.#\n#########"),
+            Some(MapError::BadMoveLimit(_))
+        ));
+    }
+
+    #[test]
+    fn loads_food_zones() {
+        let text =
+            "zone 2,2 1,1 3\nzone 7,1 8,2 1\n##########\n#........#\n#...S....#\n##########\n";
+        let game = Game::from_map(text).unwrap();
+        let corners: Vec<(Point, Point, u32)> = game
+            .food_zones
+            .iter()
+            .map(|zone| (zone.from, zone.to, zone.weight))
+            .collect();
+        assert_eq!(
+            corners,
+            [
+                (Point { x: 1, y: 1 }, Point { x: 2, y: 2 }, 3),
+                (Point { x: 7, y: 1 }, Point { x: 8, y: 2 }, 1),
+            ]
+        );
+
+        let bad =
+            |zone: &str| Game::from_map(&format!("{}\n#######\n#.#.S.#\n#######", zone)).err();
+        for line in [
+            "zone 1,1 2,1",
+            "zone 1,1 2,1 0",
+            "zone 1,1 2,1 4294967295",
+            "zone 1;1 2,1 1",
+            "zone 1,1 2,1 1 x",
+        ] {
+            assert_eq!(bad(line), Some(MapError::BadZone(line.to_string())));
+        }
+        // Off the board, only on the wall, or only on obstacles.
+        for line in ["zone 1,1 7,1 1", "zone 0,0 6,0 1", "zone 2,1 2,1 1"] {
+            assert_eq!(bad(line), Some(MapError::ZoneOffFloor(line.to_string())));
+        }
+    }
+
+    #[test]
+    fn fuzz_random_bytes_never_panic() {
+        let mut rng = StdRng::seed_from_u64(408);
+        for _ in 0..2_000 {
+            let len = rng.gen_range(0..256);
+            let bytes: Vec<u8> = (0..len).map(|_| rng.r#gen()).collect();
+            check(&String::from_utf8_lossy(&bytes));
+        }
+    }
+
+    #[test]
+    fn fuzz_malformed_grids_never_panic() {
+        // Grids built from the map alphabet are far more likely to get deep into the loader.
+        const TILES: [char; 7] = ['#', '#', '.', '.', ' ', 'S', '*'];
+        let mut rng = StdRng::seed_from_u64(1408);
+        for _ in 0..5_000 {
+            let width = rng.gen_range(0..12);
+            let height = rng.gen_range(0..8);
+            let mut text = String::new();
+            for y in 0..height {
+                // Occasionally make a row the wrong length.
+                let row_width = if rng.gen_bool(0.1) {
+                    rng.gen_range(0..12)
+                } else {
+                    width
+                };
+                for x in 0..row_width {
+                    // Mostly keep the border closed so we get past the edge check.
+                    let edge = y == 0 || x == 0 || y == height - 1 || x == row_width - 1;
+                    let tile = if edge && rng.gen_bool(0.95) {
+                        '#'
+                    } else {
+                        TILES[rng.gen_range(0..TILES.len())]
+                    };
+                    text.push(tile);
+                }
+                text.push('\n');
+            }
+            check(&text);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+// The main menu: pick a mode with the arrow keys and Enter before the game
+// starts. It comes up when the game is started on a terminal with no options
+// (or with `--menu`); any other options mean the player has already picked.
+//
+// Each entry is an `Item`, and picking one either shows another screen (the
+// leaderboard, or the settings from `config.rs`) or gives back a `Choice`. A
+// mode is just a change to the options the game is built from (`Mode::apply`),
+// so a new mode means a new `Mode` variant and a line in `ITEMS`; `main` only
+// ever sees "play with these options", "watch the best run" or "quit", and the
+// settings if they were changed (`MainMenu::config`).
+
+use std::{io, time::Duration};
+
+use crossterm::{
+    QueueableCommand,
+    cursor::MoveTo,
+    event::{KeyCode, KeyModifiers},
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{Clear, ClearType},
+};
+
+use crate::{
+    cli::Options,
+    config::{Config, SettingsMenu},
+    game_loop::Input,
+    replay,
+    scores::Leaderboard,
+};
+
+// The modes the menu offers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    // The plain game: no obstacles, ever, just the walls around the edge.
+    Classic,
+    // The usual game, with new walls on every level.
+    Obstacles,
+    // Walls on every level, and the outer walls wrap around.
+    Wrap,
+    // Two players on one keyboard (`--versus`).
+    TwoPlayer,
+}
+
+impl Mode {
+    // Sets `options` up for this mode, on top of whatever flags were given.
+    pub fn apply(self, options: &mut Options) {
+        match self {
+            Mode::Classic => options.settings.no_obstacles = true,
+            // On even if `--no-obstacles` (or the config) turned them off.
+            Mode::Obstacles => options.settings.no_obstacles = false,
+            Mode::Wrap => options.settings.wrap = true,
+            // Versus and co-op don't mix, so picking this overrides `--coop`.
+            Mode::TwoPlayer => (options.settings.versus, options.settings.coop) = (true, false),
+        }
+    }
+}
+
+// What the player picked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Play(Mode),
+    // Play back the best run's replay (like `--watch-best`).
+    WatchBest,
+    Quit,
+}
+
+// What picking an item does: play or quit (returned from `MainMenu::run`), or
+// show the leaderboard or the settings screen (handled by the menu itself).
+// Watching the best run is returned too, but only once there is one to watch.
+#[derive(Clone, Copy)]
+enum Pick {
+    Choose(Choice),
+    Scores,
+    Settings,
+    WatchBest,
+}
+
+// One line of the menu: its label, a short description, and what picking it does.
+struct Item {
+    label: &'static str,
+    about: &'static str,
+    pick: Pick,
+}
+
+const ITEMS: [Item; 8] = [
+    Item {
+        label: "Classic",
+        about: "Just you, the food and the walls around the edge",
+        pick: Pick::Choose(Choice::Play(Mode::Classic)),
+    },
+    Item {
+        label: "Obstacles",
+        about: "New walls on every level",
+        pick: Pick::Choose(Choice::Play(Mode::Obstacles)),
+    },
+    Item {
+        label: "Wrap",
+        about: "Leave by one edge to come back in at the other",
+        pick: Pick::Choose(Choice::Play(Mode::Wrap)),
+    },
+    Item {
+        label: "Two-Player",
+        about: "Arrows against WASD, a score each",
+        pick: Pick::Choose(Choice::Play(Mode::TwoPlayer)),
+    },
+    Item {
+        label: "Best Run",
+        about: "Watch the run that set the high score",
+        pick: Pick::WatchBest,
+    },
+    Item {
+        label: "Leaderboard",
+        about: "The best runs so far",
+        pick: Pick::Scores,
+    },
+    Item {
+        label: "Settings",
+        about: "Sound, theme, speed and controls",
+        pick: Pick::Settings,
+    },
+    Item {
+        label: "Quit",
+        about: "",
+        pick: Pick::Choose(Choice::Quit),
+    },
+];
+
+// The menu screen: which item is selected, the leaderboard to show, and the
+// saved preferences for the settings screen to edit.
+#[derive(Default)]
+pub struct MainMenu {
+    selected: usize,
+    scores: Leaderboard,
+    // Whether the leaderboard is on screen instead of the menu.
+    showing_scores: bool,
+    config: Config,
+    // Whether the settings screen has been opened (and so `config` may have changed).
+    edited: bool,
+    // Whether a best run has been kept to watch (see `replay::best_path`).
+    has_best_run: bool,
+    // A line under the menu saying why the last pick didn't work, until the next key.
+    note: Option<&'static str>,
+}
+
+impl MainMenu {
+    pub fn new(scores: Leaderboard, config: Config) -> MainMenu {
+        MainMenu {
+            scores,
+            config,
+            has_best_run: replay::best_path().is_some_and(|path| path.exists()),
+            ..MainMenu::default()
+        }
+    }
+
+    // The settings as the settings screen left them, if the player opened it.
+    pub fn config(self) -> Option<Config> {
+        self.edited.then_some(self.config)
+    }
+
+    // Moves the selection up (`-1`) or down (`1`), wrapping around at the ends.
+    fn select(&mut self, step: isize) {
+        self.selected = (self.selected as isize + step).rem_euclid(ITEMS.len() as isize) as usize;
+    }
+
+    // Draws the whole screen, with the selected item highlighted.
+    fn draw(&self, out: &mut impl io::Write) -> io::Result<()> {
+        out.queue(Clear(ClearType::All))?
+            .queue(MoveTo(2, 1))?
+            .queue(SetForegroundColor(Color::Green))?
+            .queue(Print("SNAKE"))?;
+        for (row, item) in ITEMS.iter().enumerate() {
+            let (marker, color) = if row == self.selected {
+                ("> ", Color::Yellow)
+            } else {
+                ("  ", Color::White)
+            };
+            out.queue(MoveTo(2, 3 + row as u16))?
+                .queue(SetForegroundColor(color))?
+                .queue(Print(format!("{}{:<12}", marker, item.label)))?
+                .queue(SetForegroundColor(Color::DarkGrey))?
+                .queue(Print(item.about))?;
+        }
+        out.queue(MoveTo(2, 4 + ITEMS.len() as u16))?
+            .queue(SetForegroundColor(Color::DarkGrey))?
+            .queue(Print("Up/Down choose, Enter start, Q quit"))?;
+        if let Some(note) = self.note {
+            out.queue(MoveTo(2, 6 + ITEMS.len() as u16))?
+                .queue(SetForegroundColor(Color::Yellow))?
+                .queue(Print(note))?;
+        }
+        out.queue(ResetColor)?;
+        out.flush()
+    }
+
+    // Runs the menu until the player picks something.
+    pub fn run(&mut self, input: &mut impl Input, out: &mut impl io::Write) -> io::Result<Choice> {
+        loop {
+            if self.showing_scores {
+                self.scores.draw_screen(out)?;
+                out.flush()?;
+            } else {
+                self.draw(out)?;
+            }
+            // Nothing changes on its own here, so just wait for the next key.
+            let Some(key) = input.poll(Duration::from_millis(500))? else {
+                continue;
+            };
+            // Any key takes the leaderboard away again.
+            if self.showing_scores {
+                self.showing_scores = false;
+                continue;
+            }
+            self.note = None;
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(Choice::Quit),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(Choice::Quit);
+                }
+                KeyCode::Up | KeyCode::Char('w') => self.select(-1),
+                KeyCode::Down | KeyCode::Char('s') => self.select(1),
+                KeyCode::Enter | KeyCode::Char(' ') => match ITEMS[self.selected].pick {
+                    Pick::Choose(choice) => return Ok(choice),
+                    Pick::Scores => self.showing_scores = true,
+                    // The settings screen takes over until the player leaves it.
+                    // (`mem::take` lends it the config, leaving the default in
+                    // its place until it's handed back.)
+                    Pick::Settings => {
+                        let config = std::mem::take(&mut self.config);
+                        self.config = SettingsMenu::new(config).run(input, out)?;
+                        self.edited = true;
+                    }
+                    Pick::WatchBest if self.has_best_run => return Ok(Choice::WatchBest),
+                    Pick::WatchBest => {
+                        self.note = Some("No best run yet: play with --record and set a high score")
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_loop::testing::Keys;
+
+    #[test]
+    fn the_menu_picks_a_mode_or_quits() {
+        let pick = |keys: Vec<KeyCode>| {
+            let mut screen = Vec::new();
+            let choice = MainMenu::default().run(&mut Keys(keys), &mut screen).unwrap();
+            assert!(String::from_utf8_lossy(&screen).contains("Two-Player"));
+            (choice, String::from_utf8_lossy(&screen).contains("HIGH SCORES"))
+        };
+        assert_eq!(pick(vec![KeyCode::Enter]), (Choice::Play(Mode::Classic), false));
+        // Down twice, then back up past the top to the last item.
+        assert_eq!(pick(vec![KeyCode::Down, KeyCode::Down, KeyCode::Enter]), (Choice::Play(Mode::Wrap), false));
+        assert_eq!(pick(vec![KeyCode::Up, KeyCode::Enter]), (Choice::Quit, false));
+        assert_eq!(pick(vec![KeyCode::Down, KeyCode::Esc]), (Choice::Quit, false));
+        // The leaderboard shows until a key is pressed, and that key does nothing else.
+        let scores = vec![KeyCode::Up, KeyCode::Up, KeyCode::Up, KeyCode::Enter, KeyCode::Esc, KeyCode::Up, KeyCode::Up, KeyCode::Enter];
+        assert_eq!(pick(scores), (Choice::Play(Mode::TwoPlayer), true));
+
+        // Best Run plays the best run back, once there is one; until then it says why not.
+        let best_run = vec![KeyCode::Up, KeyCode::Up, KeyCode::Up, KeyCode::Up, KeyCode::Enter];
+        let mut menu = MainMenu { has_best_run: true, ..MainMenu::default() };
+        assert_eq!(menu.run(&mut Keys(best_run.clone()), &mut Vec::new()).unwrap(), Choice::WatchBest);
+        let mut screen = Vec::new();
+        let keys = [best_run, vec![KeyCode::Esc]].concat();
+        assert_eq!(MainMenu::default().run(&mut Keys(keys), &mut screen).unwrap(), Choice::Quit);
+        assert!(String::from_utf8_lossy(&screen).contains("No best run yet"));
+
+        // Settings opens the settings screen (here to switch the sound on), and
+        // the menu carries on when it closes.
+        let mut menu = MainMenu::default();
+        let keys = vec![
+            KeyCode::Up,
+            KeyCode::Up,
+            KeyCode::Enter,
+            KeyCode::Enter,
+            KeyCode::Esc,
+            KeyCode::Down,
+            KeyCode::Enter,
+        ];
+        let choice = menu.run(&mut Keys(keys), &mut Vec::new()).unwrap();
+        assert_eq!(choice, Choice::Quit);
+        assert!(menu.config().is_some_and(|config| config.sound));
+        // Without it, there are no new settings to save.
+        let mut menu = MainMenu::default();
+        menu.run(&mut Keys(vec![KeyCode::Esc]), &mut Vec::new()).unwrap();
+        assert_eq!(menu.config(), None);
+
+        let mut options = Options::default();
+        Mode::TwoPlayer.apply(&mut options);
+        Mode::Classic.apply(&mut options);
+        assert!(options.settings.versus && options.settings.no_obstacles);
+        Mode::Obstacles.apply(&mut options);
+        assert!(!options.settings.no_obstacles);
+    }
+}
@@ -0,0 +1,430 @@
+// Networked co-op (experimental): two players on two machines, one snake each.
+//
+// One player hosts (`--host ADDR`) and runs the real game, in co-op mode; the
+// other joins (`--join ADDR`) and just shows what the host sends. The host is
+// the only one deciding what happens, so the two boards can never disagree:
+//
+//   joiner -> host: one line per key press, naming a direction ("UP")
+//   host -> joiner: one JSON line per change, either a snapshot of the whole
+//                   game or, when a tick only moved the snakes, their new heads
+//
+// A snapshot is the same state a saved game uses (see `save.rs`), plus whether
+// the game is over. Most ticks only move the snakes, so the host sends just
+// those moves, and a snapshot when anything else changed (an apple eaten, a
+// pickup appearing) and every `SNAPSHOT_EVERY` ticks anyway. The host keeps
+// its own copy of what the joiner has been told, so it can tell which to send.
+//
+// The lines go out on a thread of their own, so a joiner on a slow connection
+// can't hold up the host's game: if too many lines pile up waiting for them,
+// the host skips ahead and sends a fresh snapshot once they catch up.
+//
+// On the host, the joiner steers player two, just like WASD on the host's own
+// keyboard (which still works too).
+
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError},
+    thread,
+    time::Duration,
+};
+
+use crossterm::event::KeyEvent;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Direction, Game, Point,
+    game_loop::{Input, Renderer},
+    keys::{Action, KeyBindings},
+    save::SaveGame,
+    scores,
+};
+
+// How many lines can wait to be sent to the joiner before the host stops adding more.
+const MAX_BACKLOG: usize = 64;
+// A full snapshot goes out at least this often (in ticks), whatever changed.
+const SNAPSHOT_EVERY: u64 = 10;
+
+// What the host sends the joiner.
+#[derive(Serialize, Deserialize)]
+enum Message {
+    // The whole game. (Boxed because it's much bigger than a `Tick`.)
+    Snapshot(Box<Snapshot>),
+    // One tick where only the snakes moved: each snake's new head and direction.
+    Tick(Vec<(Point, Direction)>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    game: SaveGame,
+    tick: u64,
+    over: bool,
+    won: bool,
+}
+
+impl Snapshot {
+    fn from_game(game: &Game) -> Snapshot {
+        Snapshot {
+            game: SaveGame::from_game(game),
+            tick: game.tick,
+            over: game.game_over,
+            won: game.game_won,
+        }
+    }
+
+    fn into_game(self) -> Result<Game, String> {
+        let mut game = self.game.into_game()?;
+        game.tick = self.tick;
+        game.game_over = self.over;
+        game.game_won = self.won;
+        Ok(game)
+    }
+}
+
+// The whole state of `game` as a line of JSON, to compare the host's game with
+// the joiner's copy.
+fn state_of(game: &Game) -> io::Result<String> {
+    serde_json::to_string(&Snapshot::from_game(game)).map_err(io::Error::other)
+}
+
+// Reads a game back from what `state_of` wrote.
+fn state_to_game(state: &str) -> io::Result<Game> {
+    serde_json::from_str::<Snapshot>(state)
+        .map_err(|err| err.to_string())
+        .and_then(Snapshot::into_game)
+        .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+}
+
+// Makes a `Tick`'s moves on `game`, the way `Game::update` moves the snakes.
+// Snakes that are out of the round (versus mode) stay where they are.
+fn step(game: &mut Game, moves: &[(Point, Direction)]) {
+    game.tick += 1;
+    for (snake, &(head, direction)) in game.snakes.iter_mut().zip(moves) {
+        if snake.crashed {
+            continue;
+        }
+        if direction == snake.last_direction {
+            snake.straight_ticks += 1;
+        } else {
+            snake.straight_ticks = 0;
+        }
+        snake.direction = direction;
+        snake.last_direction = direction;
+        snake.body.push_front(head);
+        snake.body.pop_back();
+    }
+}
+
+// Reads lines from `stream` on a thread of its own, so the game never waits
+// for the network, and hands each line over through a channel.
+fn read_lines(stream: TcpStream) -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            // Stop when the connection breaks or the game stops listening.
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+// Writes each line sent through the channel to `stream`, on a thread of its own,
+// so the game never waits for the network. At most `MAX_BACKLOG` lines wait
+// their turn; `try_send` says when that's full, instead of waiting for room.
+fn write_lines(mut stream: TcpStream) -> SyncSender<String> {
+    let (sender, receiver) = mpsc::sync_channel::<String>(MAX_BACKLOG);
+    thread::spawn(move || {
+        for line in receiver {
+            // Stop when the connection breaks; the game notices on its next send.
+            if stream.write_all(line.as_bytes()).is_err() {
+                break;
+            }
+        }
+    });
+    sender
+}
+
+// `--host`: waits for the other player to connect to `addr`.
+pub fn wait_for_guest(addr: &str) -> io::Result<TcpStream> {
+    let listener = TcpListener::bind(addr)?;
+    println!(
+        "Waiting for player two: run `hello_rust --join {}` on their machine...",
+        addr
+    );
+    let (stream, _) = listener.accept()?;
+    // Send each frame right away instead of waiting to fill a packet.
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+// The host's input: its own keyboard (`inner`), plus the joiner's turns for player two.
+pub struct HostInput<I: Input> {
+    inner: I,
+    guest: Receiver<String>,
+}
+
+impl<I: Input> HostInput<I> {
+    pub fn new(inner: I, stream: &TcpStream) -> io::Result<HostInput<I>> {
+        Ok(HostInput {
+            inner,
+            guest: read_lines(stream.try_clone()?),
+        })
+    }
+}
+
+impl<I: Input> Input for HostInput<I> {
+    fn poll(&mut self, timeout: Duration) -> io::Result<Option<KeyEvent>> {
+        self.inner.poll(timeout)
+    }
+
+    fn turns(&mut self, tick: u64) -> Vec<(usize, Direction)> {
+        let mut turns = self.inner.turns(tick);
+        // `try_iter` takes whatever has arrived, without waiting for more.
+        let guest_turns = self
+            .guest
+            .try_iter()
+            .filter_map(|line| Direction::from_name(line.trim()));
+        turns.extend(guest_turns.map(|direction| (1, direction)));
+        turns
+    }
+}
+
+// The host's renderer: draws as usual with `inner`, then tells the joiner
+// what changed.
+pub struct HostRenderer<R: Renderer> {
+    inner: R,
+    // `None` once the joiner has gone; the game carries on without them.
+    guest: Option<SyncSender<String>>,
+    // The game as the joiner has it, from what they've been sent so far.
+    copy: Option<Game>,
+    // Whether a line had to be dropped because the backlog was full, so the
+    // joiner needs a snapshot to catch up.
+    behind: bool,
+}
+
+impl<R: Renderer> HostRenderer<R> {
+    pub fn new(inner: R, guest: TcpStream) -> HostRenderer<R> {
+        HostRenderer {
+            inner,
+            guest: Some(write_lines(guest)),
+            copy: None,
+            behind: false,
+        }
+    }
+
+    // Sends the joiner whatever they need to see `game`: nothing if they already
+    // do, the snakes' moves if that's all one tick changed, or else a snapshot.
+    fn send(&mut self, game: &Game) -> io::Result<()> {
+        let Some(guest) = &self.guest else {
+            return Ok(());
+        };
+        let state = state_of(game)?;
+        let mut message = None;
+        if let Some(copy) = &mut self.copy
+            && !self.behind
+        {
+            if state_of(copy)? == state {
+                return Ok(());
+            }
+            if game.tick == copy.tick + 1 && !game.tick.is_multiple_of(SNAPSHOT_EVERY) {
+                let moves: Vec<(Point, Direction)> = game
+                    .snakes
+                    .iter()
+                    .map(|snake| (snake.head(), snake.direction))
+                    .collect();
+                step(copy, &moves);
+                // Only if the moves really were all that changed.
+                if state_of(copy)? == state {
+                    message = Some(Message::Tick(moves));
+                }
+            }
+        }
+        let message = match message {
+            Some(message) => message,
+            None => {
+                let snapshot = Snapshot::from_game(game);
+                self.copy = Some(state_to_game(&state)?);
+                Message::Snapshot(Box::new(snapshot))
+            }
+        };
+
+        let mut line = serde_json::to_string(&message).map_err(io::Error::other)?;
+        line.push('\n');
+        match guest.try_send(line) {
+            Ok(()) => self.behind = false,
+            Err(TrySendError::Full(_)) => self.behind = true,
+            Err(TrySendError::Disconnected(_)) => self.guest = None,
+        }
+        Ok(())
+    }
+}
+
+impl<R: Renderer> Renderer for HostRenderer<R> {
+    fn draw(&mut self, game: &Game, message: Option<&str>) -> io::Result<()> {
+        self.inner.draw(game, message)?;
+        self.send(game)
+    }
+
+    fn beep(&mut self) -> io::Result<()> {
+        self.inner.beep()
+    }
+
+    fn suspend(&mut self) -> io::Result<()> {
+        self.inner.suspend()
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        self.inner.resume()
+    }
+
+    fn fits(&self, game: &Game) -> bool {
+        self.inner.fits(game)
+    }
+
+    fn draw_scores(&mut self, board: &scores::Leaderboard) -> io::Result<()> {
+        self.inner.draw_scores(board)
+    }
+}
+
+// The direction a key steers in with `keys`, if it steers at all.
+fn direction_of(keys: &KeyBindings, key: KeyEvent) -> Option<Direction> {
+    match keys.action(key)? {
+        Action::Up => Some(Direction::Up),
+        Action::Down => Some(Direction::Down),
+        Action::Left => Some(Direction::Left),
+        Action::Right => Some(Direction::Right),
+        // Pausing and restarting are up to the host.
+        Action::Pause | Action::Quit | Action::Restart => None,
+    }
+}
+
+// Applies one line from the host to the joiner's copy of the game.
+fn apply(game: &mut Option<Game>, line: &str) -> io::Result<()> {
+    let message = serde_json::from_str::<Message>(line)
+        .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+    match message {
+        Message::Snapshot(snapshot) => {
+            *game = Some(
+                snapshot
+                    .into_game()
+                    .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?,
+            );
+        }
+        // The host always starts with a snapshot, so there's a game to move.
+        Message::Tick(moves) => {
+            if let Some(game) = game {
+                step(game, &moves);
+            }
+        }
+    }
+    Ok(())
+}
+
+// `--join`: shows the host's game and sends this player's turns (steering with
+// `keys`), until they quit or the host goes away.
+pub fn join(
+    mut stream: TcpStream,
+    input: &mut impl Input,
+    renderer: &mut impl Renderer,
+    keys: &KeyBindings,
+) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let lines = read_lines(stream.try_clone()?);
+    let mut game = None;
+    loop {
+        if let Some(key) = input.poll(Duration::from_millis(10))? {
+            if keys.quits(key) {
+                return Ok(());
+            }
+            if let Some(direction) = direction_of(keys, key) {
+                writeln!(stream, "{}", direction.name())?;
+            }
+        }
+
+        // Apply everything that has arrived, then draw once.
+        let mut changed = false;
+        loop {
+            match lines.try_recv() {
+                Ok(line) => {
+                    apply(&mut game, &line)?;
+                    changed = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    return Err(io::Error::new(
+                        ErrorKind::ConnectionAborted,
+                        "the host left the game",
+                    ));
+                }
+            }
+        }
+        if changed && let Some(game) = &game {
+            renderer.draw(game, None)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_loop::testing::{HeadRecorder, NoKeys};
+    use crossterm::event::KeyCode;
+
+    #[test]
+    fn host_sends_state_and_takes_the_guests_turns() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut guest = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (host, _) = listener.accept().unwrap();
+
+        let mut game = Game::new(12, 8, None, Some(Point { x: 2, y: 2 })).unwrap();
+        game.add_mirror_snake().unwrap();
+        let mut renderer = HostRenderer::new(HeadRecorder::default(), host.try_clone().unwrap());
+        renderer.draw(&game, None).unwrap();
+        // Drawing the same frame again sends nothing new.
+        renderer.draw(&game, None).unwrap();
+        for _ in 0..3 {
+            game.update();
+            renderer.draw(&game, None).unwrap();
+        }
+
+        // The joiner gets the board, then just the snakes' moves, and ends up
+        // with the same board.
+        let mut reader = BufReader::new(guest.try_clone().unwrap());
+        let mut copy = None;
+        let mut kinds = Vec::new();
+        for _ in 0..4 {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            kinds.push(line.split('"').nth(1).unwrap().to_string());
+            apply(&mut copy, &line).unwrap();
+        }
+        assert_eq!(kinds, ["Snapshot", "Tick", "Tick", "Tick"]);
+        assert_eq!(state_of(&copy.unwrap()).unwrap(), state_of(&game).unwrap());
+
+        // And the joiner's turns steer player two.
+        let mut input = HostInput::new(NoKeys, &host).unwrap();
+        writeln!(guest, "UP\nnonsense\nLEFT").unwrap();
+        let mut turns = Vec::new();
+        for _ in 0..200 {
+            turns.extend(input.turns(1));
+            if turns.len() == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(turns, [(1, Direction::Up), (1, Direction::Left)]);
+    }
+
+    #[test]
+    fn the_joiner_steers_with_their_own_keys() {
+        let keys: KeyBindings = serde_json::from_str(r#"{ "up": ["k"] }"#).unwrap();
+        let press = |code| direction_of(&keys, KeyEvent::from(code));
+        assert_eq!(press(KeyCode::Char('k')), Some(Direction::Up));
+        assert_eq!(press(KeyCode::Up), None);
+        assert_eq!(press(KeyCode::Left), Some(Direction::Left));
+        assert_eq!(press(KeyCode::Char('q')), None);
+    }
+}
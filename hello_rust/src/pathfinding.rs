@@ -0,0 +1,122 @@
+// Breadth-first search ("BFS") over the board, shared by everything that needs
+// to know where the snake can get to.
+//
+// BFS explores outwards from a starting cell one step at a time, using a queue
+// so nearer cells are always visited before farther ones. That makes it both a
+// "flood fill" (which cells can I reach?) and a shortest-path finder.
+//
+// The functions here don't know about walls, obstacles or wrap-around. The
+// caller passes two closures instead:
+//   - `step(p, direction)` says which cell is one move from `p` (so wrapping works),
+//   - `is_free(p)` says whether the snake could move onto `p`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{Direction, Point};
+
+// The four ways the snake can move, in the order we try them.
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+// Every free cell reachable from `from` by moving through free cells.
+// `from` itself is only included if it's free and can be re-entered.
+pub fn reachable_cells(
+    from: Point,
+    step: impl Fn(Point, Direction) -> Point,
+    is_free: impl Fn(Point) -> bool,
+) -> HashSet<Point> {
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([from]);
+    while let Some(p) = queue.pop_front() {
+        for direction in DIRECTIONS {
+            let next = step(p, direction);
+            // `insert` returns false if we've already been here.
+            if is_free(next) && seen.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    seen
+}
+
+// A shortest route from `from` to `to` through free cells: the cells to move
+// onto, in order, ending with `to`. `None` if `to` can't be reached.
+pub fn bfs_path(
+    from: Point,
+    to: Point,
+    step: impl Fn(Point, Direction) -> Point,
+    is_free: impl Fn(Point) -> bool,
+) -> Option<Vec<Point>> {
+    // For every cell we reach, remember the cell we came from, so once we find
+    // `to` we can walk backwards to `from` to recover the route.
+    let mut came_from = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    while let Some(p) = queue.pop_front() {
+        if p == to && p != from {
+            let mut path = vec![p];
+            let mut current = p;
+            while let Some(&previous) = came_from.get(&current) {
+                if previous == from {
+                    break;
+                }
+                path.push(previous);
+                current = previous;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        for direction in DIRECTIONS {
+            let next = step(p, direction);
+            if next != from && is_free(next) && !came_from.contains_key(&next) {
+                came_from.insert(next, p);
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An open 5x5 grid with a wall of blocked cells at x = 2, except at y = 4.
+    fn is_free(p: Point) -> bool {
+        p.x < 5 && p.y < 5 && (p.x != 2 || p.y == 4)
+    }
+
+    fn point(x: u16, y: u16) -> Point {
+        Point { x, y }
+    }
+
+    #[test]
+    fn finds_the_shortest_way_around_a_wall() {
+        let path = bfs_path(point(0, 0), point(4, 0), Point::step, is_free).unwrap();
+        assert_eq!(path.len(), 12);
+        assert_eq!(path.first(), Some(&point(0, 1)));
+        assert_eq!(path.last(), Some(&point(4, 0)));
+        assert!(path.contains(&point(2, 4)));
+        // Every move is one step onto a free cell.
+        let mut previous = point(0, 0);
+        for &p in &path {
+            assert!(is_free(p));
+            assert_eq!(p.x.abs_diff(previous.x) + p.y.abs_diff(previous.y), 1);
+            previous = p;
+        }
+    }
+
+    #[test]
+    fn reports_unreachable_cells() {
+        let blocked = |p: Point| is_free(p) && p != point(2, 4);
+        assert_eq!(
+            bfs_path(point(0, 0), point(4, 0), Point::step, blocked),
+            None
+        );
+        assert_eq!(reachable_cells(point(0, 0), Point::step, blocked).len(), 10);
+        assert_eq!(reachable_cells(point(0, 0), Point::step, is_free).len(), 21);
+    }
+}
@@ -0,0 +1,102 @@
+// Frame timing log for performance tuning (`--perf-log path.csv`).
+//
+// Every frame we time the game update and the render (draw + flush), write one
+// CSV row per frame, and keep the numbers so a summary can be printed at exit.
+// That's usually enough to spot a slow terminal or a performance regression
+// without reaching for a real profiler.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+pub struct PerfLog {
+    // `BufWriter` collects small writes in memory and sends them to the file in
+    // big chunks, so logging doesn't slow down the very frames we're measuring.
+    out: BufWriter<File>,
+    updates: Vec<Duration>,
+    renders: Vec<Duration>,
+}
+
+impl PerfLog {
+    // Creates (or overwrites) the CSV file and writes the header row.
+    pub fn create(path: &Path) -> io::Result<PerfLog> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "frame,update_us,render_us")?;
+        Ok(PerfLog {
+            out,
+            updates: Vec::new(),
+            renders: Vec::new(),
+        })
+    }
+
+    // Records one frame's timings (in microseconds in the file).
+    pub fn record(&mut self, update: Duration, render: Duration) -> io::Result<()> {
+        writeln!(
+            self.out,
+            "{},{},{}",
+            self.renders.len(),
+            update.as_micros(),
+            render.as_micros()
+        )?;
+        self.updates.push(update);
+        self.renders.push(render);
+        Ok(())
+    }
+
+    // A short human-readable report, e.g. for printing when the game exits.
+    pub fn summary(&self) -> String {
+        format!(
+            "Frames: {}\n{}\n{}\n",
+            self.renders.len(),
+            stats_line("update", &self.updates),
+            stats_line("render", &self.renders)
+        )
+    }
+}
+
+// Makes sure everything buffered reaches the file even if nobody asks for it.
+impl Drop for PerfLog {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
+
+// One line of min/max/average/99th-percentile timings, in milliseconds.
+pub fn stats_line(label: &str, samples: &[Duration]) -> String {
+    if samples.is_empty() {
+        return format!("{}: no samples", label);
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    let total: Duration = sorted.iter().sum();
+    let average = total / sorted.len() as u32;
+    // The 99th percentile: 99% of frames were at least this fast.
+    let p99 = sorted[(sorted.len() * 99).div_ceil(100) - 1];
+    format!(
+        "{}: min {:.3}ms  max {:.3}ms  avg {:.3}ms  p99 {:.3}ms",
+        label,
+        ms(sorted[0]),
+        ms(sorted[sorted.len() - 1]),
+        ms(average),
+        ms(p99)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_timings() {
+        let samples: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(
+            stats_line("render", &samples),
+            "render: min 1.000ms  max 100.000ms  avg 50.500ms  p99 99.000ms"
+        );
+        assert_eq!(stats_line("update", &[]), "update: no samples");
+    }
+}
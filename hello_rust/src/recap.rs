@@ -0,0 +1,156 @@
+// The post-game recap (`--recap`): a small, sped-up replay of the run just
+// played, shown on the game over screen above the final score.
+//
+// While the game runs we keep a scaled-down picture of the board every few
+// ticks. A long game would pile up a lot of these, so once there are
+// `MAX_FRAMES` we throw away every other one and keep pictures half as often
+// from then on. However long the run, the recap stays the same size in memory
+// and still covers all of it, start to finish.
+
+use std::time::Duration;
+
+// The most pictures kept at once.
+const MAX_FRAMES: usize = 128;
+
+// How long each picture is shown for when playing the recap back.
+const FRAME_TIME: Duration = Duration::from_millis(60);
+
+// How long the last picture stays up before the recap starts over.
+const HOLD_TIME: Duration = Duration::from_secs(1);
+
+// Each cell of a picture stands for a `SCALE` x `SCALE` block of the board.
+const SCALE: usize = 2;
+
+// One picture: rows of `'o'` (snake), `'*'` (food), `'#'` (wall or obstacle) and
+// `' '` (empty floor).
+pub type Frame = Vec<String>;
+
+#[derive(Debug)]
+pub struct Recap {
+    frames: Vec<Frame>,
+    // A picture is kept every `every` ticks; this doubles each time we thin them out.
+    every: u64,
+    // The tick of the last picture kept, so the same tick is never kept twice.
+    last_tick: u64,
+}
+
+impl Default for Recap {
+    fn default() -> Self {
+        Recap {
+            frames: Vec::new(),
+            every: 1,
+            last_tick: 0,
+        }
+    }
+}
+
+impl Recap {
+    // Does tick `tick` need a picture? Every `every`th tick does, and so does the
+    // last one (`over`), so the recap always ends on how the game ended.
+    pub fn due(&self, tick: u64, over: bool) -> bool {
+        tick > self.last_tick && (over || tick.is_multiple_of(self.every))
+    }
+
+    // Keeps `frame` as the picture for `tick`, thinning out the older ones if
+    // there are too many.
+    pub fn push(&mut self, tick: u64, frame: Frame) {
+        self.frames.push(frame);
+        self.last_tick = tick;
+        if self.frames.len() > MAX_FRAMES {
+            // Pictures were kept on ticks `every`, `2 * every`, `3 * every`...; keep
+            // the ones on even multiples, so the gaps stay even at `2 * every`.
+            self.frames = std::mem::take(&mut self.frames)
+                .into_iter()
+                .skip(1)
+                .step_by(2)
+                .collect();
+            self.every *= 2;
+        }
+    }
+
+    // The picture to show `since_end` after the game ended: the recap plays
+    // through, holds on the last picture for a moment, then starts over.
+    pub fn frame_at(&self, since_end: Duration) -> Option<&Frame> {
+        let last = self.frames.len().checked_sub(1)?;
+        let cycle = FRAME_TIME * last as u32 + HOLD_TIME;
+        let into_cycle = since_end.as_millis() % cycle.as_millis();
+        let index = (into_cycle / FRAME_TIME.as_millis()) as usize;
+        self.frames.get(index.min(last))
+    }
+}
+
+// Shrinks a board drawn by `Game::render_ascii` down to a picture, one cell per
+// `SCALE` x `SCALE` block. A block shows the most important thing in it: snake
+// first, then food, then walls.
+pub fn thumbnail(ascii: &str) -> Frame {
+    let rows: Vec<Vec<char>> = ascii.lines().map(|line| line.chars().collect()).collect();
+    rows.chunks(SCALE)
+        .map(|band| {
+            let width = band[0].len();
+            (0..width)
+                .step_by(SCALE)
+                .map(|left| {
+                    let block = band
+                        .iter()
+                        .flat_map(|row| row.iter().skip(left).take(SCALE));
+                    let mut cell = ' ';
+                    for &tile in block {
+                        cell = match tile {
+                            'O' | 'o' => return 'o',
+                            '*' => '*',
+                            '#' | 'X' | '^' | 'v' | '<' | '>' if cell == ' ' => '#',
+                            _ => cell,
+                        };
+                    }
+                    cell
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thumbnails_keep_what_matters_in_each_block() {
+        let ascii = "######\n#..*X#\n#oO..#\n######\n";
+        assert_eq!(thumbnail(ascii), ["#*#", "oo#"]);
+    }
+
+    #[test]
+    fn long_runs_are_thinned_out_evenly() {
+        let mut recap = Recap::default();
+        assert_eq!(recap.frame_at(Duration::ZERO), None);
+        for tick in 1..=1000 {
+            if recap.due(tick, false) {
+                recap.push(tick, vec![tick.to_string()]);
+            }
+        }
+        assert!(recap.frames.len() <= MAX_FRAMES);
+        // Evenly spaced from the start: 8, 16, 24...
+        let ticks: Vec<u64> = recap
+            .frames
+            .iter()
+            .map(|frame| frame[0].parse().unwrap())
+            .collect();
+        assert!(
+            ticks
+                .windows(2)
+                .all(|pair| pair[1] - pair[0] == recap.every)
+        );
+        assert_eq!(ticks[0], recap.every);
+
+        // The last tick is always kept, but only once.
+        assert!(recap.due(1001, true));
+        recap.push(1001, vec!["end".to_string()]);
+        assert!(!recap.due(1001, true));
+
+        // Playback starts at the first picture and holds on the last.
+        assert_eq!(recap.frame_at(Duration::ZERO), Some(&recap.frames[0]));
+        let end = FRAME_TIME * (recap.frames.len() as u32 - 1);
+        assert_eq!(recap.frame_at(end + HOLD_TIME / 2).unwrap()[0], "end");
+        assert_eq!(recap.frame_at(end + HOLD_TIME), Some(&recap.frames[0]));
+    }
+}
@@ -0,0 +1,961 @@
+// Drawing the game on the terminal.
+//
+// The game's rules live in the library (`lib.rs`), which knows nothing about
+// terminals. Rust only lets the crate that defines a type add methods to it
+// directly, so the drawing methods come in a trait instead: `Draw`, implemented
+// here for `Game`. With `use crate::render::Draw;` in scope, `game.draw(..)` works
+// just like a method defined on `Game` itself.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    time::Duration,
+};
+
+use crossterm::{
+    QueueableCommand,
+    cursor::MoveTo,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+};
+
+use crate::{
+    crt,
+    keys::{Action, KeyBindings},
+    theme,
+};
+use hello_rust::*;
+
+// `--show-regions`: the shades regions are drawn in, reused in turn if there are more regions.
+const REGION_COLORS: [Color; 6] = [
+    Color::DarkBlue,
+    Color::DarkGreen,
+    Color::DarkMagenta,
+    Color::DarkCyan,
+    Color::DarkYellow,
+    Color::DarkRed,
+];
+
+// The game over screen's color for a new record (see `result_color`).
+const GOLD: Color = Color::Rgb { r: 255, g: 200, b: 0 };
+
+// The side panel's width, border included.
+pub const PANEL_WIDTH: u16 = 24;
+
+pub trait Draw {
+    fn move_to(&self, p: Point) -> MoveTo;
+    fn cell_at(&self, p: Point) -> crt::CellAt;
+    fn speed_tint(&self) -> Option<Color>;
+    fn draw(&self, stdout: &mut impl Write) -> io::Result<()>;
+    fn draw_pause_panel(&self, stdout: &mut impl Write) -> io::Result<()>;
+    fn draw_game_over(&self, stdout: &mut impl Write, hint: &str) -> io::Result<()>;
+    fn draw_panel(&self, stdout: &mut impl Write) -> io::Result<()>;
+    fn wall_glyph(&self, p: Point) -> char;
+    fn theme(&self) -> &'static theme::Theme;
+    fn cycle_theme(&mut self);
+    fn obstacle_color(&self) -> Color;
+}
+
+impl Draw for Game {
+    // --- Mirror mode ---
+    // The game itself always works in "canonical" coordinates; only drawing and
+    // the arrow keys see the flipped board. That way collisions, food spawning
+    // and everything else never need to know about flips at all.
+
+    // Where board cell `p` appears on screen, after any mirror flips.
+    fn move_to(&self, p: Point) -> MoveTo {
+        let (flip_x, flip_y) = self.flip;
+        let x = if flip_x { self.width - 1 - p.x } else { p.x };
+        let y = if flip_y { self.height - 1 - p.y } else { p.y };
+        MoveTo(x, y)
+    }
+
+    // Moves to board cell `p` for drawing, like `move_to`, keeping the cell's
+    // scanline background in `--crt` mode.
+    fn cell_at(&self, p: Point) -> crt::CellAt {
+        let MoveTo(x, y) = self.move_to(p);
+        crt::CellAt::new(x, y, self.settings.crt)
+    }
+
+    // `--speed-tint`: the snake's color for its current speed, from blue at its
+    // slowest (slow motion) through green and yellow to red at its fastest.
+    // `None`, meaning the theme's usual color, while the speed can't change:
+    // without acceleration, a speed curve or slow motion, there's nothing to show.
+    fn speed_tint(&self) -> Option<Color> {
+        let variable = self.settings.accelerate
+            || self.settings.speed_curve != SpeedCurve::Flat
+            || !self.slow_motion.is_zero()
+            || !self.boost.is_zero();
+        if !self.settings.speed_tint || !variable {
+            return None;
+        }
+        let (fastest, slowest) = (MIN_TICK_RATE, self.settings.tick_rate * SLOW_MOTION_FACTOR);
+        let tick = self.effective_tick_rate().clamp(fastest, slowest);
+        // 0.0 at the fastest, 1.0 at the slowest.
+        let slowness = (tick - fastest).as_secs_f32() / (slowest - fastest).as_secs_f32();
+        Some(hue_to_rgb(240.0 * slowness))
+    }
+
+    // Render the current state to the terminal using buffered output.
+    fn draw(&self, stdout: &mut impl Write) -> io::Result<()> {
+        // Draw Borders
+        // Queueing commands is faster than printing immediately.
+        let theme = self.theme();
+        stdout.queue(SetForegroundColor(theme.wall))?;
+        
+        // Every cell outside the playable area is wall (more than one cell thick with `--border`).
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let p = Point { x, y };
+                if !self.in_bounds(p) {
+                    stdout.queue(MoveTo(x, y))?.queue(Print(self.wall_glyph(p)))?;
+                }
+            }
+        }
+
+        // `--crt`: darken every other row inside the walls. Cells drawn from here on
+        // use `cell_at`, which keeps their row's background (see `crt.rs`).
+        if self.settings.crt {
+            let inside = " ".repeat((self.width - 2 * self.border) as usize);
+            for y in (self.border..self.height - self.border).filter(|y| y % 2 == 1) {
+                stdout
+                    .queue(MoveTo(self.border, y))?
+                    .queue(SetBackgroundColor(crt::SCANLINE))?
+                    .queue(Print(&inside))?;
+            }
+            stdout.queue(SetBackgroundColor(Color::Reset))?;
+        }
+
+        // `--show-regions`: shade the floor by region, under everything else.
+        let regions = if self.settings.show_regions { self.regions() } else { HashMap::new() };
+        for (&cell, &region) in &regions {
+            stdout
+                .queue(SetForegroundColor(REGION_COLORS[region % REGION_COLORS.len()]))?
+                .queue(self.cell_at(cell))?
+                .queue(Print("░"))?;
+        }
+
+        // `--local-grid`: faint dots around the head. Every frame starts from a cleared
+        // screen, so the dots left behind as the head moves on are simply not redrawn.
+        if self.settings.local_grid {
+            stdout.queue(SetForegroundColor(Color::DarkGrey))?;
+            for cell in self.local_grid() {
+                stdout.queue(self.cell_at(cell))?.queue(Print("·"))?;
+            }
+        }
+
+        // `--hint`: mark the greedy move and the safe move (or both in one, when
+        // they agree). The food draws over a marker on its cell.
+        if self.settings.hint {
+            let (greedy, safe) = self.hints();
+            let markers = if greedy == safe {
+                vec![(greedy, "◆", Color::White)]
+            } else {
+                vec![(greedy, "◆", Color::Yellow), (safe, "◇", Color::Cyan)]
+            };
+            for (cell, marker, color) in markers {
+                if let Some(cell) = cell {
+                    stdout
+                        .queue(SetForegroundColor(color))?
+                        .queue(self.cell_at(cell))?
+                        .queue(Print(marker))?;
+                }
+            }
+        }
+
+        // Draw painted cells first (dimly), so everything else draws over them.
+        if self.settings.paint {
+            stdout.queue(SetForegroundColor(theme.paint))?;
+            for cell in &self.painted {
+                stdout.queue(self.cell_at(*cell))?.queue(Print("░"))?;
+            }
+            let status = format!("Paint {}/{}%", self.painted_percent(), self.settings.paint_target);
+            stdout
+                .queue(SetForegroundColor(Color::Blue))?
+                .queue(MoveTo(self.width.saturating_sub(status.len() as u16), self.height))?
+                .queue(Print(status))?;
+        }
+
+        // Draw Obstacles (The generated walls), tinted by level.
+        stdout.queue(SetForegroundColor(self.obstacle_color()))?;
+        for obstacle in self.obstacles() {
+            // One-way gates show the way they can be crossed (as drawn, so flipped in mirror mode).
+            let glyph = match self.gates.get(&obstacle) {
+                Some(&direction) => self.unflip(direction).arrow(),
+                None => "▓", // Use a different character for inner walls
+            };
+            stdout.queue(self.cell_at(obstacle))?.queue(Print(glyph))?;
+        }
+
+        // The HUD is on the wall, so no scanline background for it.
+        stdout.queue(SetBackgroundColor(Color::Reset))?;
+
+        // Draw Score and Level
+        stdout.queue(MoveTo(2, 0))?;
+        if self.settings.versus {
+            // Versus mode: each player's own score instead of the shared one.
+            let scores: Vec<String> = self
+                .snakes
+                .iter()
+                .enumerate()
+                .map(|(i, snake)| format!("P{}: {}", i + 1, snake.apples))
+                .collect();
+            stdout.queue(Print(format!(" {}  Level: {} ", scores.join("  "), self.level)))?;
+        } else {
+            stdout.queue(Print(format!(" Score: {}  Level: {} ", self.score, self.level)))?;
+        }
+        // `--show-length`: the snake's actual size, which isn't always the score
+        // (paint mode scores without growing, bombs don't count, ...). In co-op: "5/7".
+        if self.settings.show_length {
+            let lengths: Vec<String> = self.snakes.iter().map(|s| s.body.len().to_string()).collect();
+            stdout.queue(Print(format!("Length: {} ", lengths.join("/"))))?;
+        }
+        // Puzzle mode: which food this is, and the moves left to eat the rest.
+        if let Some(puzzle) = &self.puzzle {
+            let current = (puzzle.eaten + 1).min(puzzle.food.len());
+            let progress = format!("Food {}/{}  Moves: {} ", current, puzzle.food.len(), puzzle.moves_left);
+            stdout.queue(Print(progress))?;
+        }
+        // `--scaled-score`: what the score will be worth on the leaderboard.
+        if self.settings.scaled_score {
+            stdout.queue(Print(format!("×{:.2} ", self.score_multiplier())))?;
+        }
+        // Endless mode never wins on a full board; say so, and say when there's no food left.
+        if self.settings.endless {
+            let label = if self.snake_at(self.food) { "∞ board full " } else { "∞ " };
+            stdout.queue(Print(label))?;
+        }
+        // How many regions the floor is split into (1 means fully connected).
+        if self.settings.show_regions {
+            let count = regions.values().max().map_or(0, |&last| last + 1);
+            stdout.queue(Print(format!("Regions: {} ", count)))?;
+        }
+        // Shields as a bar: one block per shield, filled while it's ready.
+        if self.settings.shields > 0 {
+            let ready = self.shields as usize;
+            let spent = (self.settings.shields - self.shields) as usize;
+            stdout.queue(Print(format!("Shields {}{} ", "■".repeat(ready), "□".repeat(spent))))?;
+        }
+        // Slow motion from an hourglass, and the timed power-ups, counting down.
+        if !self.slow_motion.is_zero() {
+            stdout.queue(Print(format!("Slow-mo {:.1}s ", self.slow_motion.as_secs_f32())))?;
+        }
+        if !self.boost.is_zero() {
+            stdout.queue(Print(format!("Boost {:.1}s ", self.boost.as_secs_f32())))?;
+        }
+        if !self.double_points.is_zero() {
+            stdout.queue(Print(format!("x2 {:.1}s ", self.double_points.as_secs_f32())))?;
+        }
+        // The turns waiting to be made, so a turn that comes a tick late makes sense.
+        if self.settings.show_queue
+            && let Some(queue) = self.queue_indicator()
+        {
+            stdout.queue(Print(queue))?;
+        }
+
+        // Show how the walls behave (top-right), counting down after wrap is switched off.
+        let walls = match (self.settings.wrap, self.wrap_grace) {
+            (true, _) => " Walls: wrap ".to_string(),
+            (false, 0) => " Walls: solid ".to_string(),
+            (false, ticks) => format!(" Walls: solid in {} ", ticks),
+        };
+        stdout
+            .queue(MoveTo(self.width.saturating_sub(walls.len() as u16 + 2), 0))?
+            .queue(Print(walls))?;
+
+        // Draw the speedrun timer on the bottom wall (right-aligned).
+        let timer = format!(" {} ", format_time(self.elapsed()));
+        stdout
+            .queue(MoveTo(self.width.saturating_sub(timer.len() as u16 + 2), self.height - 1))?
+            .queue(Print(timer))?;
+
+        // The saved best score on the bottom wall (left), where it can't run into
+        // the walls label on a narrow board. Versus mode has no shared score to beat.
+        stdout.queue(MoveTo(2, self.height - 1))?;
+        if !self.settings.versus {
+            stdout.queue(Print(format!(" Best: {} ", self.best)))?;
+        }
+
+        // Then the speed gauge: one bar per few ticks of acceleration.
+        if self.settings.accelerate {
+            let filled = (self.snake().straight_ticks.min(MAX_ACCEL_TICKS) / 3) as usize;
+            let gauge = format!(" Speed {}{} ", "▮".repeat(filled), "▯".repeat(5 - filled));
+            stdout.queue(Print(gauge))?;
+        }
+
+        // Survival waves: the wave number on the bottom wall (centered), replaced by a
+        // warning shortly before the next surge.
+        if self.settings.waves {
+            let (label, color) = if self.wave_timer <= WAVE_WARNING {
+                (" WAVE INCOMING ".to_string(), Color::Red)
+            } else {
+                (format!(" Wave {} ", self.wave), Color::Yellow)
+            };
+            stdout
+                .queue(SetForegroundColor(color))?
+                .queue(MoveTo((self.width / 2).saturating_sub(label.len() as u16 / 2), self.height - 1))?
+                .queue(Print(label))?;
+        }
+
+        // Draw the time attack clock as a bar on the line below the board.
+        if self.settings.time_attack {
+            const BAR_WIDTH: u128 = 20;
+            let filled = (self.time_left.as_millis() * BAR_WIDTH / TIME_ATTACK_START.as_millis())
+                .min(BAR_WIDTH) as usize;
+            let color = if self.time_left < Duration::from_secs(5) { Color::Red } else { Color::Yellow };
+            stdout
+                .queue(MoveTo(0, self.height))?
+                .queue(SetForegroundColor(color))?
+                .queue(Print(format!(
+                    "Time {}{} {:.1}s",
+                    "█".repeat(filled),
+                    "░".repeat(BAR_WIDTH as usize - filled),
+                    self.time_left.as_secs_f32()
+                )))?;
+        }
+
+        // Draw Food (slowly shifting hue in rainbow mode, 10 degrees per tick).
+        let food_color = if self.settings.rainbow_food {
+            hue_to_rgb((self.tick * 10 % 360) as f32)
+        } else {
+            theme.food
+        };
+        stdout
+            .queue(SetForegroundColor(food_color))?
+            .queue(self.cell_at(self.food))?
+            .queue(Print("●"))?;
+
+        // Puzzle mode: the food still to come, faintly, numbered in order.
+        if let Some(puzzle) = &self.puzzle {
+            stdout.queue(SetForegroundColor(Color::DarkGrey))?;
+            for (i, &food) in puzzle.food.iter().enumerate().skip(puzzle.eaten + 1) {
+                if !self.snake_at(food) {
+                    stdout.queue(self.cell_at(food))?.queue(Print(i + 1))?;
+                }
+            }
+        }
+
+        // Draw the bomb, if there is one.
+        if let Some(bomb) = self.bomb {
+            stdout
+                .queue(SetForegroundColor(theme.bomb))?
+                .queue(self.cell_at(bomb))?
+                .queue(Print("¤"))?;
+        }
+
+        // Draw the hourglass, if there is one.
+        if let Some(hourglass) = self.hourglass {
+            stdout
+                .queue(SetForegroundColor(theme.hourglass))?
+                .queue(self.cell_at(hourglass))?
+                .queue(Print("⧖"))?;
+        }
+
+        // Draw the power-up, if there is one: » for a boost, ÷ for a shrink, $ for double points.
+        if let Some((at, kind)) = self.power_up {
+            let glyph = match kind {
+                PowerUp::Boost => "»",
+                PowerUp::Shrink => "÷",
+                PowerUp::DoublePoints => "$",
+            };
+            stdout
+                .queue(SetForegroundColor(theme.power_ups[kind as usize]))?
+                .queue(self.cell_at(at))?
+                .queue(Print(glyph))?;
+        }
+
+        // Draw the reverser, if there is one.
+        if let Some(reverser) = self.reverser {
+            stdout
+                .queue(SetForegroundColor(theme.reverser))?
+                .queue(self.cell_at(reverser))?
+                .queue(Print("⇄"))?;
+        }
+
+        // Draw the snakes, each in its own color (player one is green in the classic theme),
+        // or all in the color for the current speed with `--speed-tint`.
+        let tint = self.speed_tint();
+        for (snake, color) in self.snakes.iter().zip(theme.snakes) {
+            let color = tint.unwrap_or(color);
+            stdout.queue(SetForegroundColor(color))?;
+            for (i, point) in snake.body.iter().enumerate() {
+                stdout.queue(self.cell_at(*point))?;
+                // The segment the eating pulse is on is drawn brighter.
+                let pulsing = snake.pulse == Some(i);
+                if pulsing {
+                    stdout.queue(SetForegroundColor(brighter(color)))?;
+                }
+                if i == 0 && snake.crashed {
+                    stdout.queue(Print("X"))?; // Versus mode: this snake is out
+                } else if i == 0 {
+                    stdout.queue(Print("O"))?; // Head
+                } else if self.settings.ghost {
+                    stdout.queue(Print("·"))?; // See-through body, so you can tell you're a ghost
+                } else {
+                    stdout.queue(Print("o"))?; // Body
+                }
+                if pulsing {
+                    stdout.queue(SetForegroundColor(color))?;
+                }
+            }
+        }
+
+        // Toasts go over everything else on the board, just above where their event
+        // happened (kept inside the walls), dimming in their second half.
+        for toast in &self.toasts {
+            let MoveTo(x, y) = self.move_to(toast.pos);
+            let len = toast.text.chars().count() as u16;
+            let x = x.saturating_sub(len / 2).clamp(1, self.width.saturating_sub(len + 1).max(1));
+            let y = y.saturating_sub(1).max(1);
+            let shown = self.clock.now().duration_since(toast.since);
+            let color = if shown < TOAST_TIME / 2 { Color::White } else { Color::DarkGrey };
+            stdout
+                .queue(SetForegroundColor(color))?
+                .queue(crt::CellAt::new(x, y, self.settings.crt))?
+                .queue(Print(&toast.text))?;
+        }
+        
+        if self.paused {
+            stdout.queue(SetBackgroundColor(Color::Reset))?;
+            self.draw_pause_panel(stdout)?;
+        }
+
+        // Reset color to default so we don't mess up the terminal
+        stdout.queue(ResetColor)?;
+        Ok(())
+    }
+
+    // Draws a box over the middle of the board while paused.
+    // In debug mode it doubles as an analysis tool, showing metrics about the board.
+    fn draw_pause_panel(&self, stdout: &mut impl Write) -> io::Result<()> {
+        let mut lines = vec![
+            "PAUSED".to_string(),
+            "P or Space to resume".to_string(),
+            format!("Theme: {} (t)", self.theme().name),
+        ];
+        if self.settings.ghost {
+            lines.push("Ghost: on (g)".to_string());
+        }
+        if self.settings.debug {
+            // "Win progress" is how much of the open floor the snake already fills.
+            let open_floor = self.interior_capacity() - self.obstacles.len();
+            let snake_cells: usize = self.snakes.iter().map(|s| s.body.len()).sum();
+            let progress = snake_cells * 100 / open_floor.max(1);
+            let yes_no = |b: bool| if b { "yes" } else { "no" };
+            lines.push(String::new());
+            lines.push(format!("Free cells:  {}", self.free_cell_count()));
+            lines.push(format!("Obstacles:   {}", self.obstacles.len()));
+            lines.push(format!("Length:      {}", self.snake().body.len()));
+            lines.push(format!("Progress:    {}%", progress));
+            lines.push(format!("Food reach.: {}", yes_no(self.is_food_reachable())));
+            let distance = self.food_distance().map_or("-".to_string(), |d| d.to_string());
+            lines.push(format!("Food dist.:  {}", distance));
+            lines.push(format!("Trapped:     {}", yes_no(self.is_trapped())));
+        }
+
+        // Size the box to the longest line, plus a space of padding on each side.
+        let inner = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u16 + 2;
+        let left = (self.width / 2).saturating_sub(inner / 2);
+        let top = (self.height / 2).saturating_sub(lines.len() as u16 / 2);
+
+        stdout.queue(SetForegroundColor(Color::White))?;
+        for (i, line) in lines.iter().enumerate() {
+            stdout
+                .queue(MoveTo(left, top + i as u16))?
+                .queue(Print(format!(" {:<width$} ", line, width = inner as usize - 2)))?;
+        }
+        Ok(())
+    }
+
+    // Draws the game over (or win) screen: the result, final score and time in the
+    // middle of the board, and how to carry on. With `--recap`, the replay of the
+    // run plays above them, if the board is tall enough for both. `hint` is the
+    // line that says how to carry on (see `game_over_hint`).
+    // The result is colored by how the run compares with the best score from
+    // before it (see `result_color`), or just red or green with `--no-score-colors`.
+    fn draw_game_over(&self, stdout: &mut impl Write, hint: &str) -> io::Result<()> {
+        let out_of_moves = self.puzzle.as_ref().is_some_and(|puzzle| puzzle.moves_left == 0);
+        let msg = if self.settings.versus {
+            match self.versus_winner() {
+                Some(i) => format!("PLAYER {} WINS!", i + 1),
+                None => "DRAW".to_string(),
+            }
+        } else if self.game_won {
+            "YOU WIN!".to_string()
+        } else if out_of_moves {
+            "OUT OF MOVES".to_string()
+        } else {
+            "GAME OVER".to_string()
+        };
+        let score_msg = if self.settings.versus {
+            let scores: Vec<String> = self.snakes.iter().map(|snake| snake.apples.to_string()).collect();
+            format!("Final Score: {}", scores.join(" - "))
+        } else {
+            format!("Final Score: {}", self.score())
+        };
+        let time_msg = format!("Time: {}", format_time(self.elapsed()));
+        let best_msg = format!("Best: {}", self.best);
+
+        let (width, height) = self.dimensions();
+        let center_x = width / 2;
+        let center_y = height / 2;
+        // The row for the first line of text.
+        let mut top = center_y - 1;
+
+        let frame = match self.ended_at {
+            Some(ended) if self.settings.recap => self.recap.frame_at(self.clock.now().duration_since(ended)),
+            _ => None,
+        };
+        // The recap goes from the second row down, with a blank row and the six rows
+        // of text under it.
+        if let Some(frame) = frame
+            && frame.len() as u16 + 8 <= height
+        {
+            let theme = self.theme();
+            for (row, line) in frame.iter().enumerate() {
+                let left = center_x.saturating_sub(line.len() as u16 / 2);
+                stdout.queue(MoveTo(left, 1 + row as u16))?;
+                for cell in line.chars() {
+                    let (symbol, color) = match cell {
+                        'o' => ("█", theme.snakes[0]),
+                        '*' => ("●", theme.food),
+                        '#' => ("▒", theme.wall),
+                        _ => (" ", Color::Reset),
+                    };
+                    stdout.queue(SetForegroundColor(color))?.queue(Print(symbol))?;
+                }
+            }
+            top = frame.len() as u16 + 2;
+        }
+
+        let score = self.leaderboard_score();
+        let accent = if self.settings.score_colors {
+            result_color(score, self.best)
+        } else if self.game_won {
+            Color::Green
+        } else {
+            Color::Red
+        };
+
+        // Center the text (starting at the left edge if it's wider than a small map).
+        stdout.queue(SetForegroundColor(accent))?;
+        stdout.queue(MoveTo(center_x.saturating_sub(msg.len() as u16 / 2), top))?;
+        stdout.queue(Print(msg))?;
+        if self.settings.score_colors && score > self.best {
+            let record_msg = "NEW RECORD!";
+            stdout.queue(MoveTo(center_x.saturating_sub(record_msg.len() as u16 / 2), top + 1))?;
+            stdout.queue(Print(record_msg))?;
+        }
+
+        stdout.queue(SetForegroundColor(Color::White))?;
+        stdout.queue(MoveTo(center_x.saturating_sub(score_msg.len() as u16 / 2), top + 2))?;
+        stdout.queue(Print(score_msg))?;
+
+        stdout.queue(MoveTo(center_x.saturating_sub(time_msg.len() as u16 / 2), top + 3))?;
+        stdout.queue(Print(time_msg))?;
+
+        stdout.queue(MoveTo(center_x.saturating_sub(best_msg.len() as u16 / 2), top + 4))?;
+        stdout.queue(Print(best_msg))?;
+
+        stdout.queue(MoveTo(center_x.saturating_sub(hint.chars().count() as u16 / 2), top + 5))?;
+        stdout.queue(Print(hint))?;
+        stdout.queue(ResetColor)?;
+        Ok(())
+    }
+
+    // Draws the side panel just right of the board: the stats at the top and the
+    // message log underneath.
+    // The caller checks the terminal is wide enough first.
+    fn draw_panel(&self, stdout: &mut impl Write) -> io::Result<()> {
+        let lengths: Vec<String> = self.snakes.iter().map(|s| s.body.len().to_string()).collect();
+        let mut lines = vec![
+            format!("Score:  {}", self.score),
+            format!("Level:  {}", self.level),
+            format!("Length: {}", lengths.join("/")),
+            format!("Best:   {}", self.best.max(self.score)),
+            String::new(),
+        ];
+        lines.extend(self.messages.iter().map(|(text, _)| text.clone()));
+
+        let left = self.width + 1;
+        let inner = PANEL_WIDTH as usize - 2;
+        stdout
+            .queue(SetForegroundColor(self.theme().wall))?
+            .queue(MoveTo(left, 0))?
+            .queue(Print(format!("┌{}┐", "─".repeat(inner))))?;
+        for row in 1..self.height - 1 {
+            let line = lines.get(row as usize - 1).map_or("", String::as_str);
+            // `{:<w$.w$}` pads short lines and cuts long ones, so the box stays square.
+            stdout
+                .queue(MoveTo(left, row))?
+                .queue(Print(format!("│ {:<w$.w$}│", line, w = inner - 1)))?;
+        }
+        stdout
+            .queue(MoveTo(left, self.height - 1))?
+            .queue(Print(format!("└{}┘", "─".repeat(inner))))?
+            .queue(ResetColor)?;
+        Ok(())
+    }
+
+    // The theme's character for the wall cell `p`: a corner glyph where the top or
+    // bottom wall meets a side, otherwise the glyph for the edge it's on. A glyph
+    // that isn't one column wide would throw the board out of line, so those are
+    // drawn solid instead.
+    fn wall_glyph(&self, p: Point) -> char {
+        let walls = &self.theme().walls;
+        let (xs, ys) = self.interior_bounds();
+        let side = p.x < *xs.start() || p.x > *xs.end();
+        let top_or_bottom = p.y < *ys.start() || p.y > *ys.end();
+        let glyph = match (side, top_or_bottom) {
+            (true, true) => {
+                let right = p.x > *xs.end();
+                let bottom = p.y > *ys.end();
+                walls.corners[usize::from(bottom) * 2 + usize::from(right)]
+            }
+            (false, true) => walls.top_bottom,
+            _ => walls.sides,
+        };
+        if theme::is_single_width(glyph) { glyph } else { '█' }
+    }
+
+    // The color theme in use.
+    fn theme(&self) -> &'static theme::Theme {
+        &theme::THEMES[self.theme]
+    }
+
+    // Switches to the next color theme, wrapping around after the last one.
+    fn cycle_theme(&mut self) {
+        self.theme = (self.theme + 1) % theme::THEMES.len();
+    }
+
+    // The obstacle color for the current level, so each level looks a little different.
+    fn obstacle_color(&self) -> Color {
+        let palette = self.theme().obstacles;
+        palette[(self.level as usize - 1) % palette.len()]
+    }
+}
+
+// The game over screen's line about how to carry on, naming the keys that are
+// bound to retry and quit, like "R to Retry, L for Scores, Q to Quit".
+// Ctrl+C quits whatever the bindings say, so that's named if nothing else is.
+pub fn game_over_hint(keys: &KeyBindings) -> String {
+    let quit = keys.label(Action::Quit).unwrap_or_else(|| "Ctrl+C".to_string());
+    match keys.label(Action::Restart) {
+        Some(retry) => format!("{} to Retry, L for Scores, {} to Quit", retry, quit),
+        None => format!("L for Scores, {} to Quit", quit),
+    }
+}
+
+// The game over screen's color for a final `score`, against
+// `best`, the high score before this run. Gold for a new record, green for at
+// least half the record, grey for anything less.
+fn result_color(score: usize, best: usize) -> Color {
+    if score > best {
+        GOLD
+    } else if score * 2 >= best && score > 0 {
+        Color::Green
+    } else {
+        Color::DarkGrey
+    }
+}
+
+// Converts a hue (an angle on the color wheel, 0-360 degrees) to a fully bright,
+// fully saturated RGB color. Red is at 0, green at 120 and blue at 240, so
+// `240.0 * x` runs from red (x = 0.0) to blue (x = 1.0).
+pub fn hue_to_rgb(h: f32) -> Color {
+    let h = h.rem_euclid(360.0) / 60.0; // Which sixth of the wheel we're in (0.0..6.0).
+    let rising = (h.fract() * 255.0) as u8; // Ramps 0 -> 255 across the sixth.
+    let falling = 255 - rising;
+    let (r, g, b) = match h as u8 {
+        0 => (255, rising, 0),
+        1 => (falling, 255, 0),
+        2 => (0, 255, rising),
+        3 => (0, falling, 255),
+        4 => (rising, 0, 255),
+        _ => (255, 0, falling),
+    };
+    Color::Rgb { r, g, b }
+}
+
+// A brighter version of `color`, for the eating pulse: RGB colors are mixed
+// halfway to white, and the named colors (already bright) just become white.
+fn brighter(color: Color) -> Color {
+    match color {
+        Color::Rgb { r, g, b } => {
+            let lift = |c: u8| c + (255 - c) / 2;
+            Color::Rgb { r: lift(r), g: lift(g), b: lift(b) }
+        }
+        _ => Color::White,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hello_rust::clock::Clock;
+    use hello_rust::test_support::{mock_clock, small_game, snake};
+
+    #[test]
+    fn mirror_mode_flips_the_view_and_controls_but_not_the_board() {
+        let mut game = small_game(Point { x: 8, y: 1 });
+        game.settings.mirror = true;
+        let board = game.render_ascii();
+        game.level_up();
+        assert_ne!(game.flip, (false, false));
+        game.obstacles.clear();
+        assert_eq!(game.render_ascii(), board);
+
+        // Flipped left-right: the food (top right on the board) is drawn top left,
+        // and pressing Left moves the snake towards it by going Right on the board.
+        game.flip = (true, false);
+        assert_eq!(game.move_to(game.food), MoveTo(1, 1));
+        assert_eq!(game.unflip(Direction::Left), Direction::Right);
+        assert_eq!(game.unflip(Direction::Up), Direction::Up);
+
+        game.flip = (true, true);
+        assert_eq!(game.move_to(game.food), MoveTo(1, 4));
+        assert_eq!(game.unflip(Direction::Down), Direction::Up);
+    }
+
+    #[test]
+    fn toasts_pop_up_on_events_and_expire() {
+        let mut game = small_game(Point { x: 6, y: 3 });
+        game.update();
+        // Toasts are off by default.
+        assert!(game.toasts.is_empty());
+
+        game.settings.toasts = true;
+        game.settings.level_every = 1;
+        game.food = Point { x: 7, y: 3 };
+        // Eating spawns pickups at random; keep them off the next cell.
+        (game.bomb, game.hourglass, game.reverser) = (None, None, None);
+        game.update();
+        let texts: Vec<&str> = game.toasts.iter().map(|toast| toast.text.as_str()).collect();
+        assert_eq!(texts, ["+1", "LEVEL 2"]);
+        assert_eq!(game.toasts[0].pos, Point { x: 7, y: 3 });
+
+        let mut out = Vec::new();
+        game.draw(&mut out).unwrap();
+        assert!(String::from_utf8_lossy(&out).contains("LEVEL 2"));
+
+        // Once a toast has been up for `TOAST_TIME`, the timers clear it away.
+        let clock = mock_clock(&mut game);
+        game.toasts[0].since = clock.now();
+        game.toasts[1].since = clock.now() + TOAST_TIME / 2;
+        clock.advance(TOAST_TIME);
+        game.update_timers(Duration::ZERO);
+        assert_eq!(game.toasts.len(), 1);
+    }
+
+    #[test]
+    fn recap_plays_the_run_back_on_the_game_over_screen() {
+        let mut game = Game::new(WIDTH, HEIGHT, None, Some(Point { x: 30, y: 10 })).unwrap();
+        game.settings.recap = true;
+        while !game.is_over() {
+            game.update();
+            game.record_recap();
+        }
+        // The last picture shows the snake's head against the right-hand wall.
+        let frame = game.recap.frame_at(Duration::from_secs(3600)).unwrap().clone();
+        assert_eq!(frame.len(), HEIGHT as usize / 2);
+        assert!(frame.iter().any(|row| row.ends_with("o")));
+
+        let mut out = Vec::new();
+        game.draw_game_over(&mut out, "").unwrap();
+        let screen = String::from_utf8_lossy(&out);
+        assert!(screen.contains("█") && screen.contains("GAME OVER"));
+
+        // Off by default.
+        let mut game = small_game(Point { x: 8, y: 1 });
+        game.update();
+        game.record_recap();
+        assert!(game.recap.frame_at(Duration::ZERO).is_none());
+    }
+
+    #[test]
+    fn score_colors_rate_the_run_against_the_best() {
+        assert_eq!(result_color(11, 10), GOLD);
+        assert_eq!(result_color(5, 10), Color::Green);
+        assert_eq!(result_color(4, 10), Color::DarkGrey);
+        assert_eq!(result_color(0, 0), Color::DarkGrey);
+
+        let mut game = small_game(Point { x: 6, y: 3 });
+        while !game.is_over() {
+            game.update();
+        }
+        let screen = |game: &Game| {
+            let mut out = Vec::new();
+            game.draw_game_over(&mut out, "").unwrap();
+            String::from_utf8_lossy(&out).into_owned()
+        };
+        assert!(screen(&game).contains("NEW RECORD!"));
+        // (The food spawns at random, so the snake may have eaten more than one.)
+        game.best = game.leaderboard_score();
+        assert!(!screen(&game).contains("NEW RECORD!"));
+        game.best = 0;
+        game.settings.score_colors = false;
+        assert!(!screen(&game).contains("NEW RECORD!"));
+    }
+
+    #[test]
+    fn versus_shows_a_score_for_each_player_and_the_winner() {
+        let mut game = Game::new(12, 8, None, Some(Point { x: 3, y: 3 })).unwrap();
+        game.settings.versus = true;
+        game.add_mirror_snake().unwrap();
+        game.snakes[1].apples = 2;
+        let mut out = Vec::new();
+        game.draw(&mut out).unwrap();
+        assert!(String::from_utf8_lossy(&out).contains(" P1: 0  P2: 2  Level: 1 "));
+
+        game.snakes[0].crashed = true;
+        game.snakes[1].crashed = true;
+        game.end_game();
+        let mut out = Vec::new();
+        game.draw_game_over(&mut out, "").unwrap();
+        let screen = String::from_utf8_lossy(&out);
+        assert!(screen.contains("PLAYER 2 WINS!") && screen.contains("Final Score: 0 - 2"));
+    }
+
+    #[test]
+    fn the_game_over_hint_names_the_bound_keys() {
+        assert_eq!(game_over_hint(&KeyBindings::default()), "R to Retry, L for Scores, Q to Quit");
+        let keys: KeyBindings = serde_json::from_str(r#"{ "quit": ["Esc"], "restart": [] }"#).unwrap();
+        assert_eq!(game_over_hint(&keys), "L for Scores, Esc to Quit");
+    }
+
+    #[test]
+    fn local_grid_dots_the_empty_cells_near_the_head() {
+        let mut game = small_game(Point { x: 5, y: 1 });
+        let cells = game.local_grid();
+        // Within three steps of the head at (5,3), not on the snake or the food.
+        assert!(cells.iter().all(|&p| p.distance(Point { x: 5, y: 3 }) <= 3));
+        assert!(cells.contains(&Point { x: 6, y: 1 }) && cells.contains(&Point { x: 2, y: 3 }));
+        assert!(!cells.contains(&Point { x: 5, y: 1 }) && !cells.contains(&Point { x: 4, y: 3 }));
+        assert!(!cells.contains(&Point { x: 1, y: 1 }));
+
+        let mut out = Vec::new();
+        game.draw(&mut out).unwrap();
+        assert!(!String::from_utf8_lossy(&out).contains('·'));
+        game.settings.local_grid = true;
+        let mut out = Vec::new();
+        game.draw(&mut out).unwrap();
+        assert!(String::from_utf8_lossy(&out).contains('·'));
+    }
+
+    #[test]
+    fn hints_tell_greed_from_safety() {
+        // Out in the open, heading for the food is safe.
+        let game = small_game(Point { x: 7, y: 3 });
+        assert_eq!(game.hints(), (Some(Point { x: 6, y: 3 }), Some(Point { x: 6, y: 3 })));
+
+        // Food in a pocket: reaching up for it would leave no way back to the tail.
+        let mut game = small_game(Point { x: 3, y: 1 });
+        game.place_snake(Point { x: 3, y: 2 }).unwrap();
+        game.obstacles.extend([Point { x: 2, y: 1 }, Point { x: 4, y: 1 }]);
+        let (greedy, safe) = game.hints();
+        assert_eq!(greedy, Some(Point { x: 3, y: 1 }));
+        assert!([Point { x: 4, y: 2 }, Point { x: 3, y: 3 }].contains(&safe.unwrap()));
+
+        game.settings.hint = true;
+        let mut out = Vec::new();
+        game.draw(&mut out).unwrap();
+        let screen = String::from_utf8_lossy(&out);
+        assert!(screen.contains('◇'));
+    }
+
+    #[test]
+    fn walls_use_the_theme_glyph_for_each_edge() {
+        let mut game = small_game(Point { x: 8, y: 1 });
+        game.theme = theme::THEMES.iter().position(|theme| theme.name == "retro").unwrap();
+        let glyph = |x, y| game.wall_glyph(Point { x, y });
+        assert_eq!((glyph(0, 0), glyph(9, 5)), ('+', '+'));
+        assert_eq!((glyph(4, 0), glyph(4, 5)), ('=', '='));
+        assert_eq!((glyph(0, 2), glyph(9, 2)), ('|', '|'));
+    }
+
+    #[test]
+    fn pausing_stops_the_timers() {
+        let mut game = small_game(Point { x: 8, y: 1 });
+        game.settings.time_attack = true;
+        let clock = mock_clock(&mut game);
+        clock.advance(Duration::from_secs(5));
+        game.set_paused(true);
+        clock.advance(Duration::from_secs(60));
+        game.update_timers(Duration::from_secs(60));
+        assert_eq!(game.time_left, TIME_ATTACK_START);
+        assert_eq!(game.elapsed(), Duration::from_secs(5));
+
+        let mut out = Vec::new();
+        game.draw(&mut out).unwrap();
+        assert!(String::from_utf8_lossy(&out).contains("PAUSED"));
+
+        // Resuming picks the timer up where it left off.
+        game.set_paused(false);
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(game.elapsed(), Duration::from_secs(7));
+    }
+
+    #[test]
+    fn speed_tint_runs_from_blue_when_slow_to_red_when_fast() {
+        let mut game = small_game(Point { x: 1, y: 1 });
+        game.settings.speed_tint = true;
+        // A constant speed keeps the theme's color.
+        assert_eq!(game.speed_tint(), None);
+
+        game.slow_motion = SLOW_MOTION_TIME;
+        assert_eq!(game.speed_tint(), Some(Color::Rgb { r: 0, g: 0, b: 255 }));
+
+        game.slow_motion = Duration::ZERO;
+        game.settings.accelerate = true;
+        let Some(Color::Rgb { r: base_red, .. }) = game.speed_tint() else { panic!("no tint") };
+        game.snakes[0].straight_ticks = MAX_ACCEL_TICKS;
+        let Some(Color::Rgb { r: fast_red, b: fast_blue, .. }) = game.speed_tint() else { panic!("no tint") };
+        assert!(fast_red > base_red);
+        assert_eq!(fast_blue, 0);
+
+        // Only with `--speed-tint`.
+        game.settings.speed_tint = false;
+        assert_eq!(game.speed_tint(), None);
+    }
+
+    #[test]
+    fn eating_sends_a_pulse_down_the_body() {
+        let mut game = small_game(Point { x: 4, y: 1 });
+        game.snakes[0].body = snake(&[(3, 1), (2, 1), (1, 1)]);
+        game.update();
+        assert_eq!(game.score(), 1);
+        assert_eq!(game.snake().pulse, Some(0));
+        // Keep the new food out of the way, so a second meal doesn't restart the pulse.
+        game.food = Point { x: 8, y: 4 };
+        // One segment further each tick, over all four segments, then it's gone.
+        let mut seen = Vec::new();
+        while let Some(i) = game.snake().pulse
+            && seen.len() < 10
+        {
+            seen.push(i);
+            game.update();
+        }
+        assert_eq!(seen, [0, 1, 2, 3]);
+        assert_eq!(brighter(Color::Rgb { r: 255, g: 0, b: 100 }), Color::Rgb { r: 255, g: 127, b: 177 });
+    }
+
+    #[test]
+    fn events_are_logged_for_the_side_panel() {
+        let mut game = Game::new(20, 14, None, Some(Point { x: 1, y: 1 })).unwrap();
+        game.hourglass = Some(Point { x: 11, y: 7 });
+        game.update();
+        assert_eq!(game.messages[0].0, "Slow motion!");
+        for _ in 0..MAX_MESSAGES {
+            game.level_up();
+        }
+        let log: Vec<&str> = game.messages.iter().map(|(text, _)| text.as_str()).collect();
+        // The log is full, so the oldest message ("Slow motion!") has been dropped.
+        assert_eq!(log, ["Level 2!", "Level 3!", "Level 4!", "Level 5!", "Level 6!", "Level 7!"]);
+
+        let mut out = Vec::new();
+        game.best = 3;
+        game.draw_panel(&mut out).unwrap();
+        let text = String::from_utf8_lossy(&out);
+        assert!(text.contains("Best:   3"));
+        assert!(text.contains("Level 7!"));
+    }
+}
@@ -0,0 +1,200 @@
+// Replays: recording a run (`--record FILE`) and playing it back (`--replay FILE`).
+//
+// A replay doesn't store any frames. It stores what's needed to play the run
+// again: the starting board (as a save, see `save.rs`), the rules, the random
+// seed, and the turns each snake made, tick by tick. Given the same seed and the
+// same turns on the same ticks, `Game::update` plays out exactly the same way,
+// so the file stays small however long the run was.
+//
+// A recorded run that sets a new high score is also kept as the "best run"
+// (`best_path`), for `--watch-best` to play back.
+//
+// The file is JSON, written when the game ends:
+//
+//   {"seed":42,"settings":{...},"start":{...},"turns":[[5,0,"Up"],[9,0,"Left"]]}
+//
+// Each turn is [tick, player, direction]: the turn was used on that tick (the
+// game's first move is tick 1). A few things happen on the clock rather than on
+// ticks (the time attack countdown, power-up timers), and switching wrap or ghost
+// mode mid-run with 'b' or 'g' isn't recorded, so runs that depend on those may
+// play out a little differently.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crossterm::event::KeyEvent;
+use rand::{SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Direction, Game, Settings,
+    game_loop::Input,
+    keys::KeyBindings,
+    save::SaveGame,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    // The random numbers start from this seed, right at the start of the run.
+    seed: u64,
+    settings: Settings,
+    start: SaveGame,
+    // Every turn as (tick, player, direction), in tick order.
+    turns: Vec<(u64, usize, Direction)>,
+}
+
+// Where the best run's replay is kept, next to the leaderboard.
+pub fn best_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("hello_rust").join("best_run.json"))
+}
+
+impl Replay {
+    // Starts recording `game`, which hasn't made its first move yet. The random
+    // numbers are seeded afresh from `seed` (or a random seed), so that the replay
+    // can seed them the same way. A game built with `--seed` already starts from
+    // its seed (see `build_game`), so recording it changes nothing.
+    pub fn record(game: &mut Game, seed: Option<u64>) -> Replay {
+        let seed = seed.unwrap_or_else(rand::random);
+        game.rng = StdRng::seed_from_u64(seed);
+        Replay {
+            seed,
+            settings: game.settings.clone(),
+            start: SaveGame::from_game(game),
+            turns: Vec::new(),
+        }
+    }
+
+    // Plays one tick of the recorded game, noting the turns it used. A tick uses
+    // up turns from the front of each snake's queue: the one it makes, and any
+    // it drops on the way (see `Game::update`). Queuing those same turns again
+    // on the same tick makes the replay do exactly the same.
+    pub fn update(&mut self, game: &mut Game) {
+        let queued: Vec<Vec<Direction>> = game
+            .snakes
+            .iter()
+            .map(|snake| snake.queued_turns.iter().copied().collect())
+            .collect();
+        game.update();
+        for (player, (before, snake)) in queued.iter().zip(&game.snakes).enumerate() {
+            let used = before.len().saturating_sub(snake.queued_turns.len());
+            for &direction in &before[..used] {
+                self.turns.push((game.tick, player, direction));
+            }
+        }
+    }
+
+    // Writes the replay to `path`, making its folder if need be.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string(self).expect("a replay is always valid JSON");
+        fs::write(path, json)
+    }
+
+    // Reads the replay at `path`.
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read replay {}: {}", path.display(), err))?;
+        serde_json::from_str(&text).map_err(|err| format!("bad replay {}: {}", path.display(), err))
+    }
+
+    // The game as it was when the recording started, ready to play again.
+    pub fn start_game(&self) -> Result<Game, String> {
+        let mut game = self.start.clone().into_game()?;
+        game.settings = self.settings.clone();
+        game.shields = self.settings.shields;
+        game.rng = StdRng::seed_from_u64(self.seed);
+        Ok(game)
+    }
+}
+
+// Plays a replay's turns in place of the keyboard. Keys still come from `inner`,
+// but only the ones that quit get through: retrying would start a different game.
+pub struct ReplayInput<I: Input> {
+    inner: I,
+    keys: KeyBindings,
+    turns: Vec<(u64, usize, Direction)>,
+    // How many of `turns` have been handed out already.
+    next: usize,
+}
+
+impl<I: Input> ReplayInput<I> {
+    pub fn new(inner: I, replay: Replay, keys: KeyBindings) -> ReplayInput<I> {
+        ReplayInput {
+            inner,
+            keys,
+            turns: replay.turns,
+            next: 0,
+        }
+    }
+}
+
+impl<I: Input> Input for ReplayInput<I> {
+    fn poll(&mut self, timeout: Duration) -> io::Result<Option<KeyEvent>> {
+        let key = self.inner.poll(timeout)?;
+        Ok(key.filter(|&key| self.keys.quits(key)))
+    }
+
+    fn turns(&mut self, tick: u64) -> Vec<(usize, Direction)> {
+        let start = self.next;
+        while self.turns.get(self.next).is_some_and(|&(at, _, _)| at <= tick) {
+            self.next += 1;
+        }
+        self.turns[start..self.next]
+            .iter()
+            .map(|&(_, player, direction)| (player, direction))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game_loop::testing::NoKeys;
+
+    #[test]
+    fn a_recorded_run_plays_back_the_same() {
+        // A small co-op board with pickups, so the random numbers matter.
+        let mut game = Game::new(16, 10, None, None).unwrap();
+        game.settings.coop = true;
+        game.settings.power_ups = true;
+        game.settings.wrap = true;
+        game.add_mirror_snake().unwrap();
+        let mut replay = Replay::record(&mut game, None);
+
+        // Steer in circles, a few keys at a time, sometimes more than one per tick.
+        let pattern = [Direction::Up, Direction::Left, Direction::Down, Direction::Right];
+        for tick in 0..200 {
+            if tick % 3 == 0 {
+                game.turn(0, pattern[tick / 3 % 4]);
+                game.turn(0, pattern[(tick / 3 + 1) % 4]);
+                game.turn(1, pattern[(tick / 5) % 4]);
+            }
+            replay.update(&mut game);
+            if game.is_over() {
+                break;
+            }
+        }
+        assert!(!replay.turns.is_empty());
+
+        let path = std::env::temp_dir().join(format!("hello_rust_replay_{}.json", std::process::id()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut again = loaded.start_game().unwrap();
+        let mut input = ReplayInput::new(NoKeys, loaded, KeyBindings::default());
+        while again.tick < game.tick {
+            for (player, direction) in input.turns(again.tick + 1) {
+                again.turn(player, direction);
+            }
+            again.update();
+        }
+        assert_eq!(again.render_ascii(), game.render_ascii());
+        assert_eq!((again.score, again.apples), (game.score, game.apples));
+    }
+}
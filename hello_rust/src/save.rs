@@ -0,0 +1,193 @@
+// Saving a game in progress (Ctrl+S while playing) and picking it up again
+// later with `--resume`.
+//
+// Only the board and the progress are saved: the snakes, food, obstacles,
+// score and level, and what's left of any power-up that's running. Rules like `--wrap` or `--coop` come from the command line
+// again when resuming, the same as when starting a fresh game.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{BOOST_TIME, DOUBLE_POINTS_TIME, Direction, Game, Point, PowerUp, Snake, map};
+
+// Everything needed to rebuild a `Game` where it left off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveGame {
+    width: u16,
+    height: u16,
+    snakes: Vec<Snake>,
+    food: Point,
+    bomb: Option<Point>,
+    // `#[serde(default)]` lets saves from before these pickups existed still load.
+    #[serde(default)]
+    hourglass: Option<Point>,
+    #[serde(default)]
+    reverser: Option<Point>,
+    #[serde(default)]
+    power_up: Option<(Point, PowerUp)>,
+    // What's left of a speed boost or double points that's running.
+    #[serde(default)]
+    boost: Duration,
+    #[serde(default)]
+    double_points: Duration,
+    obstacles: Vec<Point>,
+    // One-way gates, each also listed in `obstacles`.
+    #[serde(default)]
+    gates: Vec<(Point, Direction)>,
+    score: usize,
+    level: u32,
+    apples: usize,
+    // Whether ghost mode or a cheat was used (see `Game::score_multiplier`), so
+    // saving and resuming doesn't clear it.
+    #[serde(default)]
+    assisted: bool,
+}
+
+impl SaveGame {
+    // Where the save file lives by default, next to the leaderboard.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("hello_rust").join("savegame.json"))
+    }
+
+    // Takes a snapshot of `game`.
+    pub fn from_game(game: &Game) -> SaveGame {
+        let mut obstacles: Vec<Point> = game.obstacles.iter().copied().collect();
+        // A `HashSet` has no order; sorting keeps the file the same for the same board.
+        obstacles.sort_by_key(|p| (p.y, p.x));
+        let mut gates: Vec<(Point, Direction)> = game.gates.iter().map(|(&p, &d)| (p, d)).collect();
+        gates.sort_by_key(|(p, _)| (p.y, p.x));
+        SaveGame {
+            width: game.width,
+            height: game.height,
+            snakes: game.snakes.clone(),
+            food: game.food,
+            bomb: game.bomb,
+            hourglass: game.hourglass,
+            reverser: game.reverser,
+            power_up: game.power_up,
+            boost: game.boost,
+            double_points: game.double_points,
+            obstacles,
+            gates,
+            score: game.score,
+            level: game.level,
+            apples: game.apples,
+            assisted: game.assisted,
+        }
+    }
+
+    // Rebuilds the game. The file may have been edited (or damaged) by hand, so
+    // everything is checked first: a bad save gives an error, never a panic.
+    pub fn into_game(self) -> Result<Game, String> {
+        let size_ok = (map::MIN_WIDTH..=map::MAX_SIZE).contains(&(self.width as usize))
+            && (map::MIN_HEIGHT..=map::MAX_SIZE).contains(&(self.height as usize));
+        if !size_ok {
+            return Err(format!(
+                "a {}x{} board is not allowed",
+                self.width, self.height
+            ));
+        }
+        if self.snakes.is_empty() || self.snakes.iter().any(|s| s.body.is_empty()) {
+            return Err("the save has no snake".to_string());
+        }
+        if self.level == 0 {
+            return Err("the level must be at least 1".to_string());
+        }
+
+        // Start from a fresh board of the right size, then swap in the saved contents.
+        let mut game = Game::new(self.width, self.height, None, None)?;
+        let outside = self
+            .snakes
+            .iter()
+            .flat_map(|s| &s.body)
+            .chain(&self.obstacles)
+            .chain([&self.food])
+            .chain(&self.bomb)
+            .chain(&self.hourglass)
+            .chain(&self.reverser)
+            .chain(self.power_up.iter().map(|(p, _)| p))
+            .find(|&&p| !game.in_bounds(p));
+        if let Some(p) = outside {
+            return Err(format!("{},{} is outside the board", p.x, p.y));
+        }
+        game.snakes = self.snakes;
+        game.obstacles = self.obstacles.into_iter().collect();
+        game.gates = self.gates.into_iter().collect();
+        // A gate without its obstacle cell would be a hole in nothing; drop it.
+        game.forget_removed_gates();
+        game.food = self.food;
+        game.bomb = self.bomb;
+        game.hourglass = self.hourglass;
+        game.reverser = self.reverser;
+        game.power_up = self.power_up;
+        // Never more than a fresh power-up gives, whatever the file says.
+        game.boost = self.boost.min(BOOST_TIME);
+        game.double_points = self.double_points.min(DOUBLE_POINTS_TIME);
+        game.score = self.score;
+        game.level = self.level;
+        game.apples = self.apples;
+        game.assisted = self.assisted;
+        Ok(game)
+    }
+
+    // Reads a save file.
+    pub fn load(path: &Path) -> Result<SaveGame, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+        serde_json::from_str(&text)
+            .map_err(|err| format!("{} is not a valid save: {}", path.display(), err))
+    }
+
+    // Writes the save file, creating the folder if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Serializing plain numbers and lists can't fail, so `expect` is safe here.
+        let json = serde_json::to_string_pretty(self).expect("a save is always valid JSON");
+        fs::write(path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_saved_game_resumes_where_it_left_off() {
+        let mut game = Game::new(12, 8, None, Some(Point { x: 9, y: 4 })).unwrap();
+        game.obstacles.insert(Point { x: 2, y: 2 });
+        for _ in 0..3 {
+            game.update();
+        }
+        assert_eq!(game.score, 1);
+        game.double_points = Duration::from_secs(3);
+
+        let json = serde_json::to_string(&SaveGame::from_game(&game)).unwrap();
+        let resumed = serde_json::from_str::<SaveGame>(&json)
+            .unwrap()
+            .into_game()
+            .unwrap();
+        assert_eq!(resumed.render_ascii(), game.render_ascii());
+        assert_eq!((resumed.score, resumed.level), (1, 1));
+        assert_eq!(resumed.snake().direction, game.snake().direction);
+        assert_eq!(resumed.double_points, Duration::from_secs(3));
+    }
+
+    #[test]
+    fn damaged_saves_are_rejected() {
+        let game = Game::new(12, 8, None, None).unwrap();
+        let mut save = SaveGame::from_game(&game);
+        save.food = Point { x: 11, y: 3 };
+        assert!(save.into_game().is_err());
+
+        let mut save = SaveGame::from_game(&game);
+        save.snakes.clear();
+        assert!(save.into_game().is_err());
+    }
+}
@@ -0,0 +1,357 @@
+// The leaderboard: the best finished runs, saved to disk between games.
+//
+// It's stored as JSON using `serde`, a popular crate that can turn Rust structs
+// into text formats (and back) for us. `#[derive(Serialize, Deserialize)]`
+// is all it takes to make a struct saveable.
+//
+// A run that makes the leaderboard gets a name, typed into a small text box
+// (`NameEntry`) on the game over screen. The leaderboard screen (`draw_screen`)
+// shows the table from the main menu, or with L once the game is over.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crossterm::{
+    QueueableCommand,
+    cursor::MoveTo,
+    event::{KeyCode, KeyEvent, KeyModifiers},
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{Clear, ClearType},
+};
+use serde::{Deserialize, Serialize};
+
+// How many runs the leaderboard keeps.
+pub const MAX_ENTRIES: usize = 10;
+
+// How many characters a player's name can have.
+pub const MAX_NAME_LEN: usize = 10;
+
+// One finished run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    // Who played it; empty until they've typed their name (and for older files,
+    // which `#[serde(default)]` lets load without one).
+    #[serde(default)]
+    pub name: String,
+    pub score: usize,
+    pub level: u32,
+    pub time_ms: u64,   // How long the run lasted, in milliseconds.
+    pub timestamp: u64, // When it finished, in seconds since 1970-01-01 (the "Unix epoch").
+}
+
+impl Entry {
+    // An entry for a run that finished just now.
+    pub fn now(score: usize, level: u32, time_ms: u64) -> Entry {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Entry {
+            name: String::new(),
+            score,
+            level,
+            time_ms,
+            timestamp,
+        }
+    }
+}
+
+// The saved runs, best first.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub entries: Vec<Entry>,
+}
+
+impl Leaderboard {
+    // Where the leaderboard lives by default, e.g. `~/.local/share/hello_rust/leaderboard.json`
+    // on Linux. The `dirs` crate knows the right folder on each platform.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("hello_rust").join("leaderboard.json"))
+    }
+
+    // Reads the leaderboard from `path`.
+    // A missing or corrupt file just gives an empty leaderboard: losing old scores
+    // is annoying, but it should never stop you from playing.
+    pub fn load(path: &Path) -> Leaderboard {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    // The top score, or 0 if nothing has been recorded yet.
+    pub fn best_score(&self) -> usize {
+        // The file may have been edited by hand (or written by an older version),
+        // so don't count on it being in order.
+        self.entries.iter().map(|entry| entry.score).max().unwrap_or(0)
+    }
+
+    // Writes the leaderboard to `path`, creating the folder if needed.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_json())
+    }
+
+    // Adds a run, keeping the list sorted and trimmed to `MAX_ENTRIES`.
+    // Returns the run's position (0 = first place), or `None` if it didn't make the cut.
+    pub fn record(&mut self, entry: Entry) -> Option<usize> {
+        // Higher scores first; on a tie, the faster run wins. Among exact ties the
+        // older run keeps its place, so the new entry goes after all of them.
+        let rank = self
+            .entries
+            .iter()
+            .position(|e| {
+                (entry.score, std::cmp::Reverse(entry.time_ms))
+                    > (e.score, std::cmp::Reverse(e.time_ms))
+            })
+            .unwrap_or(self.entries.len());
+        if rank >= MAX_ENTRIES {
+            return None;
+        }
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_ENTRIES);
+        Some(rank)
+    }
+
+    // Names the run `entry` (as it was recorded, still without a name). Returns
+    // `false` if it isn't on the leaderboard (any more).
+    pub fn set_name(&mut self, entry: &Entry, name: &str) -> bool {
+        match self.entries.iter_mut().find(|e| *e == entry) {
+            Some(e) => {
+                e.name = name.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The leaderboard as pretty-printed JSON (the same format as the saved file).
+    pub fn to_json(&self) -> String {
+        // Serializing plain numbers and strings can't fail, so `expect` is safe here.
+        serde_json::to_string_pretty(self).expect("leaderboard is always valid JSON")
+    }
+
+    // The leaderboard as a human-readable table.
+    pub fn format_table(&self) -> String {
+        if self.entries.is_empty() {
+            return "No scores yet - go play a game!\n".to_string();
+        }
+        let mut out = format!(
+            "{:>2}  {:<w$}  {:>5}  {:>5}  {:>9}  {}\n",
+            "#",
+            "Name",
+            "Score",
+            "Level",
+            "Time",
+            "Date",
+            w = MAX_NAME_LEN
+        );
+        for (i, entry) in self.entries.iter().enumerate() {
+            // A run nobody named shows as a dash.
+            let name = if entry.name.is_empty() { "-" } else { &entry.name };
+            out.push_str(&format!(
+                "{:>2}  {:<w$}  {:>5}  {:>5}  {:>9}  {}\n",
+                i + 1,
+                name,
+                entry.score,
+                entry.level,
+                crate::format_time(std::time::Duration::from_millis(entry.time_ms)),
+                format_date(entry.timestamp),
+                w = MAX_NAME_LEN
+            ));
+        }
+        out
+    }
+
+    // Draws the leaderboard screen: the table, under a title, on a cleared screen.
+    pub fn draw_screen(&self, out: &mut impl Write) -> io::Result<()> {
+        out.queue(Clear(ClearType::All))?
+            .queue(MoveTo(2, 1))?
+            .queue(SetForegroundColor(Color::Green))?
+            .queue(Print("HIGH SCORES"))?
+            .queue(SetForegroundColor(Color::White))?;
+        let table = self.format_table();
+        for (row, line) in table.lines().enumerate() {
+            out.queue(MoveTo(2, 3 + row as u16))?.queue(Print(line))?;
+        }
+        out.queue(MoveTo(2, 4 + table.lines().count() as u16))?
+            .queue(SetForegroundColor(Color::DarkGrey))?
+            .queue(Print("Press any key to go back"))?
+            .queue(ResetColor)?;
+        Ok(())
+    }
+}
+
+// What a key press did to a `NameEntry`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Typed {
+    // Still typing.
+    Editing,
+    // Enter: this is the name.
+    Done(String),
+    // Esc (or Ctrl+C): leave the run without a name.
+    Cancelled,
+}
+
+// The text box for naming a run that has just made the leaderboard.
+pub struct NameEntry {
+    // The run as it was recorded, to find it again (see `Leaderboard::set_name`).
+    pub entry: Entry,
+    name: String,
+}
+
+impl NameEntry {
+    pub fn new(entry: Entry) -> NameEntry {
+        NameEntry {
+            entry,
+            name: String::new(),
+        }
+    }
+
+    // Types `key` into the box: letters, digits, spaces, '-' and '_' are added
+    // (up to `MAX_NAME_LEN`), and Backspace rubs out the last one.
+    pub fn key(&mut self, key: KeyEvent) -> Typed {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return match key.code {
+                KeyCode::Char('c') => Typed::Cancelled,
+                _ => Typed::Editing,
+            };
+        }
+        match key.code {
+            KeyCode::Enter => return Typed::Done(self.name.trim().to_string()),
+            KeyCode::Esc => return Typed::Cancelled,
+            KeyCode::Backspace => {
+                self.name.pop();
+            }
+            KeyCode::Char(c)
+                if (c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+                    && self.name.chars().count() < MAX_NAME_LEN =>
+            {
+                self.name.push(c);
+            }
+            _ => {}
+        }
+        Typed::Editing
+    }
+
+    // The line to show under the board while the player types.
+    pub fn prompt(&self) -> String {
+        format!("New high score! Your name: {}_  (Enter to save, Esc to skip)", self.name)
+    }
+}
+
+// Turns seconds since 1970 into a `YYYY-MM-DD` date (UTC).
+// This is the standard "days to civil date" calculation, so we don't need a date crate.
+fn format_date(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64 + 719_468; // Shift the epoch to 0000-03-01.
+    let era = days.div_euclid(146_097); // 400-year cycles.
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153; // Month, counting from March.
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: usize, time_ms: u64) -> Entry {
+        Entry {
+            name: String::new(),
+            score,
+            level: 1,
+            time_ms,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn record_keeps_the_best_runs_in_order() {
+        let mut board = Leaderboard::default();
+        assert_eq!(board.best_score(), 0);
+        assert_eq!(board.record(entry(5, 1000)), Some(0));
+        assert_eq!(board.record(entry(9, 1000)), Some(0));
+        assert_eq!(board.record(entry(5, 500)), Some(1)); // Same score, but faster.
+        let scores: Vec<_> = board.entries.iter().map(|e| (e.score, e.time_ms)).collect();
+        assert_eq!(scores, [(9, 1000), (5, 500), (5, 1000)]);
+        assert_eq!(board.best_score(), 9);
+
+        for _ in 0..MAX_ENTRIES {
+            board.record(entry(20, 0));
+        }
+        assert_eq!(board.entries.len(), MAX_ENTRIES);
+        assert_eq!(board.record(entry(1, 0)), None);
+    }
+
+    #[test]
+    fn exports_as_json_and_table() {
+        let mut board = Leaderboard::default();
+        board.record(Entry {
+            name: "Ada".to_string(),
+            score: 12,
+            level: 3,
+            time_ms: 61_250,
+            timestamp: 1_700_000_000,
+        });
+
+        let parsed: Leaderboard = serde_json::from_str(&board.to_json()).unwrap();
+        assert_eq!(parsed, board);
+        assert_eq!(
+            board.format_table(),
+            " #  Name        Score  Level       Time  Date\n 1  Ada            12      3  01:01.250  2023-11-14\n"
+        );
+    }
+
+    #[test]
+    fn a_new_entry_gets_the_name_typed_for_it() {
+        let mut board = Leaderboard::default();
+        board.record(entry(3, 1000));
+        let mut name = NameEntry::new(entry(7, 1000));
+        board.record(name.entry.clone());
+
+        // Only name characters are typed, and no more than fit.
+        let mut typed = Typed::Editing;
+        for code in "Bo!b".chars().map(KeyCode::Char).chain([KeyCode::Backspace]) {
+            typed = name.key(KeyEvent::from(code));
+        }
+        assert_eq!(typed, Typed::Editing);
+        assert!(name.prompt().contains("Your name: Bo_"));
+        for _ in 0..20 {
+            name.key(KeyEvent::from(KeyCode::Char('z')));
+        }
+        assert_eq!(
+            name.key(KeyEvent::from(KeyCode::Enter)),
+            Typed::Done("Bozzzzzzzz".to_string())
+        );
+
+        assert!(board.set_name(&name.entry, "Bob"));
+        assert_eq!(board.entries[0].name, "Bob");
+        assert_eq!(board.entries[1].name, "");
+        // Once named, it isn't the nameless entry any more.
+        assert!(!board.set_name(&name.entry, "Bob"));
+        assert_eq!(name.key(KeyEvent::from(KeyCode::Esc)), Typed::Cancelled);
+    }
+
+    #[test]
+    fn corrupt_or_missing_files_load_as_empty() {
+        let dir = std::env::temp_dir().join(format!("hello_rust_scores_{}", std::process::id()));
+        let path = dir.join("leaderboard.json");
+        assert_eq!(Leaderboard::load(&path), Leaderboard::default());
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "{ not json").unwrap();
+        assert_eq!(Leaderboard::load(&path), Leaderboard::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,306 @@
+// Diff-based drawing: only the cells that changed since the last frame are sent
+// to the terminal.
+//
+// Clearing the whole screen and drawing everything again on every frame makes
+// some terminals flicker: for a moment, they show the empty screen. So the
+// renderer draws each frame into memory first, exactly as it would draw it on
+// the terminal. `Screen` reads those commands back into a grid of cells (which
+// character is where, in which colors) and compares it with the grid shown
+// last. Only the cells that differ are sent to the terminal. When the terminal
+// changes size, or something else may have messed up what's on it, the next
+// frame is a full redraw instead.
+
+use std::io::{self, Write};
+
+use crossterm::{
+    QueueableCommand,
+    cursor::MoveTo,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{Clear, ClearType},
+};
+
+use crate::theme;
+
+// One character cell on the terminal. `None` colors are the terminal's defaults.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Cell {
+    symbol: char,
+    foreground: Option<Color>,
+    background: Option<Color>,
+}
+
+// An empty cell. The right half of a wide character (like an emoji) is a cell
+// too, holding `WIDE_RIGHT_HALF`, so the grid lines up with the screen.
+const BLANK: Cell = Cell {
+    symbol: ' ',
+    foreground: None,
+    background: None,
+};
+const WIDE_RIGHT_HALF: char = '\0';
+
+// A whole screen of cells, row by row.
+#[derive(Debug, Clone, PartialEq)]
+struct Grid {
+    columns: u16,
+    rows: u16,
+    cells: Vec<Cell>,
+}
+
+impl Grid {
+    fn blank(columns: u16, rows: u16) -> Grid {
+        Grid {
+            columns,
+            rows,
+            cells: vec![BLANK; columns as usize * rows as usize],
+        }
+    }
+
+    fn index(&self, x: u16, y: u16) -> Option<usize> {
+        (x < self.columns && y < self.rows).then(|| y as usize * self.columns as usize + x as usize)
+    }
+
+    // Plays back the escape codes and text a frame was drawn with, as a terminal
+    // `columns` x `rows` big would, starting from a blank screen. Only what our
+    // drawing code uses is understood: moving the cursor, setting and resetting
+    // colors, and clearing the screen. Anything else is skipped.
+    fn from_frame(frame: &str, columns: u16, rows: u16) -> Grid {
+        let mut grid = Grid::blank(columns, rows);
+        let (mut x, mut y) = (0u16, 0u16);
+        let (mut foreground, mut background) = (None, None);
+        let mut chars = frame.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1b' if chars.peek() == Some(&'[') => {
+                    chars.next();
+                    // The numbers, then the letter that says what to do with them.
+                    let mut params = String::new();
+                    let mut command = None;
+                    for c in chars.by_ref() {
+                        if c.is_ascii_alphabetic() {
+                            command = Some(c);
+                            break;
+                        }
+                        params.push(c);
+                    }
+                    let numbers: Vec<u16> = params.split(';').map(|n| n.parse().unwrap_or(0)).collect();
+                    match command {
+                        // Cursor position, counted from 1.
+                        Some('H') => {
+                            y = numbers[0].saturating_sub(1);
+                            x = numbers.get(1).copied().unwrap_or(1).saturating_sub(1);
+                        }
+                        Some('m') => set_colors(&numbers, &mut foreground, &mut background),
+                        Some('J') => grid = Grid::blank(columns, rows),
+                        _ => {}
+                    }
+                }
+                // A window title or other "operating system command", up to the bell.
+                '\x1b' if chars.peek() == Some(&']') => {
+                    for c in chars.by_ref() {
+                        if c == '\x07' {
+                            break;
+                        }
+                    }
+                }
+                '\r' => x = 0,
+                '\n' => y += 1,
+                c if c.is_control() => {}
+                symbol => {
+                    let cell = Cell {
+                        symbol,
+                        foreground,
+                        background,
+                    };
+                    if let Some(i) = grid.index(x, y) {
+                        grid.cells[i] = cell;
+                    }
+                    x = x.saturating_add(1);
+                    if !theme::is_single_width(symbol) {
+                        if let Some(i) = grid.index(x, y) {
+                            grid.cells[i] = Cell {
+                                symbol: WIDE_RIGHT_HALF,
+                                ..cell
+                            };
+                        }
+                        x = x.saturating_add(1);
+                    }
+                }
+            }
+        }
+        grid
+    }
+}
+
+// Applies a "select graphic rendition" command (`ESC [ ... m`), as written by
+// crossterm's `SetForegroundColor`, `SetBackgroundColor` and `ResetColor`.
+fn set_colors(numbers: &[u16], foreground: &mut Option<Color>, background: &mut Option<Color>) {
+    let mut numbers = numbers.iter().copied();
+    while let Some(n) = numbers.next() {
+        match n {
+            0 => (*foreground, *background) = (None, None),
+            39 => *foreground = None,
+            49 => *background = None,
+            38 | 48 => {
+                let color = match numbers.next() {
+                    Some(5) => numbers.next().map(|n| Color::AnsiValue(n as u8)),
+                    Some(2) => match (numbers.next(), numbers.next(), numbers.next()) {
+                        (Some(r), Some(g), Some(b)) => Some(Color::Rgb {
+                            r: r as u8,
+                            g: g as u8,
+                            b: b as u8,
+                        }),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                if n == 38 {
+                    *foreground = color;
+                } else {
+                    *background = color;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// What's on the terminal, so the next frame can be sent as just the changes.
+#[derive(Default)]
+pub struct Screen {
+    // The grid last sent, or `None` if the next frame has to be drawn in full.
+    shown: Option<Grid>,
+}
+
+impl Screen {
+    // Makes the next frame a full redraw, after something else drew on the
+    // terminal (say, the shell while the game was suspended).
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    // Sends `frame` (what was drawn for this frame, escape codes and all) to `out`:
+    // the cells that changed since the last frame, or everything if the terminal
+    // is now a different `size` (columns, rows) or the screen was invalidated.
+    pub fn show(&mut self, frame: &[u8], size: (u16, u16), out: &mut impl Write) -> io::Result<()> {
+        let (columns, rows) = size;
+        let grid = Grid::from_frame(&String::from_utf8_lossy(frame), columns, rows);
+        let before = match self.shown.take() {
+            Some(shown) if (shown.columns, shown.rows) == size => shown,
+            _ => {
+                out.queue(Clear(ClearType::All))?;
+                Grid::blank(columns, rows)
+            }
+        };
+
+        // Where the cursor is and which colors are set, so we only send what changes.
+        let mut cursor = None;
+        let mut colors = None;
+        for y in 0..rows {
+            for x in 0..columns {
+                let i = y as usize * columns as usize + x as usize;
+                let cell = grid.cells[i];
+                if cell == before.cells[i] || cell.symbol == WIDE_RIGHT_HALF {
+                    continue;
+                }
+                if cursor != Some((x, y)) {
+                    out.queue(MoveTo(x, y))?;
+                }
+                if colors != Some((cell.foreground, cell.background)) {
+                    out.queue(SetForegroundColor(cell.foreground.unwrap_or(Color::Reset)))?
+                        .queue(SetBackgroundColor(cell.background.unwrap_or(Color::Reset)))?;
+                    colors = Some((cell.foreground, cell.background));
+                }
+                out.queue(Print(cell.symbol))?;
+                let width = if theme::is_single_width(cell.symbol) { 1 } else { 2 };
+                cursor = Some((x + width, y));
+            }
+        }
+        if colors.is_some() {
+            out.queue(ResetColor)?;
+        }
+        self.shown = Some(grid);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Draws `frame` on `screen` and returns what was sent to the terminal.
+    fn show(screen: &mut Screen, frame: &[u8], size: (u16, u16)) -> String {
+        let mut out = Vec::new();
+        screen.show(frame, size, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn only_changed_cells_are_sent() {
+        let mut frame = Vec::new();
+        frame
+            .queue(Clear(ClearType::All))
+            .unwrap()
+            .queue(MoveTo(1, 0))
+            .unwrap()
+            .queue(SetForegroundColor(Color::Green))
+            .unwrap()
+            .queue(Print("Oo"))
+            .unwrap()
+            .queue(MoveTo(0, 1))
+            .unwrap()
+            .queue(SetBackgroundColor(Color::Rgb { r: 28, g: 28, b: 28 }))
+            .unwrap()
+            .queue(Print("*"))
+            .unwrap();
+
+        // The first frame is drawn in full, on a cleared screen.
+        let mut screen = Screen::default();
+        let first = show(&mut screen, &frame, (4, 2));
+        assert!(first.starts_with("\x1b[2J"));
+        assert!(first.contains("Oo") && first.contains('*'));
+
+        // The same frame again sends nothing at all.
+        assert_eq!(show(&mut screen, &frame, (4, 2)), "");
+
+        // A moved snake sends just the cells that changed: the new head and the
+        // cell it left, which is blank now.
+        let mut moved = frame.clone();
+        moved
+            .queue(ResetColor)
+            .unwrap()
+            .queue(MoveTo(1, 0))
+            .unwrap()
+            .queue(Print(" "))
+            .unwrap()
+            .queue(SetForegroundColor(Color::Green))
+            .unwrap()
+            .queue(Print("oO"))
+            .unwrap();
+        let mut expected = Vec::new();
+        expected
+            .queue(MoveTo(1, 0))
+            .unwrap()
+            .queue(SetForegroundColor(Color::Reset))
+            .unwrap()
+            .queue(SetBackgroundColor(Color::Reset))
+            .unwrap()
+            .queue(Print(' '))
+            .unwrap()
+            .queue(MoveTo(3, 0))
+            .unwrap()
+            .queue(SetForegroundColor(Color::Green))
+            .unwrap()
+            .queue(SetBackgroundColor(Color::Reset))
+            .unwrap()
+            .queue(Print('O'))
+            .unwrap()
+            .queue(ResetColor)
+            .unwrap();
+        assert_eq!(show(&mut screen, &moved, (4, 2)), String::from_utf8(expected).unwrap());
+
+        // A resize, or an invalidated screen, draws everything again.
+        assert!(show(&mut screen, &moved, (5, 2)).starts_with("\x1b[2J"));
+        screen.invalidate();
+        assert!(show(&mut screen, &moved, (5, 2)).starts_with("\x1b[2J"));
+    }
+}
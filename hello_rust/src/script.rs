@@ -0,0 +1,161 @@
+// Scripted playthroughs (`--script FILE`): steer the snake from a text file
+// instead of the keyboard, for demos, recordings and regression runs.
+//
+// Each line is a tick number and a direction; the turn is made on that tick
+// (the game's first move is tick 1). Blank lines and `#` comments are skipped:
+//
+//   # Round the top-left corner, then head home.
+//   t5 UP
+//   t9 LEFT
+//   t12 DOWN
+//
+// Directions are UP, DOWN, LEFT and RIGHT, and with `--diagonal` also UPLEFT,
+// UPRIGHT, DOWNLEFT and DOWNRIGHT (in any case). A script always steers player one.
+
+use std::{fs, io, path::Path, time::Duration};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{Direction, game_loop::Input};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    // Every turn with its tick, in tick order.
+    steps: Vec<(u64, Direction)>,
+    // How many of `steps` have been handed out already.
+    next: usize,
+}
+
+impl Script {
+    // Parses a script, or explains which line is wrong.
+    pub fn parse(text: &str) -> Result<Script, String> {
+        let mut steps = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            // Everything after a `#` is a comment.
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bad = |what: &str| format!("line {}: {} in {:?}", number + 1, what, line);
+            let mut words = line.split_whitespace();
+            let tick = words
+                .next()
+                .and_then(|word| word.strip_prefix('t'))
+                .and_then(|tick| tick.parse::<u64>().ok())
+                .filter(|&tick| tick > 0)
+                .ok_or_else(|| bad("expected a tick like t100"))?;
+            let direction = words
+                .next()
+                .and_then(Direction::from_name)
+                .ok_or_else(|| bad("expected a direction like UP"))?;
+            if words.next().is_some() {
+                return Err(bad("unexpected text after the direction"));
+            }
+            steps.push((tick, direction));
+        }
+        // A stable sort keeps turns on the same tick in the order they were written.
+        steps.sort_by_key(|&(tick, _)| tick);
+        Ok(Script { steps, next: 0 })
+    }
+
+    // Reads and parses the script at `path`.
+    pub fn load(path: &Path) -> Result<Script, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("could not read script {}: {}", path.display(), err))?;
+        Script::parse(&text).map_err(|err| format!("bad script {}: {}", path.display(), err))
+    }
+
+    // The turns due by `tick` that haven't been handed out yet.
+    pub fn due(&mut self, tick: u64) -> Vec<Direction> {
+        let start = self.next;
+        while self.steps.get(self.next).is_some_and(|&(at, _)| at <= tick) {
+            self.next += 1;
+        }
+        self.steps[start..self.next]
+            .iter()
+            .map(|&(_, direction)| direction)
+            .collect()
+    }
+}
+
+// Plays a script in place of the keyboard. Keys still come from `inner`, but
+// only the ones that quit (or retry once the game is over) get through.
+pub struct ScriptInput<I: Input> {
+    inner: I,
+    script: Script,
+    // The script as loaded, to start again from when the game is retried.
+    original: Script,
+}
+
+impl<I: Input> ScriptInput<I> {
+    pub fn new(inner: I, script: Script) -> ScriptInput<I> {
+        ScriptInput {
+            inner,
+            original: script.clone(),
+            script,
+        }
+    }
+}
+
+impl<I: Input> Input for ScriptInput<I> {
+    fn poll(&mut self, timeout: Duration) -> io::Result<Option<KeyEvent>> {
+        let key = self.inner.poll(timeout)?;
+        Ok(key.filter(|key| {
+            let ctrl_c =
+                key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            ctrl_c || matches!(key.code, KeyCode::Char('q' | 'r') | KeyCode::Esc)
+        }))
+    }
+
+    fn turns(&mut self, tick: u64) -> Vec<(usize, Direction)> {
+        // A new game counts from tick 1 again, so the script starts over too.
+        if tick == 1 {
+            self.script = self.original.clone();
+        }
+        // Scripts steer player one.
+        self.script.due(tick).into_iter().map(|direction| (0, direction)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Presses the same key whenever asked.
+    struct Holding(KeyCode);
+
+    impl Input for Holding {
+        fn poll(&mut self, _timeout: Duration) -> io::Result<Option<KeyEvent>> {
+            Ok(Some(KeyEvent::from(self.0)))
+        }
+    }
+
+    #[test]
+    fn scripts_hand_out_turns_by_tick() {
+        let text = "# a demo\nt5 up\n\nt3 LEFT  # first\nt5 RIGHT\n";
+        let script = Script::parse(text).unwrap();
+
+        let mut input = ScriptInput::new(Holding(KeyCode::Up), script);
+        assert_eq!(input.turns(1), []);
+        assert_eq!(input.turns(4), [(0, Direction::Left)]);
+        assert_eq!(input.turns(5), [(0, Direction::Up), (0, Direction::Right)]);
+        assert_eq!(input.turns(6), []);
+        // Starting over (a retry) replays the script from the top.
+        assert_eq!(input.turns(1), []);
+        assert_eq!(input.turns(3), [(0, Direction::Left)]);
+
+        // The keyboard can't steer, but can still quit.
+        assert_eq!(input.poll(Duration::ZERO).unwrap(), None);
+        let mut input = ScriptInput::new(Holding(KeyCode::Char('q')), Script::parse("").unwrap());
+        assert!(input.poll(Duration::ZERO).unwrap().is_some());
+    }
+
+    #[test]
+    fn bad_lines_are_reported() {
+        let error = |text: &str| Script::parse(text).unwrap_err();
+        assert!(error("t5 UP\n100 UP").starts_with("line 2:"));
+        assert!(error("t0 UP").contains("tick"));
+        assert!(error("t5 SIDEWAYS").contains("direction"));
+        assert!(error("t5 UP now").contains("after"));
+    }
+}
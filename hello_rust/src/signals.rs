@@ -0,0 +1,68 @@
+// Job control signals from outside the game.
+//
+// In raw mode Ctrl+Z reaches us as an ordinary key (see `TerminalRenderer::suspend`),
+// but the game can still be stopped from outside: `kill -TSTP` asks it to stop,
+// and `kill -STOP` stops it without asking. Either way `fg` carries on with a
+// SIGCONT. Without handlers the terminal would be left in raw mode on the
+// alternate screen meanwhile, and the screen not redrawn afterwards.
+//
+// A signal handler can run in the middle of anything (even while a frame is half
+// drawn), so ours only set a flag. The game loop checks the flags on every frame
+// and does the real work there.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set when SIGTSTP arrives: the game should suspend, as on Ctrl+Z.
+static STOP_ASKED: AtomicBool = AtomicBool::new(false);
+// Set when SIGCONT arrives: the game was stopped, and is running again.
+static CONTINUED: AtomicBool = AtomicBool::new(false);
+
+// `extern "C"` makes it callable from C, which is how the system calls a handler.
+#[cfg(unix)]
+extern "C" fn on_signal(signal: libc::c_int) {
+    if signal == libc::SIGTSTP {
+        STOP_ASKED.store(true, Ordering::SeqCst);
+    } else {
+        CONTINUED.store(true, Ordering::SeqCst);
+    }
+}
+
+// Installs the handlers. Until then (and on systems without job control) the
+// flags are never set.
+pub fn install() {
+    // `unsafe` because it's a call into C. The handler only stores to atomics,
+    // which is safe to do whenever a signal arrives.
+    // C takes the handler's address as a number, so the function becomes a
+    // function pointer first, and that pointer a number.
+    #[cfg(unix)]
+    unsafe {
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGTSTP, handler);
+        libc::signal(libc::SIGCONT, handler);
+    }
+}
+
+// Whether SIGTSTP has arrived since the last time we asked.
+pub fn stop_asked() -> bool {
+    // `swap` reads the flag and clears it in one step, so no signal is missed.
+    STOP_ASKED.swap(false, Ordering::SeqCst)
+}
+
+// Whether the game has been continued (SIGCONT) since the last time we asked.
+pub fn continued() -> bool {
+    CONTINUED.swap(false, Ordering::SeqCst)
+}
+
+// Stops the game until the shell continues it (`fg`). Our SIGTSTP handler would
+// only set a flag, so SIGTSTP's default action (stopping) is put back just for this.
+#[cfg(unix)]
+pub fn stop() {
+    unsafe {
+        let handler = libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+        libc::raise(libc::SIGTSTP);
+        libc::signal(libc::SIGTSTP, handler);
+    }
+    // The SIGCONT that woke us is this stop's own; whoever stopped has already
+    // planned to set the screen up again.
+    CONTINUED.store(false, Ordering::SeqCst);
+}
@@ -0,0 +1,132 @@
+// Spectator mode (`--serve ADDR`): other people can watch the game live by
+// connecting to a TCP port, e.g. `nc localhost 7777`.
+//
+// Every tick the board is rendered with `Game::render_ascii` and sent to every
+// connected spectator. Everything here is "non-blocking": a call that would
+// have to wait (for a new spectator, or for a slow one to read) returns at once
+// instead, so spectators can never stall the game itself.
+
+use std::{
+    io::{self, ErrorKind, Write},
+    net::{TcpListener, TcpStream},
+};
+
+// How many bytes may pile up for a spectator who isn't keeping up (about a
+// dozen frames of the default board) before we give up on them.
+const MAX_BACKLOG: usize = 16 * 1024;
+
+// Clears the spectator's terminal and moves the cursor to the top left, so each
+// frame draws over the last one instead of scrolling.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+struct Spectator {
+    stream: TcpStream,
+    // Bytes not sent yet, because the spectator's connection was full.
+    backlog: Vec<u8>,
+}
+
+pub struct Server {
+    listener: TcpListener,
+    spectators: Vec<Spectator>,
+}
+
+impl Server {
+    // Starts listening on `addr`, like "127.0.0.1:7777".
+    pub fn bind(addr: &str) -> io::Result<Server> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Server {
+            listener,
+            spectators: Vec::new(),
+        })
+    }
+
+    // Sends one frame to every spectator, letting in any new ones first.
+    pub fn broadcast(&mut self, frame: &str) {
+        self.accept_new();
+        let message = format!("{}{}", CLEAR_SCREEN, frame);
+        // `retain_mut` lets us change each spectator and drop the ones that return false:
+        // those who hung up, or who fell too far behind.
+        self.spectators.retain_mut(|spectator| {
+            if spectator.backlog.len() > MAX_BACKLOG {
+                return false;
+            }
+            spectator.backlog.extend_from_slice(message.as_bytes());
+            spectator.send_backlog().is_ok()
+        });
+    }
+
+    fn accept_new(&mut self) {
+        // `accept` says `WouldBlock` once nobody else is waiting to connect.
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.spectators.push(Spectator {
+                    stream,
+                    backlog: Vec::new(),
+                });
+            }
+        }
+    }
+}
+
+impl Spectator {
+    // Writes as much of the backlog as the connection will take right now.
+    // An `Err` means the connection is gone.
+    fn send_backlog(&mut self) -> io::Result<()> {
+        while !self.backlog.is_empty() {
+            match self.stream.write(&self.backlog) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(sent) => {
+                    self.backlog.drain(..sent);
+                }
+                // The connection is full; the rest waits for the next frame.
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Read, time::Duration};
+
+    #[test]
+    fn spectators_receive_each_frame() {
+        // Port 0 asks the operating system for any free port.
+        let mut server = Server::bind("127.0.0.1:0").unwrap();
+        let addr = server.listener.local_addr().unwrap();
+        let mut viewer = TcpStream::connect(addr).unwrap();
+        viewer
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        // The connection may take a moment to show up on the server's side.
+        for _ in 0..100 {
+            server.broadcast("###\n");
+            if server.spectators.len() == 1 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(server.spectators.len(), 1);
+
+        let mut received = [0; CLEAR_SCREEN.len() + 4];
+        viewer.read_exact(&mut received).unwrap();
+        assert_eq!(received, format!("{}###\n", CLEAR_SCREEN).as_bytes());
+
+        // A spectator who hangs up is dropped, without disturbing the game.
+        drop(viewer);
+        for _ in 0..100 {
+            server.broadcast("###\n");
+            if server.spectators.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(server.spectators.is_empty());
+    }
+}
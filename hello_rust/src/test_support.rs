@@ -0,0 +1,29 @@
+// Helpers shared by the library's tests and the front-end's. Like `MockClock`,
+// they're part of the library itself, because the front-end's tests can't see
+// anything inside the library's `#[cfg(test)]` code.
+
+use std::collections::VecDeque;
+
+use crate::{
+    Game, Point,
+    clock::{Clock, MockClock},
+};
+
+// A small 10x6 board with the food pinned so the output is predictable.
+pub fn small_game(food: Point) -> Game {
+    Game::new(10, 6, None, Some(food)).unwrap()
+}
+
+// Puts `game` on a clock that only moves when the test says so, starting the
+// run's timer over from the clock's time. Returns the test's copy of the clock.
+pub fn mock_clock(game: &mut Game) -> MockClock {
+    let clock = MockClock::new();
+    game.clock = Box::new(clock.clone());
+    game.started_at = clock.now();
+    clock
+}
+
+// Builds a snake from a list of (x, y) pairs, head first.
+pub fn snake(cells: &[(u16, u16)]) -> VecDeque<Point> {
+    cells.iter().map(|&(x, y)| Point { x, y }).collect()
+}
@@ -0,0 +1,156 @@
+// Color themes for the board, cycled while playing with 't'.
+//
+// Terminals differ a lot: a color that pops on one background can vanish on
+// another. Rather than make players restart with a flag, every preset lives in
+// one list and the game just remembers which one is in use.
+
+use crossterm::style::Color;
+
+// The characters the outer wall is drawn with: one for the top and bottom
+// edges, one for the left and right sides, and one for each corner.
+pub struct WallGlyphs {
+    pub top_bottom: char,
+    pub sides: char,
+    // Top-left, top-right, bottom-left, bottom-right.
+    pub corners: [char; 4],
+}
+
+// The classic solid wall.
+const SOLID: WallGlyphs = WallGlyphs {
+    top_bottom: '█',
+    sides: '█',
+    corners: ['█'; 4],
+};
+
+// Does `c` take up exactly one terminal cell? Each board cell is one column wide,
+// so a wall glyph that's wider (like most CJK characters and emoji) or has no width
+// at all (a control character) would knock everything after it out of line.
+// This covers the common wide ranges rather than the full Unicode width tables.
+pub fn is_single_width(c: char) -> bool {
+    const WIDE: [(u32, u32); 10] = [
+        (0x1100, 0x115F),
+        (0x2E80, 0xA4CF),
+        (0xAC00, 0xD7A3),
+        (0xF900, 0xFAFF),
+        (0xFE30, 0xFE4F),
+        (0xFF00, 0xFF60),
+        (0xFFE0, 0xFFE6),
+        (0x1F300, 0x1F64F),
+        (0x1F900, 0x1F9FF),
+        (0x20000, 0x3FFFD),
+    ];
+    let code = c as u32;
+    !c.is_control()
+        && !WIDE
+            .iter()
+            .any(|&(start, end)| (start..=end).contains(&code))
+}
+
+// All the colors used to draw the board.
+pub struct Theme {
+    pub name: &'static str,
+    pub wall: Color,
+    // What the outer wall is drawn with (see `WallGlyphs`).
+    pub walls: WallGlyphs,
+    // Snake colors, one per player.
+    pub snakes: [Color; 2],
+    pub food: Color,
+    pub bomb: Color,
+    pub hourglass: Color,
+    pub reverser: Color,
+    // Power-up colors, in the order of `PowerUp::ALL`.
+    pub power_ups: [Color; 3],
+    pub paint: Color,
+    // Obstacle colors, one per level, cycling when we run out.
+    // A slice (`&[Color]`) lets each theme have as many as it likes.
+    pub obstacles: &'static [Color],
+}
+
+// The presets, in the order 't' cycles through them. The first one is the default.
+pub const THEMES: [Theme; 4] = [
+    Theme {
+        name: "classic",
+        wall: Color::Grey,
+        walls: SOLID,
+        snakes: [Color::Green, Color::Cyan],
+        food: Color::Red,
+        bomb: Color::Yellow,
+        hourglass: Color::Magenta,
+        reverser: Color::White,
+        power_ups: [Color::Blue, Color::DarkGreen, Color::Yellow],
+        paint: Color::DarkBlue,
+        // Level 1 keeps the classic dark grey. Every color here stays readable on a dark
+        // terminal background and is distinct from the green snake and red food.
+        obstacles: &[
+            Color::DarkGrey,
+            Color::DarkCyan,
+            Color::DarkMagenta,
+            Color::DarkYellow,
+            Color::Blue,
+        ],
+    },
+    // Bright colors only, for washed-out or light-background terminals.
+    Theme {
+        name: "high contrast",
+        wall: Color::White,
+        walls: SOLID,
+        snakes: [Color::Green, Color::Magenta],
+        food: Color::Red,
+        bomb: Color::Yellow,
+        hourglass: Color::Cyan,
+        reverser: Color::Blue,
+        power_ups: [Color::Blue, Color::Green, Color::Yellow],
+        paint: Color::Blue,
+        obstacles: &[Color::White, Color::Cyan, Color::Yellow],
+    },
+    // No colors at all, just shades of grey (the shapes still tell things apart).
+    Theme {
+        name: "mono",
+        wall: Color::Grey,
+        walls: SOLID,
+        snakes: [Color::White, Color::Grey],
+        food: Color::White,
+        bomb: Color::White,
+        hourglass: Color::White,
+        reverser: Color::White,
+        power_ups: [Color::White; 3],
+        paint: Color::DarkGrey,
+        obstacles: &[Color::DarkGrey],
+    },
+    // Plain ASCII walls, for a retro look (and fonts without block characters).
+    Theme {
+        name: "retro",
+        wall: Color::DarkYellow,
+        walls: WallGlyphs {
+            top_bottom: '=',
+            sides: '|',
+            corners: ['+'; 4],
+        },
+        snakes: [Color::Green, Color::Cyan],
+        food: Color::Red,
+        bomb: Color::Yellow,
+        hourglass: Color::Magenta,
+        reverser: Color::White,
+        power_ups: [Color::Blue, Color::DarkGreen, Color::Yellow],
+        paint: Color::DarkBlue,
+        obstacles: &[Color::DarkGrey, Color::DarkCyan, Color::DarkMagenta],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_wall_glyph_is_one_column_wide() {
+        for theme in &THEMES {
+            let walls = &theme.walls;
+            let mut glyphs = [walls.top_bottom, walls.sides]
+                .into_iter()
+                .chain(walls.corners);
+            assert!(glyphs.all(is_single_width), "{}", theme.name);
+        }
+        assert!(is_single_width('=') && is_single_width('┌'));
+        assert!(!is_single_width('中') && !is_single_width('🐍') && !is_single_width('\t'));
+    }
+}